@@ -1,22 +1,39 @@
 use egostrategy_datahub::models::stock::StockData;
-use egostrategy_datahub::scrapers::base::StockScraper;
-use egostrategy_datahub::scrapers::sse::SSEScraper;
-use egostrategy_datahub::scrapers::szse::SZSEScraper;
+use egostrategy_datahub::models::security::{Exchange, SecurityId};
+use egostrategy_datahub::scrapers::ScraperRegistry;
+use egostrategy_datahub::scrapers::cache::ResponseCache;
 use egostrategy_datahub::services::data_service::DataService;
-use egostrategy_datahub::util::arrow_utils;
-use egostrategy_datahub::config::Config;
+use egostrategy_datahub::models::trade_date::TradeDate;
+use egostrategy_datahub::util::{self, arrow_utils, fs_utils, json_utils};
+use egostrategy_datahub::util::json_utils::JsonFormat;
+use egostrategy_datahub::util::arrow_utils::{ArrowOptions, CsvOptions};
+use egostrategy_datahub::StockDataProvider;
+use egostrategy_datahub::storage::{restore_dataset, ArchiveStore, DatasetManifest, DeltaStore, PartitionedStore, SnapshotStore};
+#[cfg(feature = "sqlite")]
+use egostrategy_datahub::storage::SqliteStore;
+use egostrategy_datahub::config::{Config, DATA_DIR_ENV};
 
 use clap::{value_parser, Arg, Command};
-use chrono::{Local, NaiveDate};
-use log::{info, error};
+use chrono::Local;
+use log::{info, warn, error};
 use std::error::Error;
-use std::sync::Arc;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     // Initialize logger
     env_logger::init();
     
+    // 注册可用的交易所数据源
+    let registry = ScraperRegistry::with_defaults();
+    let exchange_help = format!(
+        "Exchange to scrape data from ({}, all; comma separated)",
+        registry.names().join(", ")
+    );
+    
     // 创建基本的命令行应用
     let app = Command::new("DataHub")
         .version("1.0.0")
@@ -39,6 +56,116 @@ async fn main() -> Result<(), Box<dyn Error>> {
             .default_value("2"),
     );
 
+    // HTTP响应缓存参数
+    let app = app.arg(
+        Arg::new("cache")
+            .long("cache")
+            .help("Cache raw HTTP responses under the data directory")
+            .action(clap::ArgAction::SetTrue)
+            .global(true),
+    )
+    .arg(
+        Arg::new("no-cache")
+            .long("no-cache")
+            .help("Disable the HTTP response cache")
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("cache")
+            .global(true),
+    )
+    .arg(
+        Arg::new("cache-ttl")
+            .long("cache-ttl")
+            .value_name("SECONDS")
+            .help("Time-to-live of cached HTTP responses")
+            .value_parser(value_parser!(u64))
+            .default_value("21600")
+            .global(true),
+    )
+    .arg(
+        Arg::new("offline")
+            .long("offline")
+            .help("Serve every request from the HTTP cache, ignoring its TTL, without network access")
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("no-cache")
+            .global(true),
+    );
+
+    // 存储参数
+    let app = app.arg(
+        Arg::new("partitioned")
+            .long("partitioned")
+            .help("Read and write the dataset partitioned by exchange and year under the data directory")
+            .action(clap::ArgAction::SetTrue)
+            .global(true),
+    )
+    .arg(
+        Arg::new("delta")
+            .long("delta")
+            .help("Write only new, changed and removed bars to a delta-YYYYMMDD.arrow file next to stock.arrow, named by the latest trading date")
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("partitioned")
+            .global(true),
+    )
+    .arg(
+        Arg::new("backups")
+            .long("backups")
+            .value_name("N")
+            .help("Number of rotated backups (stock.arrow.bak1..N) to keep when overwriting the data file")
+            .value_parser(value_parser!(usize))
+            .default_value("3")
+            .global(true),
+    )
+    .arg(
+        Arg::new("sqlite")
+            .long("sqlite")
+            .value_name("PATH")
+            .help("Also write the securities and daily bars to this SQLite database after each run (needs the sqlite feature)")
+            .value_parser(value_parser!(String))
+            .global(true),
+    )
+    .arg(
+        Arg::new("data-dir")
+            .long("data-dir")
+            .value_name("DIR")
+            .help("Dataset directory holding stock.arrow, its manifest, deltas and partitions")
+            .env(DATA_DIR_ENV)
+            .value_parser(value_parser!(String))
+            .default_value("docs/data")
+            .global(true),
+    )
+    .arg(
+        Arg::new("cache-dir")
+            .long("cache-dir")
+            .value_name("DIR")
+            .help("Directory for cached HTTP responses [default: <DATA_DIR>/cache/http]")
+            .value_parser(value_parser!(String))
+            .global(true),
+    )
+    .arg(
+        Arg::new("snapshot")
+            .long("snapshot")
+            .help("Tag a dated snapshot (snapshots/YYYYMMDD.arrow under the data directory) after each run")
+            .action(clap::ArgAction::SetTrue)
+            .global(true),
+    )
+    .arg(
+        Arg::new("archive")
+            .long("archive")
+            .value_name("DIR")
+            .help("Keep the complete history in this partitioned archive; bars truncated by --max-records are moved there")
+            .value_parser(value_parser!(String))
+            .global(true),
+    )
+    .arg(
+        Arg::new("compressed-variant")
+            .long("compressed-variant")
+            .value_name("CODEC")
+            .help("Also publish an IPC-compressed copy (stock.zstd.arrow / stock.lz4.arrow): none, zstd, lz4")
+            .value_parser(["none", "zstd", "lz4"])
+            .default_value("none")
+            .global(true),
+    );
+
     // 添加子命令
     let app = app.subcommand(
         Command::new("scrape")
@@ -48,7 +175,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     .short('e')
                     .long("exchange")
                     .value_name("EXCHANGE")
-                    .help("Exchange to scrape data from (sse, szse, all)")
+                    .help(exchange_help)
                     .required(true)
                     .value_parser(value_parser!(String)),
             )
@@ -57,10 +184,27 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     .short('d')
                     .long("date")
                     .value_name("DATE")
-                    .help("Date to scrape data for (YYYY-MM-DD)")
+                    .help("Date to scrape data for (YYYY-MM-DD or YYYYMMDD)")
                     .value_parser(value_parser!(String))
                     .default_value(Local::now().format("%Y-%m-%d").to_string()),
             )
+            .arg(
+                Arg::new("from")
+                    .long("from")
+                    .value_name("FROM")
+                    .help("First date of a backfill range (YYYY-MM-DD)")
+                    .value_parser(value_parser!(String))
+                    .requires("to")
+                    .conflicts_with("symbol"),
+            )
+            .arg(
+                Arg::new("to")
+                    .long("to")
+                    .value_name("TO")
+                    .help("Last date of a backfill range (YYYY-MM-DD)")
+                    .value_parser(value_parser!(String))
+                    .requires("from"),
+            )
             .arg(
                 Arg::new("symbol")
                     .short('s')
@@ -84,6 +228,348 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     .help("Force fetching full history data even if incremental data exists")
                     .action(clap::ArgAction::SetTrue),
             ),
+    ).subcommand(
+        Command::new("check-sources")
+            .about("Fetch one sample from every scraper and check it against expectations")
+            .arg(
+                Arg::new("exchange")
+                    .short('e')
+                    .long("exchange")
+                    .value_name("EXCHANGE")
+                    .help("Exchanges to check (comma separated)")
+                    .value_parser(value_parser!(String))
+                    .default_value("all"),
+            )
+            .arg(
+                Arg::new("date")
+                    .short('d')
+                    .long("date")
+                    .value_name("DATE")
+                    .help("Date to fetch the sample for (YYYY-MM-DD)")
+                    .value_parser(value_parser!(String))
+                    .default_value(Local::now().format("%Y-%m-%d").to_string()),
+            )
+    ).subcommand(
+        Command::new("cache")
+            .about("Manage the HTTP response cache")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("clear")
+                    .about("Remove all cached HTTP responses")
+            )
+    ).subcommand(
+        Command::new("migrate")
+            .about("Upgrade an existing stock.arrow file to the current schema version")
+            .arg(
+                Arg::new("input")
+                    .short('i')
+                    .long("input")
+                    .value_name("INPUT")
+                    .help("Arrow file to migrate; defaults to stock.arrow in the data directory")
+                    .value_parser(value_parser!(String)),
+            )
+            .arg(
+                Arg::new("output")
+                    .short('o')
+                    .long("output")
+                    .value_name("OUTPUT")
+                    .help("Output file (defaults to rewriting the input in place)")
+                    .value_parser(value_parser!(String)),
+            )
+    ).subcommand(
+        Command::new("export")
+            .about("Export stock data to JSON, NDJSON, Arrow, Parquet, CSV or SQLite")
+            .arg(
+                Arg::new("input")
+                    .short('i')
+                    .long("input")
+                    .value_name("INPUT")
+                    .help("Arrow file to export, or - to read an Arrow stream from stdin; defaults to stock.arrow in the data directory")
+                    .value_parser(value_parser!(String)),
+            )
+            .arg(
+                Arg::new("output")
+                    .short('o')
+                    .long("output")
+                    .value_name("OUTPUT")
+                    .help("Output file (output directory for CSV), or - to write to stdout")
+                    .value_parser(value_parser!(String))
+                    .required(true),
+            )
+            .arg(
+                Arg::new("format")
+                    .short('f')
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("Output format (json, ndjson, arrow, parquet, csv, sqlite); inferred from the output extension by default, Arrow stream for stdout")
+                    .value_parser(["json", "ndjson", "arrow", "parquet", "csv", "sqlite"]),
+            )
+            .arg(
+                Arg::new("by")
+                    .long("by")
+                    .value_name("MODE")
+                    .help("CSV mode: one file per symbol or one cross-section file per trading date")
+                    .value_parser(["symbol", "date"])
+                    .default_value("symbol"),
+            )
+            .arg(
+                Arg::new("delimiter")
+                    .long("delimiter")
+                    .value_name("CHAR")
+                    .help("CSV delimiter, e.g. ',', ';' or 'tab'")
+                    .value_parser(value_parser!(String))
+                    .default_value(","),
+            )
+            .arg(
+                Arg::new("date-format")
+                    .long("date-format")
+                    .value_name("FORMAT")
+                    .help("CSV date format, e.g. %Y-%m-%d or %Y%m%d")
+                    .value_parser(value_parser!(String))
+                    .default_value("%Y-%m-%d"),
+            )
+            .arg(
+                Arg::new("bom")
+                    .long("bom")
+                    .help("Write a UTF-8 BOM so Excel displays Chinese names correctly")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("prune")
+                    .long("prune")
+                    .help("SQLite mode: remove securities and daily bars that are not in the exported data")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("layout")
+                    .long("layout")
+                    .value_name("LAYOUT")
+                    .help("Arrow/Parquet table layout: nested (one row per stock) or flat (one row per stock and day)")
+                    .value_parser(["nested", "flat"])
+                    .default_value("nested"),
+            )
+            .arg(
+                Arg::new("ipc-compression")
+                    .long("ipc-compression")
+                    .value_name("CODEC")
+                    .help("IPC body compression for Arrow output: none, zstd, lz4 (needs the ipc_compression feature)")
+                    .value_parser(["none", "zstd", "lz4"])
+                    .default_value("none"),
+            )
+            .arg(
+                Arg::new("compression")
+                    .long("compression")
+                    .value_name("CODEC")
+                    .help("Parquet compression")
+                    .value_parser(["none", "snappy", "gzip", "zstd", "lz4"])
+                    .default_value("zstd"),
+            )
+    ).subcommand(
+        Command::new("import")
+            .about("Import stock data from JSON, NDJSON or an Arrow stream")
+            .arg(
+                Arg::new("input")
+                    .short('i')
+                    .long("input")
+                    .value_name("INPUT")
+                    .help("JSON, NDJSON or Arrow file to import, or - to read from stdin")
+                    .value_parser(value_parser!(String))
+                    .required(true),
+            )
+            .arg(
+                Arg::new("output")
+                    .short('o')
+                    .long("output")
+                    .value_name("OUTPUT")
+                    .help("Arrow file to write, or - to write an Arrow stream to stdout; defaults to stock.arrow in the data directory")
+                    .value_parser(value_parser!(String)),
+            )
+            .arg(
+                Arg::new("format")
+                    .short('f')
+                    .long("format")
+                    .value_name("FORMAT")
+                    .help("Input format (json, ndjson, arrow); inferred from the input extension by default, Arrow for stdin")
+                    .value_parser(["json", "ndjson", "arrow"]),
+            )
+    ).subcommand(
+        Command::new("partition")
+            .about("Split a stock.arrow file into the partitioned dataset")
+            .arg(
+                Arg::new("input")
+                    .short('i')
+                    .long("input")
+                    .value_name("INPUT")
+                    .help("Arrow file to split; defaults to stock.arrow in the data directory")
+                    .value_parser(value_parser!(String)),
+            )
+    ).subcommand(
+        Command::new("archive")
+            .about("Merge an Arrow file into the complete-history archive")
+            .arg(
+                Arg::new("input")
+                    .short('i')
+                    .long("input")
+                    .value_name("INPUT")
+                    .help("Arrow file to archive; defaults to stock.arrow in the data directory")
+                    .value_parser(value_parser!(String)),
+            )
+            .arg(
+                Arg::new("output")
+                    .short('o')
+                    .long("output")
+                    .value_name("DIR")
+                    .help("Archive directory; defaults to --archive or the archive directory under the data directory")
+                    .value_parser(value_parser!(String)),
+            )
+    ).subcommand(
+        Command::new("compact")
+            .about("Fold delta files back into the base stock.arrow file")
+            .arg(
+                Arg::new("input")
+                    .short('i')
+                    .long("input")
+                    .value_name("INPUT")
+                    .help("Base Arrow file; defaults to stock.arrow in the data directory")
+                    .value_parser(value_parser!(String)),
+            )
+            .arg(
+                Arg::new("max-records")
+                    .long("max-records")
+                    .value_name("MAX_RECORDS")
+                    .help("Maximum number of K-line records to keep per stock")
+                    .value_parser(value_parser!(usize)),
+            )
+    ).subcommand(
+        Command::new("manifest")
+            .about("Write the checksum manifest (stock.manifest.json) next to a data file")
+            .arg(
+                Arg::new("input")
+                    .short('i')
+                    .long("input")
+                    .value_name("INPUT")
+                    .help("Arrow file to describe; defaults to stock.arrow in the data directory")
+                    .value_parser(value_parser!(String)),
+            )
+    ).subcommand(
+        Command::new("restore")
+            .about("Roll the data file back to one of its rotated backups")
+            .arg(
+                Arg::new("input")
+                    .short('i')
+                    .long("input")
+                    .value_name("INPUT")
+                    .help("Data file to restore; defaults to stock.arrow in the data directory")
+                    .value_parser(value_parser!(String)),
+            )
+            .arg(
+                Arg::new("backup")
+                    .short('b')
+                    .long("backup")
+                    .value_name("N")
+                    .help("Backup to restore, 1 is the most recent")
+                    .value_parser(value_parser!(usize))
+                    .default_value("1"),
+            )
+            .arg(
+                Arg::new("list")
+                    .long("list")
+                    .help("List available backups instead of restoring")
+                    .action(clap::ArgAction::SetTrue),
+            )
+    ).subcommand(
+        Command::new("snapshot")
+            .about("Manage dated snapshots of the dataset")
+            .subcommand_required(true)
+            .subcommand(
+                Command::new("tag")
+                    .about("Tag a snapshot of the data file")
+                    .arg(
+                        Arg::new("input")
+                            .short('i')
+                            .long("input")
+                            .value_name("INPUT")
+                            .help("Arrow file to snapshot; defaults to stock.arrow in the data directory")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("date")
+                            .short('d')
+                            .long("date")
+                            .value_name("DATE")
+                            .help("Snapshot date (YYYY-MM-DD)")
+                            .value_parser(value_parser!(String))
+                            .default_value(Local::now().format("%Y-%m-%d").to_string()),
+                    )
+            )
+            .subcommand(
+                Command::new("list")
+                    .about("List snapshots")
+            )
+            .subcommand(
+                Command::new("diff")
+                    .about("Show what changed between two snapshots")
+                    .arg(
+                        Arg::new("from")
+                            .long("from")
+                            .value_name("DATE")
+                            .help("Older snapshot date (YYYY-MM-DD)")
+                            .value_parser(value_parser!(String))
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::new("to")
+                            .long("to")
+                            .value_name("DATE")
+                            .help("Newer snapshot date (YYYY-MM-DD); defaults to the latest snapshot")
+                            .value_parser(value_parser!(String)),
+                    )
+                    .arg(
+                        Arg::new("limit")
+                            .short('l')
+                            .long("limit")
+                            .value_name("LIMIT")
+                            .help("Maximum number of changed bars to print")
+                            .value_parser(value_parser!(usize))
+                            .default_value("20"),
+                    )
+            )
+            .subcommand(
+                Command::new("restore")
+                    .about("Restore the dataset from the snapshot in effect on a date")
+                    .arg(
+                        Arg::new("date")
+                            .short('d')
+                            .long("date")
+                            .value_name("DATE")
+                            .help("Restore the latest snapshot on or before this date (YYYY-MM-DD)")
+                            .value_parser(value_parser!(String))
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .value_name("OUTPUT")
+                            .help("Data file to overwrite; defaults to stock.arrow in the data directory")
+                            .value_parser(value_parser!(String)),
+                    )
+            )
+            .subcommand(
+                Command::new("prune")
+                    .about("Delete all but the most recent snapshots")
+                    .arg(
+                        Arg::new("keep")
+                            .long("keep")
+                            .value_name("N")
+                            .help("Number of snapshots to keep")
+                            .value_parser(value_parser!(usize))
+                            .required(true),
+                    )
+            )
+    ).subcommand(
+        Command::new("exchanges")
+            .about("List available exchanges")
     ).subcommand(
         Command::new("explore")
             .about("Explore stock data")
@@ -123,38 +609,52 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let debug_mode = false;
 
     #[cfg(debug_assertions)]
-    let debug_stock_limit = matches.get_one::<usize>("debug-limit").unwrap().clone();
+    let debug_stock_limit = *matches.get_one::<usize>("debug-limit").unwrap();
     #[cfg(not(debug_assertions))]
     let debug_stock_limit = usize::MAX;
 
+    // 创建基础配置
+    let config = Config::new()
+        .with_data_dir(matches.get_one::<String>("data-dir").unwrap())
+        .with_cache_dir(matches.get_one::<String>("cache-dir").map(String::as_str))
+        .with_debug_mode(debug_mode)
+        .with_debug_stock_limit(debug_stock_limit)
+        .with_http_cache(matches.get_flag("cache") && !matches.get_flag("no-cache"))
+        .with_http_cache_ttl(*matches.get_one::<u64>("cache-ttl").unwrap())
+        .with_offline(matches.get_flag("offline"))
+        .with_partitioned(matches.get_flag("partitioned"))
+        .with_delta(matches.get_flag("delta"))
+        .with_backups(*matches.get_one::<usize>("backups").unwrap())
+        .with_compressed_variant(matches.get_one::<String>("compressed-variant").unwrap().parse()?)
+        .with_sqlite_path(matches.get_one::<String>("sqlite").map(String::as_str))
+        .with_archive_dir(matches.get_one::<String>("archive").map(String::as_str))
+        .with_snapshot(matches.get_flag("snapshot"));
+    let data_file = config.data_file();
+
     if let Some(matches) = matches.subcommand_matches("scrape") {
         let exchange = matches.get_one::<String>("exchange").unwrap();
         let date_str = matches.get_one::<String>("date").unwrap();
-        let date = NaiveDate::parse_from_str(date_str, "%Y-%m-%d")?;
+        let date = util::parse_trade_date(date_str)?.naive_date();
         let symbol = matches.get_one::<String>("symbol");
+        let from = matches.get_one::<String>("from")
+            .map(|s| util::parse_trade_date(s).map(TradeDate::naive_date))
+            .transpose()?;
+        let to = matches.get_one::<String>("to")
+            .map(|s| util::parse_trade_date(s).map(TradeDate::naive_date))
+            .transpose()?;
         let force_full = matches.get_flag("force-full");
         
         // 获取最大K线记录数量
-        let max_kline_records = matches.get_one::<usize>("max-records").unwrap().clone();
-        
-        // Create scrapers
-        let scrapers: Vec<Arc<dyn StockScraper + Send + Sync>> = match exchange.to_lowercase().as_str() {
-            "sse" => vec![Arc::new(SSEScraper::new()?)],
-            "szse" => vec![Arc::new(SZSEScraper::new()?)],
-            "all" => vec![Arc::new(SSEScraper::new()?), Arc::new(SZSEScraper::new()?)],
-            _ => {
-                error!("Unknown exchange: {}", exchange);
-                return Err(format!("Unknown exchange: {}", exchange).into());
-            }
-        };
+        let max_kline_records = *matches.get_one::<usize>("max-records").unwrap();
         
         // 创建配置
-        let config = Config::new()
-            .with_debug_mode(debug_mode)
-            .with_debug_stock_limit(debug_stock_limit)
+        let config = config
             .with_max_kline_records(max_kline_records)
             .with_force_full_history(force_full);
         
+        // Create scrapers
+        let scrapers = registry.resolve(exchange, &config)?;
+        
         info!("Using max kline records: {}", config.max_kline_records);
         if force_full {
             info!("Force full history mode enabled");
@@ -163,34 +663,312 @@ async fn main() -> Result<(), Box<dyn Error>> {
         // 创建数据服务
         let data_service = DataService::new(config, scrapers);
         
-        if let Some(symbol) = symbol {
+        if let (Some(from), Some(to)) = (from, to) {
+            // 回补日期区间内的快照数据
+            let report = data_service.backfill(from..=to).await?;
+            info!("Backfilled {} trading days", report.processed_days.len());
+            for (day, exchange) in &report.missing_days {
+                info!("Missing upstream: {} {}", day, exchange);
+            }
+            for exchange in &report.skipped_exchanges {
+                warn!("Skipped {}: upstream only serves the current trading day, use --symbol to fetch its history", exchange);
+            }
+        } else if let Some(symbol) = symbol {
             // 处理单个股票
             data_service.process_single_stock(symbol, Some(&date)).await?;
         } else {
             // 处理指定日期的所有股票
             data_service.process_daily_stocks(&date).await?;
         }
+    } else if let Some(matches) = matches.subcommand_matches("check-sources") {
+        let exchange = matches.get_one::<String>("exchange").unwrap();
+        let date_str = matches.get_one::<String>("date").unwrap();
+        let date = util::parse_trade_date(date_str)?.naive_date();
+        
+        let mut failed = Vec::new();
+        for scraper in registry.resolve(exchange, &config)? {
+            let check = scraper.check_source(&date).await?;
+            if check.is_ok() {
+                info!("{}: OK ({} stocks sampled)", check.exchange, check.sample_size);
+            } else {
+                error!("{}: {} issues ({} stocks sampled)", check.exchange, check.issues.len(), check.sample_size);
+                for issue in &check.issues {
+                    error!("  - {}", issue);
+                }
+                failed.push(check.exchange);
+            }
+        }
+        
+        if !failed.is_empty() {
+            return Err(format!("Source check failed for: {}", failed.join(", ")).into());
+        }
+    } else if let Some(matches) = matches.subcommand_matches("cache") {
+        if matches.subcommand_matches("clear").is_some() {
+            let cache_dir = ResponseCache::dir_for(&config);
+            let removed = ResponseCache::new(&cache_dir, Duration::ZERO).clear()?;
+            info!("Removed {} cached responses from {}", removed, cache_dir.display());
+        }
+    } else if let Some(matches) = matches.subcommand_matches("migrate") {
+        let input = matches.get_one::<String>("input").unwrap_or(&data_file);
+        let output = matches.get_one::<String>("output").unwrap_or(input);
+        
+        let count = arrow_utils::migrate_arrow_file(input, output, config.backups)?;
+        info!("Migrated {} stocks from {} to {} (schema v{})", count, input, output, arrow_utils::SCHEMA_VERSION);
+    } else if let Some(matches) = matches.subcommand_matches("export") {
+        let input = matches.get_one::<String>("input").unwrap_or(&data_file);
+        let output = matches.get_one::<String>("output").unwrap();
+        let format = matches.get_one::<String>("format").map(String::as_str)
+            .or_else(|| output.ends_with(".parquet").then_some("parquet"))
+            .or_else(|| (output.ends_with(".sqlite") || output.ends_with(".db")).then_some("sqlite"))
+            .or_else(|| (output.ends_with(".arrow") || output == "-").then_some("arrow"));
+        
+        let provider = if input == "-" {
+            StockDataProvider::from_reader(io::stdin().lock())?
+        } else {
+            StockDataProvider::load_from_file(input)?
+        };
+        if output == "-" && matches!(format, Some("csv") | Some("parquet") | Some("sqlite")) {
+            return Err("CSV, Parquet and SQLite export cannot be written to stdout".into());
+        }
+        
+        if format == Some("csv") {
+            let delimiter = match matches.get_one::<String>("delimiter").unwrap().as_str() {
+                "tab" | "\\t" => b'\t',
+                d if d.len() == 1 => d.as_bytes()[0],
+                d => return Err(format!("Invalid CSV delimiter: {}", d).into()),
+            };
+            let options = CsvOptions::new()
+                .with_delimiter(delimiter)
+                .with_date_format(matches.get_one::<String>("date-format").unwrap())
+                .with_bom(matches.get_flag("bom"));
+            
+            match matches.get_one::<String>("by").unwrap().as_str() {
+                "date" => arrow_utils::export_csv_by_date(provider.get_all_stocks(), output, &options)?,
+                _ => arrow_utils::export_csv_by_symbol(provider.get_all_stocks(), output, &options)?,
+            };
+        } else if format == Some("arrow") {
+            let options = ArrowOptions::new()
+                .with_layout(matches.get_one::<String>("layout").unwrap().parse()?)
+                .with_compression(matches.get_one::<String>("ipc-compression").unwrap().parse()?);
+            if output == "-" {
+                let mut stdout = BufWriter::new(io::stdout().lock());
+                arrow_utils::write_stock_data_to_stream(provider.get_all_stocks(), &mut stdout, &options)?;
+                stdout.flush()?;
+            } else {
+                arrow_utils::save_stock_data_to_arrow_with_options(provider.get_all_stocks(), output, &options)?;
+            }
+        } else if format == Some("parquet") {
+            #[cfg(feature = "parquet")]
+            {
+                let layout = matches.get_one::<String>("layout").unwrap().parse()?;
+                let compression = arrow_utils::parse_parquet_compression(matches.get_one::<String>("compression").unwrap())?;
+                arrow_utils::save_stock_data_to_parquet(provider.get_all_stocks(), output, layout, compression)?;
+            }
+            #[cfg(not(feature = "parquet"))]
+            return Err("Parquet export requires building with `--features parquet`".into());
+        } else if format == Some("sqlite") {
+            #[cfg(feature = "sqlite")]
+            SqliteStore::new(output).with_prune(matches.get_flag("prune")).save(provider.get_all_stocks())?;
+            #[cfg(not(feature = "sqlite"))]
+            return Err("SQLite export requires building with `--features sqlite`".into());
+        } else {
+            let format = match format {
+                Some(format) => format.parse()?,
+                None => JsonFormat::from_path(output),
+            };
+            if output == "-" {
+                let mut stdout = BufWriter::new(io::stdout().lock());
+                match format {
+                    JsonFormat::Json => json_utils::write_json(provider.get_all_stocks(), &mut stdout)?,
+                    JsonFormat::Ndjson => json_utils::write_ndjson(provider.get_all_stocks(), &mut stdout)?,
+                }
+                stdout.flush()?;
+            } else {
+                json_utils::save_stock_data_to_json(provider.get_all_stocks(), output, format)?;
+            }
+        }
+        info!("Exported {} stocks from {} to {}", provider.get_all_stocks().len(), input, output);
+    } else if let Some(matches) = matches.subcommand_matches("import") {
+        let input = matches.get_one::<String>("input").unwrap();
+        let output = matches.get_one::<String>("output").unwrap_or(&data_file);
+        let format = matches.get_one::<String>("format").map(String::as_str)
+            .or_else(|| (input.ends_with(".arrow") || input == "-").then_some("arrow"));
+        
+        // 标准输入和 .arrow 文件按 IPC 流或文件格式读取，自动识别
+        let provider = match (format, input.as_str()) {
+            (Some("arrow"), "-") => StockDataProvider::from_reader(io::stdin().lock())?,
+            (Some("arrow"), _) => StockDataProvider::from_reader(File::open(input)?)?,
+            (format, "-") => {
+                let format = format.map(str::parse).transpose()?.unwrap_or(JsonFormat::Json);
+                let data = match format {
+                    JsonFormat::Json => json_utils::read_json(io::stdin().lock())?,
+                    JsonFormat::Ndjson => json_utils::read_ndjson(io::stdin().lock())?,
+                };
+                StockDataProvider::new_with_data(data)?
+            }
+            (format, _) => {
+                let format = match format {
+                    Some(format) => format.parse()?,
+                    None => JsonFormat::from_path(input),
+                };
+                StockDataProvider::new_with_data(json_utils::read_stock_data_from_json(input, format)?)?
+            }
+        };
+        if output == "-" {
+            let mut stdout = BufWriter::new(io::stdout().lock());
+            arrow_utils::write_stock_data_to_stream(provider.get_all_stocks(), &mut stdout, &ArrowOptions::new())?;
+            stdout.flush()?;
+        } else {
+            provider.save_to_file_with_config(output, &config)?;
+        }
+        info!("Imported {} stocks from {} to {}", provider.get_all_stocks().len(), input, output);
+    } else if let Some(matches) = matches.subcommand_matches("partition") {
+        let input = matches.get_one::<String>("input").unwrap_or(&data_file);
+        let provider = StockDataProvider::load_from_file(input)?;
+        let store = PartitionedStore::from_config(&config);
+        let written = store.save(provider.get_all_stocks())?;
+        info!("Split {} stocks from {} into {} ({} partitions written)",
+              provider.get_all_stocks().len(), input, store.root().display(), written.len());
+    } else if let Some(matches) = matches.subcommand_matches("archive") {
+        let input = matches.get_one::<String>("input").unwrap_or(&data_file);
+        let output = matches.get_one::<String>("output")
+            .or(config.archive_dir.as_ref())
+            .cloned()
+            .unwrap_or_else(|| Path::new(&config.data_dir).join("archive").to_string_lossy().into_owned());
+        let provider = StockDataProvider::load_from_file(input)?;
+        let written = ArchiveStore::new(&output).append(provider.get_all_stocks().to_vec())?;
+        info!("Archived {} stocks from {} into {} ({} partitions written)",
+              provider.get_all_stocks().len(), input, output, written.len());
+    } else if let Some(matches) = matches.subcommand_matches("compact") {
+        let input = matches.get_one::<String>("input").unwrap_or(&data_file);
+        let max_records = matches.get_one::<usize>("max-records").copied();
+        let store = DeltaStore::new(input).with_compressed_variant(config.compressed_variant);
+        // 截断前先把完整数据合并到归档
+        if let (Some(dir), Some(_)) = (&config.archive_dir, max_records) {
+            ArchiveStore::new(dir).append(store.load()?)?;
+        }
+        let folded = store.compact(max_records)?;
+        info!("Folded {} delta files into {}", folded, input);
+    } else if let Some(matches) = matches.subcommand_matches("manifest") {
+        let input = Path::new(matches.get_one::<String>("input").unwrap_or(&data_file));
+        // 同目录下有增量文件时一并列入清单
+        let manifest = DeltaStore::new(input).publish()?;
+        info!("Wrote {} ({} stocks, latest {}, sha256 {})",
+              DatasetManifest::path_for(input).display(), manifest.stocks,
+              manifest.latest_date.map_or("-".to_string(), |d| d.to_string()), manifest.sha256);
+    } else if let Some(matches) = matches.subcommand_matches("restore") {
+        let input = Path::new(matches.get_one::<String>("input").unwrap_or(&data_file));
+        if matches.get_flag("list") {
+            for (n, path) in fs_utils::list_backups(input)? {
+                match arrow_utils::validate_arrow_file(&path) {
+                    Ok(stocks) => info!("{:>3}  {}  {} stocks", n, path.display(), stocks),
+                    Err(e) => error!("{:>3}  {}  invalid: {}", n, path.display(), e),
+                }
+            }
+        } else {
+            let n = *matches.get_one::<usize>("backup").unwrap();
+            restore_dataset(input, n, config.backups)?;
+            info!("Restored {} from backup {}, previous file kept as {}",
+                  input.display(), n, fs_utils::backup_path(input, 1).display());
+        }
+    } else if let Some(matches) = matches.subcommand_matches("snapshot") {
+        let store = SnapshotStore::from_config(&config);
+        if let Some(matches) = matches.subcommand_matches("tag") {
+            let input = matches.get_one::<String>("input").unwrap_or(&data_file);
+            let date = util::parse_trade_date(matches.get_one::<String>("date").unwrap())?;
+            let provider = StockDataProvider::load_from_file(input)?;
+            let path = store.tag(provider.get_all_stocks(), date)?;
+            info!("Tagged {} from {}", path.display(), input);
+        } else if matches.subcommand_matches("list").is_some() {
+            for (date, path) in store.list()? {
+                match DatasetManifest::load(&DatasetManifest::path_for(&path)) {
+                    Ok(manifest) => info!("{}  {}  {} stocks, latest {}, sha256 {}",
+                                          date, path.display(), manifest.stocks,
+                                          manifest.latest_date.map_or("-".to_string(), |d| d.to_string()),
+                                          &manifest.sha256[..12]),
+                    Err(_) => info!("{}  {}  (no manifest)", date, path.display()),
+                }
+            }
+        } else if let Some(matches) = matches.subcommand_matches("diff") {
+            let from = util::parse_trade_date(matches.get_one::<String>("from").unwrap())?;
+            let to = match matches.get_one::<String>("to") {
+                Some(to) => util::parse_trade_date(to)?,
+                None => store.list()?.last().map(|(date, _)| *date)
+                    .ok_or_else(|| format!("No snapshots in {}", store.root().display()))?,
+            };
+            let limit = *matches.get_one::<usize>("limit").unwrap();
+            
+            let diff = store.diff(from, to)?;
+            info!("{} -> {}: {} stocks added, {} removed, {} renamed, {} new bars, {} changed bars",
+                  from, to, diff.added.len(), diff.removed.len(), diff.renamed.len(), diff.new_bars, diff.changed.len());
+            for id in &diff.added {
+                info!("  + {}", id);
+            }
+            for id in &diff.removed {
+                info!("  - {}", id);
+            }
+            for change in diff.changed.iter().take(limit) {
+                info!("  ~ {} {}: open {} -> {}, high {} -> {}, low {} -> {}, close {} -> {}, volume {} -> {}",
+                      change.id, change.new.date,
+                      change.old.open, change.new.open, change.old.high, change.new.high,
+                      change.old.low, change.new.low, change.old.close, change.new.close,
+                      change.old.volume, change.new.volume);
+            }
+            if diff.changed.len() > limit {
+                info!("  ... {} more changed bars", diff.changed.len() - limit);
+            }
+        } else if let Some(matches) = matches.subcommand_matches("restore") {
+            let date = util::parse_trade_date(matches.get_one::<String>("date").unwrap())?;
+            if config.partitioned {
+                let data = store.load(date)?;
+                PartitionedStore::from_config(&config).save(&data)?;
+                info!("Restored partitioned dataset in {} from snapshot {}", config.data_dir, store.resolve(date)?.0);
+            } else {
+                let output = matches.get_one::<String>("output").unwrap_or(&data_file);
+                let snapshot_date = store.restore(date, Path::new(output), config.backups)?;
+                info!("Restored {} from snapshot {}", output, snapshot_date);
+            }
+        } else if let Some(matches) = matches.subcommand_matches("prune") {
+            let keep = *matches.get_one::<usize>("keep").unwrap();
+            let removed = store.prune(keep)?;
+            info!("Removed {} snapshots from {}", removed, store.root().display());
+        }
+    } else if matches.subcommand_matches("exchanges").is_some() {
+        for entry in registry.entries() {
+            info!("{:<10} {}", entry.name(), entry.description());
+        }
     } else if let Some(matches) = matches.subcommand_matches("explore") {
         let symbol_filter = matches.get_one::<String>("symbol");
-        let exchange_filter = matches.get_one::<String>("exchange");
-        let limit = matches.get_one::<usize>("limit").unwrap().clone();
+        // 带交易所的写法（如 000001.SZ、sh600519）精确匹配，纯代码按包含匹配
+        let id_filter = symbol_filter
+            .filter(|s| !s.chars().all(|c| c.is_ascii_digit()))
+            .map(|s| SecurityId::parse(s))
+            .transpose()?;
+        let exchange_filter = matches.get_one::<String>("exchange")
+            .map(|e| e.parse::<Exchange>())
+            .transpose()?;
+        let limit = *matches.get_one::<usize>("limit").unwrap();
         
         // 读取数据
-        let stocks = arrow_utils::read_stock_data_from_arrow("docs/data/stock.arrow")?;
+        let stocks = arrow_utils::read_stock_data_from_arrow(&data_file)?;
         
         info!("Found {} stocks in database", stocks.len());
         
         // 过滤数据
         let filtered_stocks: Vec<&StockData> = stocks.iter()
             .filter(|s| {
-                if let Some(symbol) = symbol_filter {
+                if let Some(id) = &id_filter {
+                    if s.security_id() != *id {
+                        return false;
+                    }
+                } else if let Some(symbol) = symbol_filter {
                     if !s.symbol.contains(symbol) {
                         return false;
                     }
                 }
                 
                 if let Some(exchange) = exchange_filter {
-                    if s.exchange.to_lowercase() != exchange.to_lowercase() {
+                    if s.exchange != exchange {
                         return false;
                     }
                 }
@@ -207,23 +985,16 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 break;
             }
             
-            info!("Stock: {} ({}) - {}", stock.name, stock.symbol, stock.exchange);
+            info!("Stock: {} ({}) - {}", stock.name, stock.security_id(), stock.exchange);
             info!("{:-<60}", "");
-            info!("{:<10} {:<10} {:<10} {:<10} {:<10} {:<15} {:<15}", 
-                     "Date", "Open", "High", "Low", "Close", "Volume", "Amount");
+            info!("{:<10} {:<10} {:<10} {:<10} {:<10} {:<8} {:<15} {:<15}", 
+                     "Date", "Open", "High", "Low", "Close", "Chg%", "Volume", "Amount");
             info!("{:-<60}", "");
             
             for daily in stock.daily.iter().take(limit) {
-                // Format date as YYYY-MM-DD
-                let date_str = format!("{}", daily.date);
-                let year = &date_str[0..4];
-                let month = &date_str[4..6];
-                let day = &date_str[6..8];
-                let formatted_date = format!("{}-{}-{}", year, month, day);
-                
-                info!("{:<10} {:<10.2} {:<10.2} {:<10.2} {:<10.2} {:<15} {:<15}", 
-                         formatted_date, daily.open, daily.high, daily.low, daily.close, 
-                         daily.volume, daily.amount);
+                info!("{:<10} {:<10.2} {:<10.2} {:<10.2} {:<10.2} {:<8.2} {:<15} {:<15}", 
+                         daily.date, daily.open, daily.high, daily.low, daily.close, 
+                         daily.change_pct, daily.volume, daily.amount);
             }
             
             if stock.daily.len() > limit {
@@ -1,9 +1,35 @@
+use crate::util::arrow_utils::IpcCompression;
+use std::path::Path;
+
+/// 覆盖数据目录的环境变量
+pub const DATA_DIR_ENV: &str = "EGOSTRATEGY_DATA_DIR";
+
+/// 数据目录下的主数据文件名
+pub const DATA_FILE: &str = "stock.arrow";
+
 pub struct Config {
     pub debug_mode: bool,
     pub debug_stock_limit: usize,
-    pub data_dir: String,
+    pub data_dir: String,   // 数据集目录，主数据文件、清单、增量和分区都保存在这里
+    pub cache_dir: Option<String>,  // HTTP 响应缓存目录，未指定时为 data_dir/cache/http
     pub max_kline_records: usize,
     pub force_full_history: bool,  // 新增字段
+    pub http_cache: bool,
+    pub http_cache_ttl: u64,  // 秒
+    pub offline: bool,
+    pub partitioned: bool,  // 按交易所和年份分区存储到 data_dir
+    pub delta: bool,        // 只写入新增、变化和删除日线的增量文件
+    pub backups: usize,     // 覆盖数据文件时保留的备份数量
+    pub compressed_variant: IpcCompression,  // 额外发布的压缩版本，None 表示不发布
+    pub sqlite_path: Option<String>,  // 每次运行后同步写入的 SQLite 数据库
+    pub archive_dir: Option<String>,  // 保存完整历史的归档目录，接收被截断的K线
+    pub snapshot: bool,     // 每次运行后在 data_dir/snapshots 中标记当天的快照
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Config {
@@ -11,9 +37,42 @@ impl Config {
         Self {
             debug_mode: false,
             debug_stock_limit: 10,
-            data_dir: "data".to_string(),
+            data_dir: "docs/data".to_string(),
+            cache_dir: None,
             max_kline_records: 200,
             force_full_history: false,  // 默认为 false
+            http_cache: false,
+            http_cache_ttl: 6 * 60 * 60,
+            offline: false,
+            partitioned: false,
+            delta: false,
+            backups: crate::util::fs_utils::DEFAULT_BACKUPS,
+            compressed_variant: IpcCompression::None,
+            sqlite_path: None,
+            archive_dir: None,
+            snapshot: false,
+        }
+    }
+    
+    /// 默认配置，数据目录可由环境变量 `EGOSTRATEGY_DATA_DIR` 覆盖
+    pub fn from_env() -> Self {
+        let config = Self::new();
+        match std::env::var(DATA_DIR_ENV) {
+            Ok(dir) if !dir.is_empty() => config.with_data_dir(&dir),
+            _ => config,
+        }
+    }
+    
+    /// 主数据文件路径，即 `{data_dir}/stock.arrow`
+    pub fn data_file(&self) -> String {
+        Path::new(&self.data_dir).join(DATA_FILE).to_string_lossy().into_owned()
+    }
+    
+    /// HTTP 响应缓存目录，未指定时为 `{data_dir}/cache/http`
+    pub fn cache_dir(&self) -> String {
+        match &self.cache_dir {
+            Some(dir) => dir.clone(),
+            None => Path::new(&self.data_dir).join("cache").join("http").to_string_lossy().into_owned(),
         }
     }
     
@@ -32,6 +91,11 @@ impl Config {
         self
     }
     
+    pub fn with_cache_dir(mut self, dir: Option<&str>) -> Self {
+        self.cache_dir = dir.map(str::to_string);
+        self
+    }
+    
     pub fn with_max_kline_records(mut self, max: usize) -> Self {
         self.max_kline_records = max;
         self
@@ -42,4 +106,101 @@ impl Config {
         self.force_full_history = force_full;
         self
     }
+    
+    pub fn with_http_cache(mut self, enabled: bool) -> Self {
+        self.http_cache = enabled;
+        self
+    }
+    
+    pub fn with_http_cache_ttl(mut self, ttl_secs: u64) -> Self {
+        self.http_cache_ttl = ttl_secs;
+        self
+    }
+    
+    // 离线模式只从缓存读取原始响应，用于修复解析器后重新解析
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+    
+    pub fn with_partitioned(mut self, partitioned: bool) -> Self {
+        self.partitioned = partitioned;
+        self
+    }
+    
+    pub fn with_delta(mut self, delta: bool) -> Self {
+        self.delta = delta;
+        self
+    }
+    
+    pub fn with_backups(mut self, backups: usize) -> Self {
+        self.backups = backups;
+        self
+    }
+    
+    pub fn with_compressed_variant(mut self, compression: IpcCompression) -> Self {
+        self.compressed_variant = compression;
+        self
+    }
+    
+    pub fn with_sqlite_path(mut self, path: Option<&str>) -> Self {
+        self.sqlite_path = path.map(str::to_string);
+        self
+    }
+    
+    pub fn with_archive_dir(mut self, dir: Option<&str>) -> Self {
+        self.archive_dir = dir.map(str::to_string);
+        self
+    }
+    
+    pub fn with_snapshot(mut self, snapshot: bool) -> Self {
+        self.snapshot = snapshot;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::trade_date::TradeDate;
+    use crate::scrapers::cache::ResponseCache;
+    use crate::storage::{DatasetManifest, DeltaStore, PartitionedStore, SnapshotStore};
+    use std::path::PathBuf;
+    
+    fn assert_paths_under(config: &Config, dir: &Path) {
+        assert_eq!(PathBuf::from(config.data_file()), dir.join("stock.arrow"));
+        assert_eq!(DatasetManifest::path_for(Path::new(&config.data_file())), dir.join("stock.manifest.json"));
+        assert_eq!(ResponseCache::dir_for(config), dir.join("cache").join("http"));
+        assert_eq!(PartitionedStore::from_config(config).manifest_path(), dir.join("manifest.json"));
+        assert_eq!(SnapshotStore::from_config(config).root(), dir.join("snapshots"));
+        let date: TradeDate = "2025-05-06".parse().unwrap();
+        assert_eq!(DeltaStore::new(config.data_file()).delta_path(date).parent(), Some(dir));
+    }
+    
+    #[test]
+    fn data_dir_resolves_every_path() {
+        let dir = Path::new("custom").join("data");
+        let config = Config::new().with_data_dir(dir.to_str().unwrap());
+        assert_paths_under(&config, &dir);
+        
+        // 单独指定的缓存目录不跟随数据目录
+        let config = config.with_cache_dir(Some("elsewhere"));
+        assert_eq!(ResponseCache::dir_for(&config), PathBuf::from("elsewhere"));
+        assert_eq!(PathBuf::from(config.data_file()), dir.join("stock.arrow"));
+    }
+    
+    #[test]
+    fn env_overrides_data_dir() {
+        let dir = std::env::temp_dir().join(format!("egostrategy_config_{}", std::process::id()));
+        std::env::set_var(DATA_DIR_ENV, &dir);
+        let config = Config::from_env();
+        std::env::set_var(DATA_DIR_ENV, "");
+        let empty = Config::from_env();
+        std::env::remove_var(DATA_DIR_ENV);
+        
+        assert_paths_under(&config, &dir);
+        // 空值时使用默认目录
+        assert_paths_under(&empty, Path::new("docs/data"));
+        assert_paths_under(&Config::from_env(), Path::new("docs/data"));
+    }
 }
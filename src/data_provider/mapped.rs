@@ -0,0 +1,325 @@
+use arrow::array::{new_empty_array, Array, AsArray, Float32Array, Int32Array, Int64Array, ListArray, RecordBatch, StringArray, StructArray};
+use arrow::datatypes::DataType;
+use log::info;
+
+use crate::models::price::Price;
+use crate::models::security::{Exchange, SecurityId};
+use crate::models::stock::{DailyData, NameChange, StockData};
+use crate::models::trade_date::TradeDate;
+use crate::errors::{Result, DataHubError};
+use crate::util::arrow_utils;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 内存映射的股票数据提供者
+///
+/// 打开时只建立代码索引，保留映射的Arrow数组，不转换为 `StockData`。
+/// 通过 `StockView` 借用访问单只股票，日线在调用 `daily` 时才解码。
+pub struct MappedStockProvider {
+    batches: Vec<RecordBatch>,
+    // 每行的交易所，缺少 exchange 列时已按代码推断
+    exchanges: Vec<Vec<Exchange>>,
+    // 索引用于快速查找：(批次, 行)
+    symbol_index: HashMap<SecurityId, (usize, usize)>,
+    exchange_index: HashMap<Exchange, Vec<(usize, usize)>>,
+}
+
+impl MappedStockProvider {
+    /// 内存映射Arrow文件，支持嵌套和扁平两种布局
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let batches = arrow_utils::map_arrow_file(path)?;
+        
+        let mut row_exchanges = Vec::with_capacity(batches.len());
+        let mut symbol_index = HashMap::new();
+        let mut exchange_index: HashMap<Exchange, Vec<(usize, usize)>> = HashMap::new();
+        for (b, batch) in batches.iter().enumerate() {
+            let symbols = arrow_utils::optional_string_column(batch, "symbol")?
+                .ok_or_else(|| DataHubError::ArrowError("Missing symbol column".to_string()))?;
+            let exchanges = arrow_utils::optional_string_column(batch, "exchange")?;
+            
+            let mut batch_exchanges = Vec::with_capacity(batch.num_rows());
+            for row in 0..batch.num_rows() {
+                let symbol = symbols.value(row);
+                let exchange = arrow_utils::row_exchange(exchanges, row, symbol)?;
+                symbol_index.insert(SecurityId::new(exchange, symbol), (b, row));
+                exchange_index.entry(exchange).or_default().push((b, row));
+                batch_exchanges.push(exchange);
+            }
+            row_exchanges.push(batch_exchanges);
+        }
+        
+        info!("Mapped {} stocks from {}", symbol_index.len(), path.display());
+        Ok(Self { batches, exchanges: row_exchanges, symbol_index, exchange_index })
+    }
+    
+    /// 股票数量
+    pub fn len(&self) -> usize {
+        self.batches.iter().map(|b| b.num_rows()).sum()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
+    fn view(&self, (batch, row): (usize, usize)) -> StockView<'_> {
+        StockView { batch: &self.batches[batch], row, exchange: self.exchanges[batch][row] }
+    }
+    
+    /// 按文件中的顺序遍历所有股票
+    pub fn stocks(&self) -> impl Iterator<Item = StockView<'_>> {
+        self.batches.iter()
+            .enumerate()
+            .flat_map(move |(b, batch)| (0..batch.num_rows()).map(move |row| self.view((b, row))))
+    }
+    
+    /// 获取指定股票，支持 600519.SH、sh600519、1.600519 等写法；
+    /// 纯代码按号段推断交易所
+    pub fn get_stock_by_symbol(&self, symbol: &str) -> Option<StockView<'_>> {
+        let id = SecurityId::parse(symbol).ok()?;
+        self.get_stock(&id)
+    }
+    
+    /// 按证券标识获取股票
+    pub fn get_stock(&self, id: &SecurityId) -> Option<StockView<'_>> {
+        self.symbol_index.get(id).map(|&pos| self.view(pos))
+    }
+    
+    /// 获取指定交易所的股票，支持 "SSE"、"sh" 等写法
+    pub fn get_stocks_by_exchange(&self, exchange: &str) -> Vec<StockView<'_>> {
+        exchange.parse::<Exchange>()
+            .map(|exchange| self.get_stocks_in(exchange))
+            .unwrap_or_default()
+    }
+    
+    /// 获取指定交易所的股票
+    pub fn get_stocks_in(&self, exchange: Exchange) -> Vec<StockView<'_>> {
+        self.exchange_index.get(&exchange)
+            .map(|positions| positions.iter().map(|&pos| self.view(pos)).collect())
+            .unwrap_or_default()
+    }
+    
+    /// 获取最新日期，只读取每只股票的第一条日线日期
+    pub fn get_latest_trading_date(&self) -> Option<TradeDate> {
+        self.stocks().filter_map(|stock| stock.latest_date()).max()
+    }
+}
+
+/// 借用映射数据的单只股票视图
+#[derive(Clone, Copy)]
+pub struct StockView<'a> {
+    batch: &'a RecordBatch,
+    row: usize,
+    exchange: Exchange,
+}
+
+impl<'a> StockView<'a> {
+    fn string(&self, name: &str) -> Option<&'a str> {
+        let batch: &'a RecordBatch = self.batch;
+        batch.column_by_name(name)
+            .and_then(|c| c.as_any().downcast_ref::<StringArray>())
+            .map(|a| a.value(self.row))
+    }
+    
+    pub fn symbol(&self) -> &'a str {
+        self.string("symbol").unwrap_or_default()
+    }
+    
+    pub fn name(&self) -> &'a str {
+        self.string("name").unwrap_or_default()
+    }
+    
+    pub fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+    
+    pub fn security_id(&self) -> SecurityId {
+        SecurityId::new(self.exchange, self.symbol())
+    }
+    
+    fn daily_list(&self) -> Option<&'a ListArray> {
+        let batch: &'a RecordBatch = self.batch;
+        batch.column_by_name("daily")
+            .and_then(|c| c.as_any().downcast_ref::<ListArray>())
+            .filter(|list| !list.is_null(self.row))
+    }
+    
+    /// 日线数量，不解码日线
+    pub fn daily_len(&self) -> usize {
+        self.daily_list().map_or(0, |list| list.value_length(self.row) as usize)
+    }
+    
+    /// 最新日线的日期，只读取一个值
+    pub fn latest_date(&self) -> Option<TradeDate> {
+        let list = self.daily_list()?;
+        if list.value_length(self.row) == 0 {
+            return None;
+        }
+        let dates = list.values().as_any().downcast_ref::<StructArray>()?
+            .column_by_name("date")?;
+        arrow_utils::date_value(dates, list.value_offsets()[self.row] as usize).ok()
+    }
+    
+    /// 日线视图，按日期降序排列，只解码日期列
+    pub fn daily(&self) -> Result<DailyView> {
+        match self.daily_list() {
+            Some(list) => {
+                let slice = list.value(self.row);
+                let array = slice.as_any().downcast_ref::<StructArray>()
+                    .ok_or_else(|| DataHubError::ArrowError("Failed to downcast daily struct".to_string()))?;
+                DailyView::try_new(array)
+            }
+            None => {
+                let empty = new_empty_array(&DataType::Struct(arrow_utils::daily_fields()));
+                DailyView::try_new(empty.as_struct())
+            }
+        }
+    }
+    
+    pub fn name_history(&self) -> Result<Vec<NameChange>> {
+        arrow_utils::read_name_history(self.batch, self.row)
+    }
+    
+    /// 股票在指定日期时的名称，用于回测中判断 ST 等状态
+    pub fn name_as_of(&self, date: TradeDate) -> Result<String> {
+        let stock = StockData {
+            exchange: self.exchange(),
+            symbol: self.symbol().to_string(),
+            name: self.name().to_string(),
+            daily: Vec::new(),
+            name_history: self.name_history()?,
+        };
+        Ok(stock.name_as_of(date).to_string())
+    }
+    
+    /// 完整解码为 `StockData`
+    pub fn to_stock_data(&self) -> Result<StockData> {
+        Ok(StockData {
+            exchange: self.exchange(),
+            symbol: self.symbol().to_string(),
+            name: self.name().to_string(),
+            daily: self.daily()?.to_vec(),
+            name_history: self.name_history()?,
+        })
+    }
+}
+
+/// 单只股票的日线视图，引用映射的Arrow数组，按需读取各字段
+pub struct DailyView {
+    dates: Vec<TradeDate>,
+    open: Int32Array,
+    high: Int32Array,
+    low: Int32Array,
+    close: Int32Array,
+    volume: Int64Array,
+    amount: Int64Array,
+    pre_close: Int32Array,
+    change: Int32Array,
+    change_pct: Float32Array,
+    turnover_rate: Float32Array,
+}
+
+impl DailyView {
+    fn try_new(array: &StructArray) -> Result<Self> {
+        let dates = arrow_utils::date_values(array.column_by_name("date")
+            .ok_or_else(|| DataHubError::ArrowError("Missing date column in daily data".to_string()))?)?;
+        
+        Ok(Self {
+            dates,
+            open: column(array, "open")?,
+            high: column(array, "high")?,
+            low: column(array, "low")?,
+            close: column(array, "close")?,
+            volume: column(array, "volume")?,
+            amount: column(array, "amount")?,
+            pre_close: column(array, "pre_close")?,
+            change: column(array, "change")?,
+            change_pct: column(array, "change_pct")?,
+            turnover_rate: column(array, "turnover_rate")?,
+        })
+    }
+    
+    pub fn len(&self) -> usize {
+        self.dates.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.dates.is_empty()
+    }
+    
+    pub fn dates(&self) -> &[TradeDate] {
+        &self.dates
+    }
+    
+    pub fn date(&self, i: usize) -> TradeDate {
+        self.dates[i]
+    }
+    
+    pub fn open(&self, i: usize) -> Price {
+        Price::from_milli(self.open.value(i))
+    }
+    
+    pub fn high(&self, i: usize) -> Price {
+        Price::from_milli(self.high.value(i))
+    }
+    
+    pub fn low(&self, i: usize) -> Price {
+        Price::from_milli(self.low.value(i))
+    }
+    
+    pub fn close(&self, i: usize) -> Price {
+        Price::from_milli(self.close.value(i))
+    }
+    
+    pub fn volume(&self, i: usize) -> i64 {
+        self.volume.value(i)
+    }
+    
+    /// 以0.001元为单位的收盘价，直接引用映射的内存
+    pub fn close_milli(&self) -> &[i32] {
+        self.close.values()
+    }
+    
+    /// 按日期查找日线位置
+    pub fn position(&self, date: TradeDate) -> Option<usize> {
+        self.dates.binary_search_by(|d| date.cmp(d)).ok()
+    }
+    
+    /// 解码一条日线
+    pub fn get(&self, i: usize) -> Option<DailyData> {
+        (i < self.len()).then(|| DailyData {
+            date: self.dates[i],
+            open: self.open(i),
+            high: self.high(i),
+            low: self.low(i),
+            close: self.close(i),
+            volume: self.volume.value(i),
+            amount: self.amount.value(i),
+            pre_close: Price::from_milli(self.pre_close.value(i)),
+            change: Price::from_milli(self.change.value(i)),
+            change_pct: self.change_pct.value(i),
+            turnover_rate: self.turnover_rate.value(i),
+        })
+    }
+    
+    /// 最新一条日线
+    pub fn latest(&self) -> Option<DailyData> {
+        self.get(0)
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = DailyData> + '_ {
+        (0..self.len()).filter_map(|i| self.get(i))
+    }
+    
+    pub fn to_vec(&self) -> Vec<DailyData> {
+        self.iter().collect()
+    }
+}
+
+// 按名称读取日线字段，克隆数组只增加引用计数
+fn column<T: Array + Clone + 'static>(array: &StructArray, name: &str) -> Result<T> {
+    array.column_by_name(name)
+        .and_then(|c| c.as_any().downcast_ref::<T>())
+        .cloned()
+        .ok_or_else(|| DataHubError::ArrowError(format!("Missing or invalid {} column in daily data", name)))
+}
@@ -1,29 +1,54 @@
+pub mod mapped;
+
 use chrono::prelude::*;
 use chrono::{Local};
-use log::{error, info};
+use log::{error, info, warn};
 
-use crate::models::stock::StockData;
+use crate::models::stock::{StockData, NameChange};
+use crate::models::security::{Exchange, SecurityId};
+use crate::models::trade_date::TradeDate;
 use crate::errors::{Result, DataHubError};
-use crate::util::arrow_utils;
+use crate::config::Config;
+use crate::storage::{save_dataset, ArchiveStore, DatasetManifest, DeltaStore, FileEntry, PartitionFilter, PartitionedStore, SnapshotStore};
+use crate::storage::partition::{Manifest as PartitionManifest, MANIFEST_FILE as PARTITION_MANIFEST_FILE};
+#[cfg(feature = "sqlite")]
+use crate::storage::SqliteStore;
+use crate::util::{arrow_utils, fs_utils};
+use crate::util::arrow_utils::IpcCompression;
+pub use mapped::{DailyView, MappedStockProvider, StockView};
 use std::collections::HashMap;
 use std::path::Path;
 use std::fs;
+use std::io::{Read, Write};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// 股票数据提供者，用于访问嵌入的股票数据
 pub struct StockDataProvider {
     data: Vec<StockData>,
     // 索引用于快速查找
-    symbol_index: HashMap<String, usize>,
-    exchange_index: HashMap<String, Vec<usize>>,
+    symbol_index: HashMap<SecurityId, usize>,
+    exchange_index: HashMap<Exchange, Vec<usize>>,
 }
 
 impl StockDataProvider {
     /// 创建新的数据提供者实例
+    ///
+    /// 数据保存在当前目录下的 `docs/data`，可通过环境变量 `EGOSTRATEGY_DATA_DIR` 指定其他目录。
     pub fn new() -> Result<Self> {
-        // 使用包内docs/data/stock.arrow路径文件
-        let data_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/docs/data");
-        let package_arrow_file = concat!(env!("CARGO_MANIFEST_DIR"), "/docs/data/stock.arrow");
+        Self::from_config(&Config::from_env())
+    }
+    
+    /// 使用配置中的数据目录，本地数据过期时从镜像站点下载更新
+    ///
+    /// 配置为分区模式时等同于 `from_config_partitioned` 加载全部分区。
+    pub fn from_config(config: &Config) -> Result<Self> {
+        if config.partitioned {
+            return Self::from_config_partitioned(config, &PartitionFilter::all());
+        }
+        
+        let data_dir = config.data_dir.as_str();
+        let data_file = config.data_file();
+        let arrow_file = data_file.as_str();
         
         // 确保数据目录存在
         if !std::path::Path::new(data_dir).exists() {
@@ -31,33 +56,35 @@ impl StockDataProvider {
         }
         
         // 检查本地文件是否存在，如果不存在则创建空文件
-        if !std::path::Path::new(&package_arrow_file).exists() {
+        if !std::path::Path::new(arrow_file).exists() {
             info!("Local stock.arrow file not found. Creating empty file.");
             
             // 创建空的Arrow文件
             let empty_data: Vec<StockData> = Vec::new();
-            arrow_utils::save_stock_data_to_arrow(&empty_data, &package_arrow_file)?;
+            arrow_utils::save_stock_data_to_arrow(&empty_data, arrow_file)?;
         }
         
-        // 从文件加载数据（更新前）
-        let data_before_update = arrow_utils::read_stock_data_from_arrow(&package_arrow_file)?;
+        // 从文件加载数据（更新前，包含本地的增量文件），无法读取时（如未启用解压的压缩文件）视为无数据，重新下载
+        let data_before_update = DeltaStore::new(arrow_file).load()
+            .unwrap_or_else(|e| {
+                warn!("Failed to read local stock.arrow: {}", e);
+                Vec::new()
+            });
         let latest_date_before = Self::get_latest_date_from_data(&data_before_update);
         let tz_offset: FixedOffset = "+08:00".parse()?;
         let dt_now = Local::now().with_timezone(&tz_offset);
-        let now_int = dt_now.format("%Y%m%d").to_string().parse::<i32>()?;
-        if latest_date_before.is_none() || latest_date_before.unwrap() < now_int {
-            info!("更新前最新交易日期: {}", latest_date_before.unwrap_or(-1));
-            // 同步检查更新
-            // 尝试多个国内镜像站点，按优先级排序
-            let mirror_sites = [
-                "raw.githubusercontent.com",
-                "raw.bgithub.xyz",
-                "raw.staticdn.net"
-            ];
-            
+        let today = TradeDate::from(dt_now.date_naive());
+        if latest_date_before.is_none_or(|date| date < today) {
+            match latest_date_before {
+                Some(date) => info!("更新前最新交易日期: {}", date),
+                None => info!("更新前无交易数据"),
+            }
+            // 同步检查更新，依次尝试各镜像站点
             let mut success = false;
-            for mirror in mirror_sites {
-                if let Ok(_) = Self::check_for_updates_sync(package_arrow_file, &format!("https://{}/EgoStrategy/DataHub/main/docs/data/stock.arrow", mirror)) {
+            for base_url in Self::mirror_urls() {
+                let updated = Self::remote_data_files().iter()
+                    .any(|file| Self::check_for_updates_sync(arrow_file, &format!("{}/{}", base_url, file)).is_ok());
+                if updated {
                     success = true;
                     break;
                 }
@@ -67,9 +94,9 @@ impl StockDataProvider {
                 error!("Failed to check for updates from all mirror sites");
             }
         }
-            
-        // 从文件加载数据（更新后）
-        let data = arrow_utils::read_stock_data_from_arrow(&package_arrow_file)?;
+        
+        // 从文件加载数据（更新后），合并本地的增量文件
+        let data = DeltaStore::new(arrow_file).load()?;
         let latest_date_after = Self::get_latest_date_from_data(&data);
         if let Some(date) = latest_date_after {
             info!("更新后最新交易日期: {}", date);
@@ -89,6 +116,26 @@ impl StockDataProvider {
         Ok(provider)
     }
     
+    /// 使用配置中的数据目录加载分区数据集，只从镜像站点下载过滤条件选中且与远程清单不一致的分区
+    ///
+    /// 未选中的分区不会下载，之后用更宽的过滤条件加载前需要重新调用。
+    pub fn from_config_partitioned(config: &Config, filter: &PartitionFilter) -> Result<Self> {
+        let store = PartitionedStore::from_config(config);
+        std::fs::create_dir_all(store.root())?;
+        
+        let synced = Self::mirror_urls().iter().any(|base_url| {
+            Self::sync_partitions_sync(&store, base_url, filter)
+                .inspect_err(|e| warn!("Failed to sync partitions from {}: {}", base_url, e))
+                .is_ok()
+        });
+        if !synced {
+            error!("Failed to sync partitions from all mirror sites");
+        }
+        
+        let data = store.load(filter)?;
+        Self::new_with_data(data)
+    }
+    
     /// 使用提供的数据创建新的数据提供者实例
     pub fn new_with_data(data: Vec<StockData>) -> Result<Self> {
         let mut provider = Self {
@@ -102,7 +149,7 @@ impl StockDataProvider {
         Ok(provider)
     }
     
-    /// 从文件加载数据
+    /// 从文件加载数据，自动识别嵌套和扁平两种布局
     pub fn load_from_file(path: &str) -> Result<Self> {
         let data = if Path::new(path).exists() {
             arrow_utils::read_stock_data_from_arrow(path)?
@@ -121,16 +168,82 @@ impl StockDataProvider {
         Ok(provider)
     }
     
-    /// 保存数据到文件
+    /// 从任意输入加载数据，如标准输入或网络连接，支持 IPC 流格式和文件格式
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        let data = arrow_utils::read_stock_data_from_reader(reader)?;
+        Self::new_with_data(data)
+    }
+    
+    /// 从 Parquet 文件加载数据，支持嵌套和扁平两种布局
+    #[cfg(feature = "parquet")]
+    pub fn load_from_parquet(path: &str) -> Result<Self> {
+        let data = arrow_utils::read_stock_data_from_parquet(path)?;
+        Self::new_with_data(data)
+    }
+    
+    /// 从 SQLite 数据库加载数据，数据库由 `DataService` 写入
+    ///
+    /// `DataService` 同步写入时不删除旧记录，数据库中可能有比主数据更长的日线历史和已经不在列表中的证券，
+    /// 需要一致的数据时用 `SqliteStore::with_prune` 写入。
+    #[cfg(feature = "sqlite")]
+    pub fn load_from_sqlite(path: &str) -> Result<Self> {
+        let data = SqliteStore::new(path).load()?;
+        Self::new_with_data(data)
+    }
+    
+    /// 内存映射Arrow文件，按需解码单只股票，见 `MappedStockProvider`
+    pub fn open_mapped(path: &str) -> Result<MappedStockProvider> {
+        MappedStockProvider::open(path)
+    }
+    
+    /// 从基础文件加载数据，并合并同目录下的 `delta-YYYYMMDD.arrow` 增量文件
+    pub fn load_with_deltas(path: &str) -> Result<Self> {
+        let data = DeltaStore::new(path).load()?;
+        Self::new_with_data(data)
+    }
+    
+    /// 从分区数据集加载数据，只读取匹配过滤条件的分区
+    pub fn load_partitioned(root: &str, filter: &PartitionFilter) -> Result<Self> {
+        let data = PartitionedStore::new(root).load(filter)?;
+        Self::new_with_data(data)
+    }
+    
+    /// 只加载归档中的完整历史，可按交易所和年份过滤
+    pub fn load_archive(root: &str, filter: &PartitionFilter) -> Result<Self> {
+        let data = ArchiveStore::new(root).load(filter)?;
+        Self::new_with_data(data)
+    }
+    
+    /// 加载归档并合并主数据文件，用于需要超过滚动窗口长度的长周期回测
+    pub fn load_with_archive(path: &str, archive_root: &str, filter: &PartitionFilter) -> Result<Self> {
+        let hot = arrow_utils::read_stock_data_from_arrow(path)?;
+        let data = ArchiveStore::new(archive_root).load_merged(hot, filter)?;
+        Self::new_with_data(data)
+    }
+    
+    /// 加载指定日期当天有效的快照，即不晚于该日期的最近一个快照，用于复现当时的数据
+    ///
+    /// 快照目录为数据目录下的 `snapshots`，数据目录可由环境变量 `EGOSTRATEGY_DATA_DIR` 指定。
+    pub fn load_snapshot(date: TradeDate) -> Result<Self> {
+        Self::load_snapshot_from(&Config::from_env(), date)
+    }
+    
+    /// 从配置的数据目录加载快照
+    pub fn load_snapshot_from(config: &Config, date: TradeDate) -> Result<Self> {
+        let data = SnapshotStore::from_config(config).load(date)?;
+        Self::new_with_data(data)
+    }
+    
+    /// 保存数据到文件，并在旁边发布清单
     pub fn save_to_file(&self, path: &str) -> Result<()> {
-        // 确保目录存在
-        if let Some(parent) = Path::new(path).parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent)?;
-            }
-        }
-        
-        arrow_utils::save_stock_data_to_arrow(&self.data, path)
+        save_dataset(&self.data, path, 0, IpcCompression::None)?;
+        Ok(())
+    }
+    
+    /// 按配置保存数据文件：轮换备份，发布清单，按需发布压缩版本
+    pub fn save_to_file_with_config(&self, path: &str, config: &Config) -> Result<()> {
+        save_dataset(&self.data, path, config.backups, config.compressed_variant)?;
+        Ok(())
     }
     
     /// 获取所有股票列表
@@ -138,14 +251,38 @@ impl StockDataProvider {
         &self.data
     }
     
-    /// 获取指定股票
+    /// 获取指定股票，支持 600519.SH、sh600519、1.600519 等写法；
+    /// 纯代码按号段推断交易所
     pub fn get_stock_by_symbol(&self, symbol: &str) -> Option<&StockData> {
-        self.symbol_index.get(symbol).map(|&idx| &self.data[idx])
+        let id = SecurityId::parse(symbol).ok()?;
+        self.get_stock(&id)
     }
     
-    /// 获取指定交易所的股票
+    /// 按证券标识获取股票
+    pub fn get_stock(&self, id: &SecurityId) -> Option<&StockData> {
+        self.symbol_index.get(id).map(|&idx| &self.data[idx])
+    }
+    
+    /// 获取股票在指定日期时的名称，用于回测中判断 ST 等状态
+    pub fn get_name_as_of(&self, symbol: &str, date: TradeDate) -> Option<&str> {
+        self.get_stock_by_symbol(symbol).map(|stock| stock.name_as_of(date))
+    }
+    
+    /// 获取股票的名称历史
+    pub fn get_name_history(&self, symbol: &str) -> Option<&[NameChange]> {
+        self.get_stock_by_symbol(symbol).map(|stock| stock.name_history.as_slice())
+    }
+    
+    /// 获取指定交易所的股票，支持 "SSE"、"sh" 等写法
     pub fn get_stocks_by_exchange(&self, exchange: &str) -> Vec<&StockData> {
-        self.exchange_index.get(exchange)
+        exchange.parse::<Exchange>()
+            .map(|exchange| self.get_stocks_in(exchange))
+            .unwrap_or_default()
+    }
+    
+    /// 获取指定交易所的股票
+    pub fn get_stocks_in(&self, exchange: Exchange) -> Vec<&StockData> {
+        self.exchange_index.get(&exchange)
             .map(|indices| indices.iter().map(|&idx| &self.data[idx]).collect())
             .unwrap_or_default()
     }
@@ -156,22 +293,22 @@ impl StockDataProvider {
         self.exchange_index.clear();
         
         for (i, stock) in self.data.iter().enumerate() {
-            self.symbol_index.insert(stock.symbol.clone(), i);
+            self.symbol_index.insert(stock.security_id(), i);
             
             self.exchange_index
-                .entry(stock.exchange.clone())
-                .or_insert_with(Vec::new)
+                .entry(stock.exchange)
+                .or_default()
                 .push(i);
         }
     }
     
     /// 获取最新日期
-    pub fn get_latest_trading_date(&self) -> Option<i32> {
+    pub fn get_latest_trading_date(&self) -> Option<TradeDate> {
         Self::get_latest_date_from_data(&self.data)
     }
     
     /// 从数据中获取最新日期（辅助函数）
-    fn get_latest_date_from_data(data: &[StockData]) -> Option<i32> {
+    fn get_latest_date_from_data(data: &[StockData]) -> Option<TradeDate> {
         let mut latest_date = None;
         
         for stock in data {
@@ -189,16 +326,45 @@ impl StockDataProvider {
         latest_date
     }
     
+    // 镜像站点上的数据目录，按优先级排序
+    fn mirror_urls() -> Vec<String> {
+        // 尝试多个国内镜像站点，按优先级排序
+        let mirror_sites = [
+            "raw.githubusercontent.com",
+            "raw.bgithub.xyz",
+            "raw.staticdn.net"
+        ];
+        mirror_sites.iter()
+            .map(|mirror| format!("https://{}/EgoStrategy/DataHub/main/docs/data", mirror))
+            .collect()
+    }
+    
+    // 远程数据文件，能解压时优先使用压缩版本
+    fn remote_data_files() -> Vec<String> {
+        let mut files = Vec::new();
+        if cfg!(feature = "ipc_compression") {
+            files.push(IpcCompression::Zstd.variant_path("stock.arrow"));
+        }
+        files.push("stock.arrow".to_string());
+        files
+    }
+    
     // 同步检查远程文件是否有更新
     fn check_for_updates_sync(arrow_file: &str, from_url: &str) -> Result<()> {
         let remote_url = from_url;
         
+        // 优先根据发布的清单判断，镜像站返回的文件大小和修改时间并不可靠
+        match Self::fetch_manifest_sync(&DatasetManifest::url_for(remote_url)) {
+            Ok(manifest) => return Self::update_from_manifest_sync(arrow_file, remote_url, &manifest),
+            Err(e) => info!("Dataset manifest unavailable ({}), falling back to HEAD check", e),
+        }
+        
         // 获取本地文件信息
         let local_metadata = match fs::metadata(arrow_file) {
             Ok(meta) => meta,
             Err(_) => {
                 // 本地文件不存在，直接下载
-                return Self::download_file_sync(remote_url, arrow_file);
+                return Self::download_file_sync(remote_url, arrow_file, None);
             }
         };
         
@@ -230,7 +396,7 @@ impl StockDataProvider {
         // 如果远程文件大小不同且不为0，下载新文件
         if remote_size != local_size && remote_size > 0 {
             info!("Remote stock.arrow file size differs. Downloading updates...");
-            return Self::download_file_sync(remote_url, arrow_file);
+            return Self::download_file_sync(remote_url, arrow_file, None);
         }
         
         // 获取远程文件修改时间
@@ -245,7 +411,7 @@ impl StockDataProvider {
                     // 比较修改时间
                     if remote_time > local_modified {
                         info!("Remote stock.arrow file is newer. Downloading updates...");
-                        return Self::download_file_sync(remote_url, arrow_file);
+                        return Self::download_file_sync(remote_url, arrow_file, None);
                     }
                 }
             }
@@ -254,8 +420,123 @@ impl StockDataProvider {
         Ok(())
     }
     
-    // 同步下载文件
-    fn download_file_sync(url: &str, arrow_file: &str) -> Result<()> {
+    // 同步获取远程清单
+    fn fetch_manifest_sync(url: &str) -> Result<DatasetManifest> {
+        let client = reqwest::blocking::Client::new();
+        let resp = client.get(url).send()?;
+        if !resp.status().is_success() {
+            return Err(DataHubError::DataError(format!("HTTP status {}", resp.status())));
+        }
+        
+        DatasetManifest::from_json(&resp.bytes()?)
+    }
+    
+    // 根据清单判断是否需要下载：内容相同、远程数据较旧或 Schema 版本过新时跳过，基础文件一致后同步清单中的增量文件
+    fn update_from_manifest_sync(arrow_file: &str, url: &str, manifest: &DatasetManifest) -> Result<()> {
+        if manifest.schema_version > arrow_utils::SCHEMA_VERSION {
+            warn!("Remote stock.arrow uses schema v{} (max supported v{}), please upgrade egostrategy_datahub",
+                  manifest.schema_version, arrow_utils::SCHEMA_VERSION);
+            return Ok(());
+        }
+        
+        if let Ok(bytes) = fs::read(arrow_file) {
+            if manifest.matches(&bytes) {
+                info!("Local stock.arrow matches the remote manifest");
+                return Self::sync_deltas_sync(arrow_file, url, manifest);
+            }
+            
+            let local_latest = arrow_utils::read_stock_data_from_memory(&bytes).ok()
+                .and_then(|data| Self::get_latest_date_from_data(&data));
+            if manifest.latest_date < local_latest {
+                info!("Remote stock.arrow is older than the local file, skipping download");
+                return Ok(());
+            }
+        }
+        
+        info!("Remote stock.arrow differs from the local file. Downloading updates...");
+        Self::download_file_sync(url, arrow_file, Some(manifest))?;
+        Self::sync_deltas_sync(arrow_file, url, manifest)
+    }
+    
+    // 下载清单中列出的增量文件并校验，删除远程已折叠进基础文件的本地增量
+    fn sync_deltas_sync(arrow_file: &str, url: &str, manifest: &DatasetManifest) -> Result<()> {
+        let store = DeltaStore::new(arrow_file);
+        let dir = Path::new(arrow_file).parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let base_url = url.rsplit_once('/').map_or("", |(base, _)| base);
+        let client = reqwest::blocking::Client::new();
+        
+        for entry in &manifest.deltas {
+            let path = entry.local_path(dir)?;
+            Self::download_entry_sync(&client, &format!("{}/{}", base_url, entry.file), &path, entry)?;
+        }
+        
+        // 清单中没有、且不晚于远程最新数据的本地增量已被折叠，较新的可能是本地尚未发布的增量，保留
+        let listed = |path: &Path| manifest.deltas.iter().any(|entry| path.ends_with(&entry.file));
+        let local = store.deltas()?;
+        let newest_remote = local.iter()
+            .filter(|(_, path)| listed(path))
+            .map(|(date, _)| *date)
+            .max()
+            .max(manifest.latest_date);
+        for (date, path) in local {
+            if !listed(&path) && newest_remote.is_some_and(|newest| date <= newest) {
+                info!("Removing delta file {} folded into the remote stock.arrow", path.display());
+                fs::remove_file(&path)?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    // 按远程分区清单下载过滤条件选中的分区，本地内容一致的分区跳过，最后保存远程清单
+    fn sync_partitions_sync(store: &PartitionedStore, base_url: &str, filter: &PartitionFilter) -> Result<()> {
+        let client = reqwest::blocking::Client::new();
+        let resp = client.get(format!("{}/{}", base_url, PARTITION_MANIFEST_FILE)).send()?;
+        if !resp.status().is_success() {
+            return Err(DataHubError::DataError(format!("HTTP status {}", resp.status())));
+        }
+        let manifest = PartitionManifest::from_json(&resp.bytes()?)?;
+        if manifest.schema_version > arrow_utils::SCHEMA_VERSION {
+            warn!("Remote partitions use schema v{} (max supported v{}), please upgrade egostrategy_datahub",
+                  manifest.schema_version, arrow_utils::SCHEMA_VERSION);
+            return Ok(());
+        }
+        
+        for entry in manifest.partitions.iter().filter(|p| filter.matches(p.key())) {
+            let path = store.partition_path(entry)?;
+            Self::download_entry_sync(&client, &format!("{}/{}", base_url, entry.path), &path, &entry.file_entry())?;
+        }
+        
+        manifest.save(&store.manifest_path())
+    }
+    
+    // 本地文件与记录不一致时下载，校验字节数和 sha256 后原子替换
+    fn download_entry_sync(client: &reqwest::blocking::Client, url: &str, path: &Path, entry: &FileEntry) -> Result<()> {
+        if fs::read(path).is_ok_and(|bytes| entry.matches(&bytes)) {
+            return Ok(());
+        }
+        
+        info!("Downloading {} from: {}", entry.file, url);
+        let resp = client.get(url).send()?;
+        if !resp.status().is_success() {
+            return Err(DataHubError::DataError(format!(
+                "Failed to download {}: HTTP status {}", entry.file, resp.status()
+            )));
+        }
+        let bytes = resp.bytes()?;
+        entry.verify(&bytes)?;
+        fs_utils::write_atomic(
+            path,
+            0,
+            |file| Ok(file.write_all(&bytes)?),
+            |tmp| arrow_utils::validate_arrow_file(tmp).map(|_| ()),
+        )
+    }
+    
+    // 同步下载文件，提供清单时先校验字节数和 sha256
+    fn download_file_sync(url: &str, arrow_file: &str, manifest: Option<&DatasetManifest>) -> Result<()> {
         info!("Downloading stock data from: {}", url);
         
         // 下载文件
@@ -268,10 +549,112 @@ impl StockDataProvider {
         }
         
         let bytes = resp.bytes()?;
+        if let Some(manifest) = manifest {
+            manifest.verify(&bytes)?;
+        }
         
-        std::fs::write(&arrow_file, &bytes)?;
+        // 校验下载内容后再原子替换本地文件，下载中断或内容损坏时保留原文件
+        fs_utils::write_atomic(
+            Path::new(arrow_file),
+            fs_utils::DEFAULT_BACKUPS,
+            |file| Ok(file.write_all(&bytes)?),
+            |tmp| arrow_utils::validate_arrow_file(tmp).map(|_| ()),
+        )?;
+        if let Some(manifest) = manifest {
+            manifest.save(&DatasetManifest::path_for(Path::new(arrow_file)))?;
+        }
         
         info!("Successfully downloaded stock data file");
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // 从目录提供静态文件的最小 HTTP 服务，返回基础 URL
+    #[cfg(feature = "ipc_compression")]
+    fn serve_dir(dir: std::path::PathBuf) -> String {
+        use std::io::{BufRead, BufReader};
+        
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let name = request.split_whitespace().nth(1).unwrap_or("/").trim_start_matches('/');
+                match fs::read(dir.join(name)) {
+                    Ok(body) => {
+                        let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+                        let _ = stream.write_all(&body);
+                    }
+                    Err(_) => {
+                        let _ = write!(stream, "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                    }
+                }
+            }
+        });
+        format!("http://{}", addr)
+    }
+    
+    #[test]
+    fn remote_data_files_prefer_compressed_variant_when_supported() {
+        let files = StockDataProvider::remote_data_files();
+        assert_eq!(files.last().map(String::as_str), Some("stock.arrow"));
+        assert_eq!(files.len() == 2, cfg!(feature = "ipc_compression"));
+        if cfg!(feature = "ipc_compression") {
+            assert_eq!(files[0], "stock.zstd.arrow");
+        }
+    }
+    
+    #[cfg(feature = "ipc_compression")]
+    #[test]
+    fn downloads_compressed_variant_against_its_manifest() {
+        use crate::models::price::Price;
+        use crate::models::stock::DailyData;
+        
+        let root = std::env::temp_dir().join(format!("egostrategy_provider_variant_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let remote = root.join("remote");
+        let local = root.join("local");
+        fs::create_dir_all(&remote).unwrap();
+        fs::create_dir_all(&local).unwrap();
+        
+        let data = vec![StockData {
+            exchange: Exchange::Sse,
+            symbol: "600000".to_string(),
+            name: "A".to_string(),
+            daily: vec![DailyData { date: "2025-05-06".parse().unwrap(), close: Price::from_f64(10.0), ..DailyData::default() }],
+            name_history: Vec::new(),
+        }];
+        save_dataset(&data, remote.join("stock.arrow").to_str().unwrap(), 0, IpcCompression::Zstd).unwrap();
+        let base_url = serve_dir(remote.clone());
+        
+        let arrow_file = local.join("stock.arrow");
+        let arrow_str = arrow_file.to_str().unwrap();
+        let url = format!("{}/{}", base_url, StockDataProvider::remote_data_files()[0]);
+        StockDataProvider::check_for_updates_sync(arrow_str, &url).unwrap();
+        
+        // 本地保存的是压缩版本及其清单
+        let bytes = fs::read(&arrow_file).unwrap();
+        assert_eq!(bytes, fs::read(remote.join("stock.zstd.arrow")).unwrap());
+        DatasetManifest::load(&DatasetManifest::path_for(&arrow_file)).unwrap().verify(&bytes).unwrap();
+        assert_eq!(arrow_utils::read_stock_data_from_arrow(arrow_str).unwrap()[0].daily[0].close, Price::from_f64(10.0));
+        
+        // 远程文件被篡改时校验失败，本地文件保持不变
+        fs::write(remote.join("stock.zstd.arrow"), b"corrupted").unwrap();
+        fs::remove_file(&arrow_file).unwrap();
+        assert!(StockDataProvider::check_for_updates_sync(arrow_str, &url).is_err());
+        assert!(!arrow_file.exists());
+        
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
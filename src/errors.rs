@@ -15,6 +15,14 @@ pub enum DataHubError {
     #[error("Arrow error: {0}")]
     ArrowError(String),
 
+    #[cfg(feature = "parquet")]
+    #[error("Parquet error: {0}")]
+    ParquetError(#[from] parquet::errors::ParquetError),
+
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+
     #[error("Date parsing error: {0}")]
     DateError(#[from] chrono::ParseError),
 
@@ -2,6 +2,7 @@
 pub mod models;
 pub mod data_provider;
 pub mod errors;
+pub mod storage;
 
 // 为了支持主程序，暂时保持这些模块公开
 // 但在库使用场景中，这些应该是内部模块
@@ -15,6 +16,9 @@ pub mod services;
 pub mod util;
 
 // 重新导出常用类型，方便使用
-pub use models::stock::{StockData, DailyData};
-pub use data_provider::StockDataProvider;
+pub use models::stock::{StockData, DailyData, NameChange};
+pub use models::price::Price;
+pub use models::security::{Exchange, SecurityId};
+pub use models::trade_date::TradeDate;
+pub use data_provider::{MappedStockProvider, StockDataProvider};
 pub use errors::{Result, DataHubError};
@@ -1 +1,4 @@
+pub mod price;
+pub mod security;
 pub mod stock;
+pub mod trade_date;
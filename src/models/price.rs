@@ -0,0 +1,259 @@
+use crate::errors::{Result, DataHubError};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+
+/// 定点价格，以0.001元（厘）为单位的整数
+///
+/// f32 无法精确表示许多三位小数的价格，涨跌停价计算和盈亏对账会产生漂移，
+/// 因此模型和 Arrow 文件中的价格均使用整数存储，只在分析时转换为 f64。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Price(i32);
+
+impl Price {
+    /// 每元对应的最小单位数量
+    pub const SCALE: i32 = 1000;
+    /// 小数位数
+    pub const DECIMALS: usize = 3;
+    pub const ZERO: Price = Price(0);
+    
+    /// 从厘（0.001元）创建价格
+    pub const fn from_milli(milli: i32) -> Self {
+        Price(milli)
+    }
+    
+    /// 获取以厘（0.001元）为单位的整数值
+    pub const fn as_milli(self) -> i32 {
+        self.0
+    }
+    
+    /// 从浮点数创建价格，四舍五入到0.001元
+    ///
+    /// 超出范围时饱和到边界值，NaN 为0，只用于可信的输入；解析外部数据使用 `try_from_f64`。
+    pub fn from_f64(value: f64) -> Self {
+        Price((value * Self::SCALE as f64).round() as i32)
+    }
+    
+    /// 从浮点数创建价格，四舍五入到0.001元，非有限值或超出范围时返回错误
+    pub fn try_from_f64(value: f64) -> Result<Self> {
+        let milli = (value * Self::SCALE as f64).round();
+        if !milli.is_finite() || milli < i32::MIN as f64 || milli > i32::MAX as f64 {
+            return Err(DataHubError::DataError(format!("Price out of range: {}", value)));
+        }
+        Ok(Price(milli as i32))
+    }
+    
+    /// 转换为 f64，用于分析计算
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+    
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+    
+    /// 溢出时返回 None
+    pub fn checked_add(self, rhs: Price) -> Option<Price> {
+        self.0.checked_add(rhs.0).map(Price)
+    }
+    
+    /// 溢出时返回 None
+    pub fn checked_sub(self, rhs: Price) -> Option<Price> {
+        self.0.checked_sub(rhs.0).map(Price)
+    }
+}
+
+impl TryFrom<f64> for Price {
+    type Error = DataHubError;
+    
+    fn try_from(value: f64) -> Result<Self> {
+        Price::try_from_f64(value)
+    }
+}
+
+impl From<Price> for f64 {
+    fn from(price: Price) -> Self {
+        price.to_f64()
+    }
+}
+
+/// 饱和加法，超出约±214万元时停在边界而不是溢出，需要检测溢出时使用 `checked_add`
+impl Add for Price {
+    type Output = Price;
+    
+    fn add(self, rhs: Price) -> Price {
+        Price(self.0.saturating_add(rhs.0))
+    }
+}
+
+/// 饱和减法，溢出时停在边界，需要检测溢出时使用 `checked_sub`
+impl Sub for Price {
+    type Output = Price;
+    
+    fn sub(self, rhs: Price) -> Price {
+        Price(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 指定精度时按 f64 格式化，如 {:.2}
+        if f.precision().is_some() {
+            return fmt::Display::fmt(&self.to_f64(), f);
+        }
+        
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        let text = format!("{}{}.{:03}", sign, abs / Self::SCALE as u32, abs % Self::SCALE as u32);
+        f.pad(&text)
+    }
+}
+
+/// 精确解析十进制价格字符串，最多三位小数，不经过浮点数
+impl FromStr for Price {
+    type Err = DataHubError;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || DataHubError::DataError(format!("Invalid price: {}", s));
+        let text = s.trim().replace(',', "");
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.as_str()),
+        };
+        
+        let (int_part, frac_part) = digits.split_once('.').unwrap_or((digits, ""));
+        if int_part.is_empty() && frac_part.is_empty() {
+            return Err(invalid());
+        }
+        if !int_part.chars().chain(frac_part.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        
+        let int_value: i64 = if int_part.is_empty() { 0 } else { int_part.parse().map_err(|_| invalid())? };
+        
+        // 超过三位的小数按四舍五入处理
+        let mut frac_digits: Vec<u32> = frac_part.chars().filter_map(|c| c.to_digit(10)).collect();
+        let round_up = frac_digits.get(Self::DECIMALS).is_some_and(|&d| d >= 5);
+        frac_digits.resize(Self::DECIMALS, 0);
+        let frac_value = frac_digits.iter().fold(0i64, |acc, &d| acc * 10 + d as i64);
+        
+        let mut milli = int_value * Self::SCALE as i64 + frac_value + round_up as i64;
+        if negative {
+            milli = -milli;
+        }
+        
+        i32::try_from(milli).map(Price).map_err(|_| invalid())
+    }
+}
+
+/// 序列化为十进制数值，便于其他语言直接使用
+impl Serialize for Price {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+/// 支持数值和字符串两种形式，字符串按十进制精确解析
+impl<'de> Deserialize<'de> for Price {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct PriceVisitor;
+
+        impl Visitor<'_> for PriceVisitor {
+            type Value = Price;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a decimal price as number or string")
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> std::result::Result<Price, E> {
+                Price::try_from_f64(value).map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> std::result::Result<Price, E> {
+                Price::try_from_f64(value as f64).map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> std::result::Result<Price, E> {
+                Price::try_from_f64(value as f64).map_err(E::custom)
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<Price, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(PriceVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn parses_decimal_strings_exactly() {
+        assert_eq!("10.12".parse::<Price>().unwrap(), Price::from_milli(10_120));
+        assert_eq!(" 1,234.5 ".parse::<Price>().unwrap(), Price::from_milli(1_234_500));
+        assert_eq!("-0.01".parse::<Price>().unwrap(), Price::from_milli(-10));
+        assert_eq!(".5".parse::<Price>().unwrap(), Price::from_milli(500));
+        assert_eq!("7".parse::<Price>().unwrap(), Price::from_milli(7_000));
+        // 超过三位的小数四舍五入
+        assert_eq!("1.2345".parse::<Price>().unwrap(), Price::from_milli(1_235));
+        assert_eq!("1.2344".parse::<Price>().unwrap(), Price::from_milli(1_234));
+        
+        for text in ["", ".", "-", "abc", "1.2.3", "1e3", "+-1", "3000000"] {
+            assert!(text.parse::<Price>().is_err(), "{:?} was accepted", text);
+        }
+    }
+    
+    #[test]
+    fn rounds_floats_and_rejects_out_of_range() {
+        assert_eq!(Price::from_f64(10.1234), Price::from_milli(10_123));
+        assert_eq!(Price::from_f64(10.1235), Price::from_milli(10_124));
+        assert_eq!(Price::from_f64(-2.0005), Price::from_milli(-2_001));
+        assert_eq!(Price::try_from(0.1f32 as f64).unwrap(), Price::from_milli(100));
+        
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 2_147_484.0, -2_147_484.0] {
+            assert!(Price::try_from_f64(value).is_err(), "{} was accepted", value);
+        }
+        // from_f64 饱和到边界
+        assert_eq!(Price::from_f64(1e12), Price::from_milli(i32::MAX));
+        assert_eq!(Price::from_f64(f64::NAN), Price::ZERO);
+    }
+    
+    #[test]
+    fn arithmetic_saturates_and_checked_variants_detect_overflow() {
+        let a = Price::from_milli(10_500);
+        let b = Price::from_milli(10_000);
+        assert_eq!(a - b, Price::from_milli(500));
+        assert_eq!(b - a, Price::from_milli(-500));
+        assert_eq!(a + b, Price::from_milli(20_500));
+        
+        let max = Price::from_milli(i32::MAX);
+        let min = Price::from_milli(i32::MIN);
+        assert_eq!(max + a, max);
+        assert_eq!(min - a, min);
+        assert_eq!(max.checked_add(a), None);
+        assert_eq!(min.checked_sub(a), None);
+        assert_eq!(a.checked_sub(b), Some(Price::from_milli(500)));
+    }
+    
+    #[test]
+    fn display_uses_three_decimals() {
+        assert_eq!(Price::from_milli(10_120).to_string(), "10.120");
+        assert_eq!(Price::from_milli(-500).to_string(), "-0.500");
+        assert_eq!(Price::ZERO.to_string(), "0.000");
+        assert_eq!(format!("{:>8}", Price::from_milli(1_005)), "   1.005");
+        assert_eq!(format!("{:.2}", Price::from_milli(10_126)), "10.13");
+    }
+    
+    #[test]
+    fn deserializes_numbers_and_strings() {
+        let prices: Vec<Price> = serde_json::from_str(r#"[10.12, 3, "1.2345"]"#).unwrap();
+        assert_eq!(prices, [Price::from_milli(10_120), Price::from_milli(3_000), Price::from_milli(1_235)]);
+        assert_eq!(serde_json::to_string(&Price::from_milli(10_120)).unwrap(), "10.12");
+        assert!(serde_json::from_str::<Price>("1e12").is_err());
+    }
+}
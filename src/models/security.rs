@@ -0,0 +1,282 @@
+use crate::errors::{Result, DataHubError};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+
+/// 交易所
+///
+/// 内置的沪深北交易所之外，自定义抓取器使用 `Exchange::custom` 创建的交易所，
+/// 以大写代码（如 "HKEX"）写入数据文件。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Exchange {
+    /// 上海证券交易所
+    Sse,
+    /// 深圳证券交易所
+    Szse,
+    /// 北京证券交易所
+    Bse,
+    /// 自定义交易所，值为大写代码
+    Other(&'static str),
+}
+
+// 自定义交易所代码的最大长度
+const MAX_CUSTOM_CODE_LEN: usize = 16;
+
+// 自定义交易所代码在进程内去重后保存为 &'static str，使 Exchange 保持 Copy
+fn intern(code: String) -> &'static str {
+    static CODES: OnceLock<Mutex<Vec<&'static str>>> = OnceLock::new();
+    let mut codes = CODES.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(existing) = codes.iter().find(|c| **c == code) {
+        return existing;
+    }
+    let code: &'static str = Box::leak(code.into_boxed_str());
+    codes.push(code);
+    code
+}
+
+impl Exchange {
+    /// 内置交易所
+    pub const ALL: [Exchange; 3] = [Exchange::Sse, Exchange::Szse, Exchange::Bse];
+
+    /// 自定义交易所，代码为字母开头、不超过16个字符的字母和数字，不区分大小写
+    ///
+    /// 代码不能与内置交易所的代码或别名（如 "SH"）相同。字母开头避免与 1.600519 等写法中的代码混淆。
+    pub fn custom(code: &str) -> Result<Exchange> {
+        let code = code.trim().to_uppercase();
+        let valid = code.len() <= MAX_CUSTOM_CODE_LEN
+            && code.starts_with(|c: char| c.is_ascii_alphabetic())
+            && code.chars().all(|c| c.is_ascii_alphanumeric());
+        if !valid {
+            return Err(DataHubError::ExchangeError(format!("Invalid exchange code: {}", code)));
+        }
+        if let Some(builtin) = Self::builtin(&code) {
+            return Err(DataHubError::ExchangeError(format!(
+                "Exchange code {} is reserved for {}", code, builtin
+            )));
+        }
+        Ok(Exchange::Other(intern(code)))
+    }
+
+    // 内置交易所的代码和别名
+    fn builtin(code: &str) -> Option<Exchange> {
+        match code.trim().to_uppercase().as_str() {
+            "SSE" | "SH" | "SS" | "SHSE" => Some(Exchange::Sse),
+            "SZSE" | "SZ" => Some(Exchange::Szse),
+            "BSE" | "BJ" | "BJSE" => Some(Exchange::Bse),
+            _ => None,
+        }
+    }
+
+    /// 交易所代码，如 "SSE"，也是Arrow文件中的存储形式
+    pub fn code(self) -> &'static str {
+        match self {
+            Exchange::Sse => "SSE",
+            Exchange::Szse => "SZSE",
+            Exchange::Bse => "BSE",
+            Exchange::Other(code) => code,
+        }
+    }
+
+    /// 代码后缀，如 600519.SH 中的 "SH"，自定义交易所为其代码
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Exchange::Sse => "SH",
+            Exchange::Szse => "SZ",
+            Exchange::Bse => "BJ",
+            Exchange::Other(code) => code,
+        }
+    }
+
+    /// 代码前缀，如 sh600519 中的 "sh"，自定义交易所为小写代码
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Exchange::Sse => "sh",
+            Exchange::Szse => "sz",
+            Exchange::Bse => "bj",
+            Exchange::Other(code) => intern(code.to_lowercase()),
+        }
+    }
+
+    /// 东方财富 secid 中的市场编号，如 1.600519 中的 1，自定义交易所没有编号
+    pub fn market_id(self) -> Option<u8> {
+        match self {
+            Exchange::Sse => Some(1),
+            Exchange::Szse | Exchange::Bse => Some(0),
+            Exchange::Other(_) => None,
+        }
+    }
+
+    /// 根据证券代码的号段推断交易所
+    pub fn infer_from_symbol(symbol: &str) -> Option<Exchange> {
+        if symbol.len() != 6 || !symbol.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        if symbol.starts_with("92") {
+            return Some(Exchange::Bse);
+        }
+        match symbol.as_bytes()[0] {
+            b'5' | b'6' | b'9' => Some(Exchange::Sse),
+            b'0' | b'1' | b'2' | b'3' => Some(Exchange::Szse),
+            b'4' | b'8' => Some(Exchange::Bse),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Exchange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.code())
+    }
+}
+
+/// 解析交易所，接受 "SSE"、"SH"、"sh" 等写法，不区分大小写，其他合法代码解析为自定义交易所
+impl FromStr for Exchange {
+    type Err = DataHubError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::builtin(s) {
+            Some(exchange) => Ok(exchange),
+            None => Self::custom(s)
+                .map_err(|_| DataHubError::ExchangeError(format!("Unknown exchange: {}", s))),
+        }
+    }
+}
+
+impl Serialize for Exchange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Exchange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        code.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// 证券标识，由交易所和证券代码组成
+///
+/// 上交所指数 000001 与深交所平安银行 000001 代码相同，只有加上交易所才能唯一确定。
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SecurityId {
+    pub exchange: Exchange,
+    pub symbol: String,
+}
+
+impl SecurityId {
+    pub fn new(exchange: Exchange, symbol: &str) -> Self {
+        Self {
+            exchange,
+            symbol: symbol.to_string(),
+        }
+    }
+
+    /// 解析常见写法：600519.SH、sz000001、1.600519，以及按号段推断交易所的 600519
+    pub fn parse(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let invalid = || DataHubError::DataError(format!("Invalid security id: {}", s));
+
+        // 600519.SH / 1.600519
+        if let Some((left, right)) = s.split_once('.') {
+            if let Ok(exchange) = right.parse::<Exchange>() {
+                return Self::with_symbol(exchange, left);
+            }
+            let exchange = match left {
+                "1" => Exchange::Sse,
+                "0" if Exchange::infer_from_symbol(right) == Some(Exchange::Bse) => Exchange::Bse,
+                "0" => Exchange::Szse,
+                _ => return Err(invalid()),
+            };
+            return Self::with_symbol(exchange, right);
+        }
+
+        // sh600519 / SZ000001
+        if s.len() > 2 && s.is_char_boundary(2) {
+            let (prefix, symbol) = s.split_at(2);
+            if prefix.chars().all(|c| c.is_ascii_alphabetic()) {
+                let exchange = prefix.parse::<Exchange>().map_err(|_| invalid())?;
+                return Self::with_symbol(exchange, symbol);
+            }
+        }
+
+        // 600519
+        let exchange = Exchange::infer_from_symbol(s).ok_or_else(invalid)?;
+        Self::with_symbol(exchange, s)
+    }
+
+    fn with_symbol(exchange: Exchange, symbol: &str) -> Result<Self> {
+        if symbol.is_empty() || !symbol.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(DataHubError::DataError(format!("Invalid symbol: {}", symbol)));
+        }
+        Ok(Self::new(exchange, symbol))
+    }
+
+    /// 前缀写法，如 sh600519
+    pub fn to_prefixed(&self) -> String {
+        format!("{}{}", self.exchange.prefix(), self.symbol)
+    }
+
+    /// 东方财富 secid 写法，如 1.600519，自定义交易所没有 secid
+    pub fn to_secid(&self) -> Option<String> {
+        self.exchange.market_id().map(|id| format!("{}.{}", id, self.symbol))
+    }
+}
+
+/// 规范写法，如 600519.SH
+impl fmt::Display for SecurityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&format!("{}.{}", self.symbol, self.exchange.suffix()))
+    }
+}
+
+impl FromStr for SecurityId {
+    type Err = DataHubError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl Serialize for SecurityId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecurityId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn custom_exchange_codes() {
+        let hkex = Exchange::custom("hkex").unwrap();
+        assert_eq!(hkex, Exchange::Other("HKEX"));
+        assert_eq!(hkex.code(), "HKEX");
+        assert_eq!(hkex.prefix(), "hkex");
+        assert_eq!("HKEX".parse::<Exchange>().unwrap(), hkex);
+        assert_eq!(serde_json::to_string(&hkex).unwrap(), "\"HKEX\"");
+        
+        // 内置交易所的别名和非法代码
+        assert_eq!("sh".parse::<Exchange>().unwrap(), Exchange::Sse);
+        assert!(Exchange::custom("SZ").is_err());
+        assert!(Exchange::custom("").is_err());
+        assert!(Exchange::custom("1HK").is_err());
+        assert!(Exchange::custom("HK-EX").is_err());
+        
+        let id = SecurityId::parse("00700.HKEX").unwrap();
+        assert_eq!(id, SecurityId::new(hkex, "00700"));
+        assert_eq!(id.to_string(), "00700.HKEX");
+        assert_eq!(id.to_secid(), None);
+        assert_eq!(SecurityId::parse("1.600519").unwrap().to_secid().as_deref(), Some("1.600519"));
+    }
+}
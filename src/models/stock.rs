@@ -1,22 +1,255 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use crate::models::price::Price;
+use crate::models::security::{Exchange, SecurityId};
+use crate::models::trade_date::TradeDate;
 
 /// 日线数据结构
-#[derive(Debug, Clone, Serialize)]
+///
+/// 反序列化时日期、价格和成交量为必填字段，旧版本导出中缺少的前收盘价等字段取默认值。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct DailyData {
-    pub date: i32,
-    pub open: f32,  // 从f64改为f32，减少内存占用
-    pub high: f32,  // 从f64改为f32
-    pub low: f32,   // 从f64改为f32
-    pub close: f32, // 从f64改为f32
+    pub date: TradeDate,
+    pub open: Price,  // 定点价格，单位0.001元
+    pub high: Price,
+    pub low: Price,
+    pub close: Price,
     pub volume: i64,
     pub amount: i64,
+    #[serde(default)]
+    pub pre_close: Price,     // 前收盘价
+    #[serde(default)]
+    pub change: Price,        // 涨跌额
+    #[serde(default)]
+    pub change_pct: f32,      // 涨跌幅（%）
+    #[serde(default)]
+    pub turnover_rate: f32,   // 换手率（%），数据源未提供时为0
+}
+
+impl DailyData {
+    /// 成交均价（VWAP），成交量为0或均价超出价格范围时返回 None
+    pub fn vwap(&self) -> Option<Price> {
+        if self.volume <= 0 {
+            return None;
+        }
+        Price::try_from_f64(self.amount as f64 / self.volume as f64).ok()
+    }
+    
+    /// 根据前收盘价计算涨跌额和涨跌幅
+    pub fn update_change(&mut self) {
+        if self.pre_close.is_zero() {
+            return;
+        }
+        self.change = self.close - self.pre_close;
+        self.change_pct = (self.change.to_f64() / self.pre_close.to_f64() * 100.0) as f32;
+    }
+}
+
+/// 股票名称变更记录
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameChange {
+    pub effective_from: TradeDate,
+    pub name: String,
 }
 
 /// Stock data structure with nested daily data
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StockData {
-    pub exchange: String,
+    pub exchange: Exchange,
     pub symbol: String,
     pub name: String,
     pub daily: Vec<DailyData>,
+    /// 名称历史，按生效日期升序；从未改名的股票为空
+    #[serde(default)]
+    pub name_history: Vec<NameChange>,
+}
+
+impl StockData {
+    /// 证券标识（交易所 + 代码）
+    pub fn security_id(&self) -> SecurityId {
+        SecurityId::new(self.exchange, &self.symbol)
+    }
+    
+    /// 指定日期时的股票名称，早于全部记录时返回最早的已知名称
+    pub fn name_as_of(&self, date: TradeDate) -> &str {
+        let pos = self.name_history.partition_point(|c| c.effective_from <= date);
+        match pos {
+            0 => self.name_history.first().map(|c| c.name.as_str()).unwrap_or(&self.name),
+            _ => &self.name_history[pos - 1].name,
+        }
+    }
+    
+    /// 记录某个交易日观察到的名称，名称变化时写入名称历史
+    ///
+    /// 首次改名时，原名称从早于该日期的最早日线开始生效。指定日期之后还有日线、但没有更晚的名称记录时，
+    /// 原来的当前名称从下一条日线起重新生效，因此回补较早日期（如深交所除权除息日的 XD/DR 前缀名称）
+    /// 不会覆盖当前名称。`name` 始终是最后一条名称记录的名称。
+    pub fn record_name(&mut self, name: &str, date: TradeDate) {
+        if self.name_as_of(date) == name {
+            return;
+        }
+        
+        let current = self.name.clone();
+        let earliest = self.daily.iter().map(|d| d.date).filter(|&d| d < date).min();
+        let next_bar = self.daily.iter().map(|d| d.date).filter(|&d| d > date).min();
+        if self.name_history.is_empty() {
+            if earliest.is_none() && next_bar.is_none() {
+                // 没有其他日线，原名称没有可记录的生效日期
+                self.name = name.to_string();
+                return;
+            }
+            if let Some(since) = earliest {
+                self.name_history.push(NameChange { effective_from: since, name: current.clone() });
+            }
+        }
+        
+        let pos = self.name_history.partition_point(|c| c.effective_from <= date);
+        if pos > 0 && self.name_history[pos - 1].effective_from == date {
+            self.name_history[pos - 1].name = name.to_string();
+        } else {
+            self.name_history.insert(pos, NameChange { effective_from: date, name: name.to_string() });
+        }
+        
+        // 之后的日线没有名称记录时，原来的当前名称从下一条日线起重新生效
+        let has_later = self.name_history.last().is_some_and(|c| c.effective_from > date);
+        if let (false, Some(next_bar)) = (has_later, next_bar) {
+            self.name_history.push(NameChange { effective_from: next_bar, name: current });
+        }
+        
+        // 合并名称相同的相邻记录，保留较早的生效日期
+        self.name_history.dedup_by(|later, earlier| later.name == earlier.name);
+        if let Some(last) = self.name_history.last() {
+            self.name = last.name.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn date(s: &str) -> TradeDate {
+        s.parse().unwrap()
+    }
+    
+    fn stock(name: &str, dates: &[&str], history: &[(&str, &str)]) -> StockData {
+        let mut daily: Vec<DailyData> = dates.iter()
+            .map(|d| DailyData { date: date(d), ..DailyData::default() })
+            .collect();
+        daily.sort_by_key(|d| std::cmp::Reverse(d.date));
+        StockData {
+            exchange: Exchange::Sse,
+            symbol: "600000".to_string(),
+            name: name.to_string(),
+            daily,
+            name_history: history.iter()
+                .map(|(d, n)| NameChange { effective_from: date(d), name: n.to_string() })
+                .collect(),
+        }
+    }
+    
+    fn history(stock: &StockData) -> Vec<(String, String)> {
+        stock.name_history.iter().map(|c| (c.effective_from.to_string(), c.name.clone())).collect()
+    }
+    
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items.iter().map(|(d, n)| (d.to_string(), n.to_string())).collect()
+    }
+    
+    #[test]
+    fn backfill_older_name_without_history_keeps_current_name() {
+        let mut s = stock("New", &["2025-05-01", "2025-05-02", "2025-05-05"], &[]);
+        s.record_name("Old", date("2025-05-01"));
+        
+        assert_eq!(s.name, "New");
+        assert_eq!(history(&s), pairs(&[("2025-05-01", "Old"), ("2025-05-02", "New")]));
+        assert_eq!(s.name_as_of(date("2025-05-01")), "Old");
+        assert_eq!(s.name_as_of(date("2025-05-05")), "New");
+    }
+    
+    #[test]
+    fn backfill_middle_date_without_history_keeps_surrounding_names() {
+        let mut s = stock("A", &["2025-05-01", "2025-05-02", "2025-05-05", "2025-05-06"], &[]);
+        s.record_name("B", date("2025-05-05"));
+        
+        assert_eq!(s.name, "A");
+        assert_eq!(history(&s), pairs(&[("2025-05-01", "A"), ("2025-05-05", "B"), ("2025-05-06", "A")]));
+    }
+    
+    #[test]
+    fn backfill_older_name_with_history_keeps_current_name() {
+        let mut s = stock("B", &["2025-05-01", "2025-05-02", "2025-05-05"], &[("2025-05-01", "A"), ("2025-05-05", "B")]);
+        s.record_name("X", date("2025-05-02"));
+        
+        assert_eq!(s.name, "B");
+        assert_eq!(history(&s), pairs(&[("2025-05-01", "A"), ("2025-05-02", "X"), ("2025-05-05", "B")]));
+    }
+    
+    #[test]
+    fn backfill_after_last_history_entry_keeps_current_name() {
+        // 深交所除权除息日的名称带 XD 前缀，回补时晚于最后一条名称记录但早于最新日线
+        let mut s = stock("B", &["2025-05-01", "2025-05-05", "2025-05-07", "2025-05-10"], &[("2025-05-01", "A"), ("2025-05-05", "B")]);
+        s.record_name("XDB", date("2025-05-07"));
+        
+        assert_eq!(s.name, "B");
+        assert_eq!(history(&s), pairs(&[("2025-05-01", "A"), ("2025-05-05", "B"), ("2025-05-07", "XDB"), ("2025-05-10", "B")]));
+        assert_eq!(s.name_as_of(date("2025-05-07")), "XDB");
+        assert_eq!(s.name_as_of(date("2025-05-10")), "B");
+    }
+    
+    #[test]
+    fn same_day_rename_without_history() {
+        let mut s = stock("Old", &["2025-05-01", "2025-05-02"], &[]);
+        s.record_name("New", date("2025-05-02"));
+        
+        assert_eq!(s.name, "New");
+        assert_eq!(history(&s), pairs(&[("2025-05-01", "Old"), ("2025-05-02", "New")]));
+    }
+    
+    #[test]
+    fn same_day_rename_with_history() {
+        let mut s = stock("B", &["2025-05-01", "2025-05-02"], &[("2025-05-01", "A"), ("2025-05-02", "B")]);
+        s.record_name("C", date("2025-05-02"));
+        
+        assert_eq!(s.name, "C");
+        assert_eq!(history(&s), pairs(&[("2025-05-01", "A"), ("2025-05-02", "C")]));
+    }
+    
+    #[test]
+    fn forward_rename_without_history() {
+        let mut s = stock("Old", &["2025-05-01", "2025-05-02"], &[]);
+        s.record_name("New", date("2025-05-05"));
+        
+        assert_eq!(s.name, "New");
+        assert_eq!(history(&s), pairs(&[("2025-05-01", "Old"), ("2025-05-05", "New")]));
+    }
+    
+    #[test]
+    fn forward_rename_with_history() {
+        let mut s = stock("B", &["2025-05-01", "2025-05-02"], &[("2025-05-01", "A"), ("2025-05-02", "B")]);
+        s.record_name("C", date("2025-05-05"));
+        
+        assert_eq!(s.name, "C");
+        assert_eq!(history(&s), pairs(&[("2025-05-01", "A"), ("2025-05-02", "B"), ("2025-05-05", "C")]));
+    }
+    
+    #[test]
+    fn consecutive_backfills_merge_repeated_names() {
+        let mut s = stock("New", &["2025-05-01", "2025-05-02", "2025-05-03", "2025-05-10"], &[]);
+        s.record_name("Old", date("2025-05-01"));
+        s.record_name("Old", date("2025-05-02"));
+        assert_eq!(s.name, "New");
+        s.record_name("New", date("2025-05-03"));
+        
+        assert_eq!(s.name, "New");
+        assert_eq!(history(&s), pairs(&[("2025-05-01", "Old"), ("2025-05-03", "New")]));
+    }
+    
+    #[test]
+    fn unchanged_name_records_nothing() {
+        let mut s = stock("A", &["2025-05-01"], &[]);
+        s.record_name("A", date("2025-05-05"));
+        
+        assert_eq!(s.name, "A");
+        assert!(s.name_history.is_empty());
+    }
 }
@@ -0,0 +1,138 @@
+use crate::errors::{Result, DataHubError};
+use chrono::{Datelike, NaiveDate};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// 交易日期
+///
+/// 交易所接口和 v4 及更早的数据文件使用 YYYYMMDD 形式的 i32，这里统一转换为经过校验的日期；
+/// 数据文件和 CSV 导出写入 Arrow Date32（自1970-01-01起的天数）。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TradeDate(NaiveDate);
+
+impl TradeDate {
+    pub fn new(date: NaiveDate) -> Self {
+        TradeDate(date)
+    }
+
+    /// 从 YYYYMMDD 形式的整数创建，如 20250516
+    pub fn from_yyyymmdd(value: i32) -> Result<Self> {
+        let (year, month, day) = (value / 10000, (value / 100 % 100) as u32, (value % 100) as u32);
+        if !(1000..=9999).contains(&year) {
+            return Err(DataHubError::DataError(format!("Invalid date format: {}", value)));
+        }
+        NaiveDate::from_ymd_opt(year, month, day)
+            .map(TradeDate)
+            .ok_or_else(|| DataHubError::DataError(format!("Invalid date: {}", value)))
+    }
+
+    /// 转换为 YYYYMMDD 形式的整数
+    pub fn to_yyyymmdd(self) -> i32 {
+        self.0.year() * 10000 + self.0.month() as i32 * 100 + self.0.day() as i32
+    }
+
+    /// 从 Arrow Date32（自1970-01-01起的天数）创建
+    pub fn from_date32(days: i32) -> Result<Self> {
+        NaiveDate::from_num_days_from_ce_opt(days + Self::UNIX_EPOCH_DAYS_FROM_CE)
+            .map(TradeDate)
+            .ok_or_else(|| DataHubError::DataError(format!("Invalid Date32 value: {}", days)))
+    }
+
+    /// 转换为 Arrow Date32（自1970-01-01起的天数）
+    pub fn to_date32(self) -> i32 {
+        self.0.num_days_from_ce() - Self::UNIX_EPOCH_DAYS_FROM_CE
+    }
+
+    pub fn naive_date(self) -> NaiveDate {
+        self.0
+    }
+
+    // 1970-01-01 距公元元年的天数
+    const UNIX_EPOCH_DAYS_FROM_CE: i32 = 719_163;
+}
+
+impl From<NaiveDate> for TradeDate {
+    fn from(date: NaiveDate) -> Self {
+        TradeDate(date)
+    }
+}
+
+impl From<TradeDate> for NaiveDate {
+    fn from(date: TradeDate) -> Self {
+        date.0
+    }
+}
+
+impl TryFrom<i32> for TradeDate {
+    type Error = DataHubError;
+
+    fn try_from(value: i32) -> Result<Self> {
+        Self::from_yyyymmdd(value)
+    }
+}
+
+impl From<TradeDate> for i32 {
+    fn from(date: TradeDate) -> Self {
+        date.to_yyyymmdd()
+    }
+}
+
+/// 格式化为 YYYY-MM-DD
+impl fmt::Display for TradeDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&self.0.format("%Y-%m-%d").to_string())
+    }
+}
+
+/// 解析 YYYY-MM-DD 或 YYYYMMDD
+impl FromStr for TradeDate {
+    type Err = DataHubError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.len() == 8 && s.chars().all(|c| c.is_ascii_digit()) {
+            return Self::from_yyyymmdd(s.parse::<i32>()?);
+        }
+        Ok(TradeDate(NaiveDate::parse_from_str(s, "%Y-%m-%d")?))
+    }
+}
+
+/// 序列化为 YYYYMMDD 整数，与旧版数据保持一致
+impl Serialize for TradeDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.to_yyyymmdd())
+    }
+}
+
+/// 支持 YYYYMMDD 整数以及 YYYY-MM-DD / YYYYMMDD 字符串
+impl<'de> Deserialize<'de> for TradeDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct TradeDateVisitor;
+
+        impl Visitor<'_> for TradeDateVisitor {
+            type Value = TradeDate;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a YYYYMMDD integer or a YYYY-MM-DD string")
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> std::result::Result<TradeDate, E> {
+                let value = i32::try_from(value).map_err(E::custom)?;
+                TradeDate::from_yyyymmdd(value).map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> std::result::Result<TradeDate, E> {
+                let value = i32::try_from(value).map_err(E::custom)?;
+                TradeDate::from_yyyymmdd(value).map_err(E::custom)
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<TradeDate, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(TradeDateVisitor)
+    }
+}
@@ -1,18 +1,41 @@
 use crate::models::stock::{StockData, DailyData};
+use crate::models::security::Exchange;
 use crate::errors::Result;
+use crate::scrapers::canary::{self, SourceCheck};
 use async_trait::async_trait;
 use chrono::NaiveDate;
 
 /// Base trait for stock data scrapers
 #[async_trait]
 pub trait StockScraper {
+    /// Get the exchange this scraper is for
+    fn exchange(&self) -> Exchange;
+    
     /// Get the exchange code this scraper is for
-    fn exchange_code(&self) -> &'static str;
+    fn exchange_code(&self) -> &'static str {
+        self.exchange().code()
+    }
     
     /// Fetch stock list for the given date
     async fn fetch_stock_list(&self, date: &NaiveDate) -> Result<Vec<StockData>>;
     
+    /// Whether `fetch_stock_list` can return snapshots for past dates.
+    /// Backfill skips scrapers that only serve the current trading day.
+    fn has_historical_snapshots(&self) -> bool {
+        true
+    }
+    
     /// Fetch historical data for a specific stock
     /// Returns daily data for the specified stock
     async fn fetch_stock_history(&self, symbol: &str) -> Result<Vec<DailyData>>;
+    
+    /// Fetch one sample for the given date and check it against expectations
+    /// before a full run. The default implementation checks value ranges only.
+    async fn check_source(&self, date: &NaiveDate) -> Result<SourceCheck> {
+        let stocks = self.fetch_stock_list(date).await?;
+        let mut check = SourceCheck::new(self.exchange_code());
+        check.sample_size = stocks.len();
+        check.issues = canary::check_value_ranges(&stocks);
+        Ok(check)
+    }
 }
@@ -0,0 +1,212 @@
+use crate::config::Config;
+use crate::errors::{Result, DataHubError};
+use crate::util::{self, fs_utils};
+use log::debug;
+use reqwest::{Client, RequestBuilder};
+use std::fs;
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// HTTP原始响应的磁盘缓存，按URL（含查询参数）索引
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+    offline: bool,
+}
+
+impl ResponseCache {
+    /// 创建缓存，`ttl` 为缓存条目的有效期
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl,
+            offline: false,
+        }
+    }
+    
+    /// 离线模式：忽略有效期，只从缓存读取，未命中时报错
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+    
+    /// 根据配置创建缓存，未启用缓存且非离线模式时返回 None
+    pub fn from_config(config: &Config) -> Option<Arc<Self>> {
+        if !config.http_cache && !config.offline {
+            return None;
+        }
+        
+        let cache = Self::new(Self::dir_for(config), Duration::from_secs(config.http_cache_ttl))
+            .with_offline(config.offline);
+        Some(Arc::new(cache))
+    }
+    
+    /// 配置对应的缓存目录
+    pub fn dir_for(config: &Config) -> PathBuf {
+        PathBuf::from(config.cache_dir())
+    }
+    
+    /// 发送请求并返回响应内容，启用缓存时优先读取缓存，成功的响应写入缓存
+    ///
+    /// `throttle` 是抓取器的限频等待，只在需要实际请求时执行。
+    pub async fn send(
+        cache: Option<&Self>,
+        client: &Client,
+        request: RequestBuilder,
+        throttle: impl Future<Output = ()>,
+    ) -> Result<Vec<u8>> {
+        let request = request.build()?;
+        let url = request.url().to_string();
+        
+        if let Some(cache) = cache {
+            if let Some(body) = cache.get(&url)? {
+                return Ok(body);
+            }
+        }
+        
+        // 限制请求频率
+        throttle.await;
+        
+        let response = client.execute(request).await?;
+        let status = response.status();
+        let body = response.bytes().await?.to_vec();
+        
+        if let Some(cache) = cache {
+            if status.is_success() {
+                cache.put(&url, &body)?;
+            }
+        }
+        
+        Ok(body)
+    }
+    
+    /// 是否处于离线模式
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+    
+    /// 读取缓存的响应，过期或不存在时返回 None；离线模式下未命中返回错误
+    pub fn get(&self, url: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path_for(url);
+        
+        let fresh = match fs::metadata(&path).and_then(|m| m.modified()) {
+            Ok(modified) => self.offline || SystemTime::now()
+                .duration_since(modified)
+                .map(|age| age <= self.ttl)
+                .unwrap_or(true),
+            Err(_) => false,
+        };
+        
+        if fresh {
+            debug!("Cache hit: {}", url);
+            return Ok(Some(fs::read(&path)?));
+        }
+        
+        if self.offline {
+            return Err(DataHubError::DataError(format!(
+                "Offline mode: no cached response for {}", url
+            )));
+        }
+        
+        Ok(None)
+    }
+    
+    /// 原子写入响应到缓存，中断时不会留下截断的条目
+    pub fn put(&self, url: &str, body: &[u8]) -> Result<()> {
+        fs_utils::write_atomic(&self.path_for(url), 0, |file| Ok(file.write_all(body)?), |_| Ok(()))
+    }
+    
+    /// 清空缓存目录，返回删除的条目数量
+    pub fn clear(&self) -> Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        
+        let mut count = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.is_file() {
+                fs::remove_file(path)?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+    
+    // 缓存文件路径，使用URL的FNV-1a哈希作为文件名
+    fn path_for(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}", util::fnv1a64(url.as_bytes())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    
+    const URL: &str = "https://example.com/list?date=2025-05-16";
+    
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("egostrategy_cache_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+    
+    #[test]
+    fn entries_expire_after_ttl_except_offline() {
+        let dir = temp_dir("ttl");
+        let cache = ResponseCache::new(&dir, Duration::from_secs(3600));
+        assert_eq!(cache.get(URL).unwrap(), None);
+        
+        cache.put(URL, b"body").unwrap();
+        assert_eq!(cache.get(URL).unwrap().as_deref(), Some(&b"body"[..]));
+        // 只留下缓存条目，没有临时文件
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        
+        let modified = SystemTime::now() - Duration::from_secs(7200);
+        File::options().write(true).open(cache.path_for(URL)).unwrap().set_modified(modified).unwrap();
+        assert_eq!(cache.get(URL).unwrap(), None);
+        
+        // 离线模式忽略有效期
+        let offline = ResponseCache::new(&dir, Duration::from_secs(3600)).with_offline(true);
+        assert_eq!(offline.get(URL).unwrap().as_deref(), Some(&b"body"[..]));
+        
+        // 过期后重新写入覆盖旧条目
+        cache.put(URL, b"new").unwrap();
+        assert_eq!(cache.get(URL).unwrap().as_deref(), Some(&b"new"[..]));
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn offline_miss_is_an_error() {
+        let dir = temp_dir("offline");
+        let cache = ResponseCache::new(&dir, Duration::from_secs(3600)).with_offline(true);
+        assert!(cache.is_offline());
+        assert!(cache.get(URL).is_err());
+        
+        cache.put(URL, b"body").unwrap();
+        assert!(cache.get(URL).unwrap().is_some());
+        assert!(cache.get("https://example.com/other").is_err());
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn clear_removes_all_entries() {
+        let dir = temp_dir("clear");
+        let cache = ResponseCache::new(&dir, Duration::from_secs(3600));
+        assert_eq!(cache.clear().unwrap(), 0);
+        
+        cache.put(URL, b"a").unwrap();
+        cache.put("https://example.com/other", b"b").unwrap();
+        assert_eq!(cache.clear().unwrap(), 2);
+        assert_eq!(cache.get(URL).unwrap(), None);
+        assert_eq!(cache.clear().unwrap(), 0);
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use crate::models::stock::{StockData, DailyData};
+use serde_json::Value;
+
+/// 单个数据源最多报告的问题数量
+const MAX_REPORTED_ISSUES: usize = 20;
+
+// 收集问题，超过上限后只计数
+#[derive(Default)]
+struct Issues {
+    reported: Vec<String>,
+    total: usize,
+}
+
+impl Issues {
+    fn push(&mut self, issue: String) {
+        self.total += 1;
+        if self.reported.len() < MAX_REPORTED_ISSUES {
+            self.reported.push(issue);
+        }
+    }
+    
+    fn finish(mut self) -> Vec<String> {
+        if self.total > self.reported.len() {
+            let more = self.total - self.reported.len();
+            self.reported.push(format!("... and {} more issues", more));
+        }
+        self.reported
+    }
+}
+
+/// JSON 数组行中一列的预期类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonType {
+    /// 非空字符串
+    String,
+    /// 整数或小数
+    Number,
+    /// 整数
+    Integer,
+}
+
+impl JsonType {
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            JsonType::String => value.as_str().is_some_and(|s| !s.trim().is_empty()),
+            JsonType::Number => value.is_number(),
+            JsonType::Integer => value.is_i64() || value.is_u64(),
+        }
+    }
+}
+
+/// 数据源结构检查结果
+#[derive(Debug, Clone)]
+pub struct SourceCheck {
+    pub exchange: String,
+    pub sample_size: usize,
+    pub issues: Vec<String>,
+}
+
+impl SourceCheck {
+    pub fn new(exchange: &str) -> Self {
+        Self {
+            exchange: exchange.to_string(),
+            sample_size: 0,
+            issues: Vec::new(),
+        }
+    }
+    
+    /// 是否通过全部检查
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// 检查样本数据的取值范围，返回发现的问题
+pub fn check_value_ranges(stocks: &[StockData]) -> Vec<String> {
+    let mut issues = Issues::default();
+    
+    if stocks.is_empty() {
+        issues.push("No stocks returned by source".to_string());
+        return issues.finish();
+    }
+    
+    for stock in stocks {
+        if stock.symbol.len() != 6 || !stock.symbol.chars().all(|c| c.is_ascii_digit()) {
+            issues.push(format!("{}: symbol is not a 6-digit code", stock.symbol));
+        }
+        
+        if stock.name.trim().is_empty() {
+            issues.push(format!("{}: empty name", stock.symbol));
+        }
+        
+        for daily in &stock.daily {
+            for problem in check_daily_bar(daily) {
+                issues.push(format!("{} {}: {}", stock.symbol, daily.date, problem));
+            }
+        }
+    }
+    
+    issues.finish()
+}
+
+/// 检查 JSON 数组形式的行：每行的长度和各列的类型
+///
+/// 解析时缺失、为 null 或类型不符的值会被默认值（如0）替代，进而被当作停牌跳过取值检查，
+/// 因此这里逐个计为问题。行以第一列（证券代码）标识。
+pub fn check_json_rows(rows: &[Value], columns: &[(&str, JsonType)]) -> Vec<String> {
+    let mut issues = Issues::default();
+    
+    if rows.is_empty() {
+        issues.push("No rows returned by source".to_string());
+        return issues.finish();
+    }
+    
+    for (i, row) in rows.iter().enumerate() {
+        let Some(values) = row.as_array() else {
+            issues.push(format!("row {}: expected an array, got {}", i, row));
+            continue;
+        };
+        let label = values.first().and_then(|v| v.as_str()).map_or_else(|| format!("row {}", i), str::to_string);
+        if values.len() != columns.len() {
+            issues.push(format!("{}: {} columns, expected {}", label, values.len(), columns.len()));
+        }
+        
+        for (idx, (name, expected)) in columns.iter().enumerate() {
+            match values.get(idx) {
+                Some(value) if expected.matches(value) => {}
+                Some(value) => issues.push(format!("{}: column {} is not {:?}: {}", label, name, expected, value)),
+                None => issues.push(format!("{}: column {} is missing", label, name)),
+            }
+        }
+    }
+    
+    issues.finish()
+}
+
+// 检查单条日线数据的取值是否合理
+fn check_daily_bar(daily: &DailyData) -> Vec<String> {
+    let mut problems = Vec::new();
+    let (open, high, low, close) = (daily.open.to_f64(), daily.high.to_f64(), daily.low.to_f64(), daily.close.to_f64());
+    let (volume, amount) = (daily.volume, daily.amount);
+    
+    if volume < 0 || amount < 0 {
+        problems.push(format!("negative volume/amount ({}, {})", volume, amount));
+    }
+    
+    // 停牌股票的价格和成交量均为0，无需继续检查；没有成交量却有成交额说明列错位或单位错误
+    if volume == 0 {
+        if amount != 0 {
+            problems.push(format!("amount {} without volume", amount));
+        }
+        return problems;
+    }
+    
+    if [open, high, low, close].iter().any(|p| !(0.0..100_000.0).contains(p) || *p == 0.0) {
+        problems.push(format!("price out of range (o={}, h={}, l={}, c={})", open, high, low, close));
+        return problems;
+    }
+    
+    if low > high || open < low || open > high || close < low || close > high {
+        problems.push(format!("inconsistent OHLC (o={}, h={}, l={}, c={})", open, high, low, close));
+    }
+    
+    // 成交均价应落在最低价和最高价附近，用于发现成交量/成交额单位或列错位
+    let avg_price = amount as f64 / volume as f64;
+    if avg_price < low * 0.5 || avg_price > high * 2.0 {
+        problems.push(format!("average price {:.3} outside [{}, {}]", avg_price, low, high));
+    }
+    
+    problems
+}
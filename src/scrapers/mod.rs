@@ -1,3 +1,8 @@
 pub mod base;
+pub mod cache;
+pub mod canary;
+pub mod registry;
 pub mod sse;
 pub mod szse;
+
+pub use registry::ScraperRegistry;
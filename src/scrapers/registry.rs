@@ -0,0 +1,123 @@
+use crate::scrapers::base::StockScraper;
+use crate::scrapers::cache::ResponseCache;
+use crate::scrapers::sse::SSEScraper;
+use crate::scrapers::szse::SZSEScraper;
+use crate::errors::{Result, DataHubError};
+use crate::config::Config;
+use std::sync::Arc;
+
+/// 抓取器工厂函数，根据配置按需创建抓取器实例
+pub type ScraperFactory = Box<dyn Fn(&Config) -> Result<Arc<dyn StockScraper + Send + Sync>> + Send + Sync>;
+
+/// 注册表中的单个抓取器条目
+pub struct ScraperEntry {
+    name: String,
+    description: String,
+    factory: ScraperFactory,
+}
+
+impl ScraperEntry {
+    /// 抓取器名称（不区分大小写）
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    
+    /// 抓取器描述
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+    
+    /// 创建抓取器实例
+    pub fn create(&self, config: &Config) -> Result<Arc<dyn StockScraper + Send + Sync>> {
+        (self.factory)(config)
+    }
+}
+
+/// 抓取器注册表，按名称插入交易所数据源
+pub struct ScraperRegistry {
+    entries: Vec<ScraperEntry>,
+}
+
+impl Default for ScraperRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScraperRegistry {
+    /// 创建空的注册表
+    pub fn new() -> Self {
+        Self { entries: Vec::new() }
+    }
+    
+    /// 创建包含内置交易所（sse、szse）的注册表
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register("sse", "上海证券交易所", |config| {
+            let scraper = SSEScraper::new()?.with_cache(ResponseCache::from_config(config));
+            Ok(Arc::new(scraper) as Arc<dyn StockScraper + Send + Sync>)
+        });
+        registry.register("szse", "深圳证券交易所", |config| {
+            let scraper = SZSEScraper::new()?.with_cache(ResponseCache::from_config(config));
+            Ok(Arc::new(scraper) as Arc<dyn StockScraper + Send + Sync>)
+        });
+        registry
+    }
+    
+    /// 注册抓取器，同名条目会被替换
+    pub fn register<F>(&mut self, name: &str, description: &str, factory: F) -> &mut Self
+    where
+        F: Fn(&Config) -> Result<Arc<dyn StockScraper + Send + Sync>> + Send + Sync + 'static,
+    {
+        let entry = ScraperEntry {
+            name: name.to_lowercase(),
+            description: description.to_string(),
+            factory: Box::new(factory),
+        };
+        
+        match self.entries.iter().position(|e| e.name == entry.name) {
+            Some(idx) => self.entries[idx] = entry,
+            None => self.entries.push(entry),
+        }
+        self
+    }
+    
+    /// 获取所有已注册的抓取器条目
+    pub fn entries(&self) -> &[ScraperEntry] {
+        &self.entries
+    }
+    
+    /// 获取所有已注册的抓取器名称
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.iter().map(|e| e.name.as_str()).collect()
+    }
+    
+    /// 按名称查找抓取器条目
+    pub fn get(&self, name: &str) -> Option<&ScraperEntry> {
+        let name = name.to_lowercase();
+        self.entries.iter().find(|e| e.name == name)
+    }
+    
+    /// 按名称创建抓取器
+    pub fn create(&self, name: &str, config: &Config) -> Result<Arc<dyn StockScraper + Send + Sync>> {
+        self.get(name)
+            .ok_or_else(|| DataHubError::ExchangeError(format!(
+                "Unknown exchange: {} (available: {})", name, self.names().join(", ")
+            )))?
+            .create(config)
+    }
+    
+    /// 解析交易所选择，支持 "all" 或逗号分隔的名称列表
+    pub fn resolve(&self, selection: &str, config: &Config) -> Result<Vec<Arc<dyn StockScraper + Send + Sync>>> {
+        if selection.eq_ignore_ascii_case("all") {
+            return self.entries.iter().map(|e| e.create(config)).collect();
+        }
+        
+        selection
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| self.create(name, config))
+            .collect()
+    }
+}
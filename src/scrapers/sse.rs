@@ -1,18 +1,40 @@
 use crate::models::stock::{StockData, DailyData};
+use crate::models::price::Price;
+use crate::models::security::Exchange;
+use crate::models::trade_date::TradeDate;
 use crate::errors::{Result, DataHubError};
 use crate::scrapers::base::StockScraper;
+use crate::scrapers::cache::ResponseCache;
+use crate::scrapers::canary::{self, JsonType, SourceCheck};
+use crate::util;
 use async_trait::async_trait;
 use chrono::NaiveDate;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde_json::Value;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use log::{debug, info};
+use log::{debug, info, warn};
+
+/// 股票列表接口 `select` 参数中的列及其预期类型，返回的每行数组按此顺序排列
+const LIST_COLUMNS: [(&str, JsonType); 11] = [
+    ("code", JsonType::String),
+    ("name", JsonType::String),
+    ("open", JsonType::Number),
+    ("high", JsonType::Number),
+    ("low", JsonType::Number),
+    ("last", JsonType::Number),
+    ("volume", JsonType::Integer),
+    ("amount", JsonType::Integer),
+    ("prev_close", JsonType::Number),
+    ("change", JsonType::Number),
+    ("chg_rate", JsonType::Number),
+];
 
 /// 上海证券交易所数据抓取器
 pub struct SSEScraper {
     client: Client,
     last_request: Mutex<Option<Instant>>,
+    cache: Option<Arc<ResponseCache>>,
 }
 
 impl SSEScraper {
@@ -21,70 +43,40 @@ impl SSEScraper {
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
             .build()
-            .map_err(|e| DataHubError::RequestError(e))?;
+            .map_err(DataHubError::RequestError)?;
         
         Ok(Self {
             client,
             last_request: Mutex::new(None),
+            cache: None,
         })
     }
     
-    /// 等待请求频率限制
-    async fn wait_for_rate_limit(&self) {
-        const MIN_INTERVAL: Duration = Duration::from_millis(500);
-        
-        let now = Instant::now();
-        let should_wait = {
-            let mut last = self.last_request.lock().unwrap();
-            let should_wait = if let Some(instant) = *last {
-                let elapsed = instant.elapsed();
-                if elapsed < MIN_INTERVAL {
-                    Some(MIN_INTERVAL - elapsed)
-                } else {
-                    None
-                }
-            } else {
-                None
-            };
-            *last = Some(now);
-            should_wait
-        };
-        
-        if let Some(wait_time) = should_wait {
-            debug!("等待 {:?} 以遵守频率限制", wait_time);
-            tokio::time::sleep(wait_time).await;
-        }
+    /// 设置HTTP响应缓存
+    pub fn with_cache(mut self, cache: Option<Arc<ResponseCache>>) -> Self {
+        self.cache = cache;
+        self
     }
-}
-
-#[async_trait]
-impl StockScraper for SSEScraper {
-    fn exchange_code(&self) -> &'static str {
-        "SSE"
+    
+    /// 发送请求并返回响应内容，启用缓存时优先读取缓存
+    async fn send(&self, request: RequestBuilder) -> Result<Vec<u8>> {
+        ResponseCache::send(self.cache.as_deref(), &self.client, request, self.wait_for_rate_limit()).await
     }
     
-    async fn fetch_stock_list(&self, date: &NaiveDate) -> Result<Vec<StockData>> {
-        // Format date as YYYYMMDD integer
-        let date_int = date.format("%Y%m%d").to_string().parse::<i32>()?;
-        info!("获取上交所{}股票列表", date_int);
-
-        // 限制请求频率
-        self.wait_for_rate_limit().await;
-
-        // 发送请求获取股票列表
-        let response = self.client
+    /// 获取当天的股票列表
+    async fn fetch_list(&self) -> Result<Value> {
+        let select = LIST_COLUMNS.map(|(name, _)| name).join(",");
+        let body = self.send(self.client
             .get("https://yunhq.sse.com.cn:32042/v1/sh1/list/exchange/equity")
             .query(&[
-                ("select", "code,name,open,high,low,last,volume,amount"),
+                ("select", select.as_str()),
                 ("begin", "0"),
                 ("end", "5000"),
             ])
-            .header("Referer", "https://www.sse.com.cn/")
-            .send()
-            .await
-            .map_err(|e| DataHubError::RequestError(e))?;
+            .header("Referer", "https://www.sse.com.cn/"))
+            .await?;
         
-        let text = response.text().await?;
+        let text = String::from_utf8_lossy(&body);
         debug!("成功获取响应");
 
         // 提取JSON部分
@@ -92,11 +84,14 @@ impl StockScraper for SSEScraper {
             .trim_start_matches("jsonpCallback31050241(")
             .trim_end_matches(")");
         
-        let json: Value = serde_json::from_str(json_str)?;
-        
+        Ok(serde_json::from_str(json_str)?)
+    }
+    
+    /// 解析股票列表，返回的交易日与指定日期不同时返回空列表
+    fn parse_list(&self, json: &Value, trade_date: TradeDate) -> Vec<StockData> {
         let mut stocks = Vec::new();
-        if json.get("date").is_some_and(|x| x.as_i64().unwrap_or_default() as i32 != date_int) {
-            return Ok(stocks);
+        if json.get("date").is_some_and(|x| x.as_i64().unwrap_or_default() as i32 != trade_date.to_yyyymmdd()) {
+            return stocks;
         }
         // 解析股票列表
         if let Some(list) = json.get("list").and_then(|l| l.as_array()) {
@@ -105,43 +100,125 @@ impl StockScraper for SSEScraper {
                     if stock_array.len() >= 8 {
                         let code = stock_array[0].as_str().unwrap_or_default().to_string();
                         let name = stock_array[1].as_str().unwrap_or_default().to_string();
-                        let open = stock_array[2].as_f64().unwrap_or_default() as f32;  // 转换为f32
-                        let high = stock_array[3].as_f64().unwrap_or_default() as f32;  // 转换为f32
-                        let low = stock_array[4].as_f64().unwrap_or_default() as f32;   // 转换为f32
-                        let close = stock_array[5].as_f64().unwrap_or_default() as f32; // 转换为f32
+                        let prices = match [2, 3, 4, 5, 8].map(|i| json_price(stock_array.get(i))).into_iter().collect::<Result<Vec<_>>>() {
+                            Ok(prices) => prices,
+                            Err(e) => {
+                                warn!("跳过上交所股票{}: {}", code, e);
+                                continue;
+                            }
+                        };
+                        let (open, high, low, close) = (prices[0], prices[1], prices[2], prices[3]);
                         let volume = stock_array[6].as_i64().unwrap_or_default();
                         let amount = stock_array[7].as_i64().unwrap_or_default();
                         
+                        let mut daily = DailyData {
+                            date: trade_date,
+                            open,
+                            high,
+                            low,
+                            close,
+                            volume,
+                            amount,
+                            pre_close: prices[4],
+                            ..Default::default()
+                        };
+                        
+                        // 优先使用交易所返回的涨跌额和涨跌幅
+                        let change = stock_array.get(9).and_then(|v| json_price(Some(v)).ok());
+                        match (change, stock_array.get(10).and_then(|v| v.as_f64())) {
+                            (Some(change), Some(change_pct)) => {
+                                daily.change = change;
+                                daily.change_pct = change_pct as f32;
+                            }
+                            _ => daily.update_change(),
+                        }
+                        
                         stocks.push(StockData {
-                            exchange: self.exchange_code().to_string(),
+                            exchange: self.exchange(),
                             symbol: code,
                             name,
-                            daily: vec![DailyData {
-                                date: date_int,
-                                open,
-                                high,
-                                low,
-                                close,
-                                volume,
-                                amount,
-                            }],
+                            daily: vec![daily],
+                            name_history: Vec::new(),
                         });
                     }
                 }
             }
         }
 
+        stocks
+    }
+    
+    /// 等待请求频率限制
+    async fn wait_for_rate_limit(&self) {
+        const MIN_INTERVAL: Duration = Duration::from_millis(500);
+        
+        let now = Instant::now();
+        let should_wait = {
+            let mut last = self.last_request.lock().unwrap();
+            let should_wait = if let Some(instant) = *last {
+                let elapsed = instant.elapsed();
+                if elapsed < MIN_INTERVAL {
+                    Some(MIN_INTERVAL - elapsed)
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+            *last = Some(now);
+            should_wait
+        };
+        
+        if let Some(wait_time) = should_wait {
+            debug!("等待 {:?} 以遵守频率限制", wait_time);
+            tokio::time::sleep(wait_time).await;
+        }
+    }
+}
+
+#[async_trait]
+impl StockScraper for SSEScraper {
+    fn exchange(&self) -> Exchange {
+        Exchange::Sse
+    }
+    
+    // 列表接口只返回当天的行情
+    fn has_historical_snapshots(&self) -> bool {
+        false
+    }
+    
+    async fn fetch_stock_list(&self, date: &NaiveDate) -> Result<Vec<StockData>> {
+        let trade_date = TradeDate::from(*date);
+        info!("获取上交所{}股票列表", trade_date);
+        
+        let json = self.fetch_list().await?;
+        let stocks = self.parse_list(&json, trade_date);
+        
         info!("成功获取 {} 支股票信息", stocks.len());
         Ok(stocks)
     }
     
+    async fn check_source(&self, date: &NaiveDate) -> Result<SourceCheck> {
+        let trade_date = TradeDate::from(*date);
+        let json = self.fetch_list().await?;
+        
+        // 检查返回的交易日和各列类型，解析时被默认值替代的字段不会出现在取值检查中
+        let mut check = SourceCheck::new(self.exchange_code());
+        check.issues = check_list(&json, trade_date);
+        
+        let stocks = self.parse_list(&json, trade_date);
+        check.sample_size = stocks.len();
+        if !stocks.is_empty() {
+            check.issues.extend(canary::check_value_ranges(&stocks));
+        }
+        
+        Ok(check)
+    }
+    
     async fn fetch_stock_history(&self, symbol: &str) -> Result<Vec<DailyData>> {
         debug!("获取股票 {} 的历史K线数据", symbol);
         
-        // 限制请求频率
-        self.wait_for_rate_limit().await;
-        
-        let response = self.client
+        let body = self.send(self.client
             .get(format!(
                 "https://yunhq.sse.com.cn:32042/v1/sh1/dayk/{}",
                 symbol
@@ -151,12 +228,10 @@ impl StockScraper for SSEScraper {
                 ("end", "-1"),
                 ("period", "day"),
             ])
-            .header("Referer", "https://www.sse.com.cn/")
-            .send()
-            .await
-            .map_err(|e| DataHubError::RequestError(e))?;
+            .header("Referer", "https://www.sse.com.cn/"))
+            .await?;
         
-        let text = response.text().await?;
+        let text = String::from_utf8_lossy(&body);
         
         // 提取JSON部分
         let json_str = text
@@ -164,7 +239,7 @@ impl StockScraper for SSEScraper {
             .split('(')
             .nth(1)
             .map(|s| s.trim_end_matches(')'))
-            .unwrap_or(&text);
+            .unwrap_or(text.as_ref());
         
         let json: Value = serde_json::from_str(json_str)?;
         
@@ -177,11 +252,14 @@ impl StockScraper for SSEScraper {
                 if let Some(data) = item.as_array() {
                     if data.len() < 7 { continue; }
                     
-                    let date = data[0].as_i64().unwrap_or_default() as i32;
-                    let open = data[1].as_f64().unwrap_or_default() as f32;  // 转换为f32
-                    let high = data[2].as_f64().unwrap_or_default() as f32;  // 转换为f32
-                    let low = data[3].as_f64().unwrap_or_default() as f32;   // 转换为f32
-                    let close = data[4].as_f64().unwrap_or_default() as f32; // 转换为f32
+                    let date = match TradeDate::from_yyyymmdd(data[0].as_i64().unwrap_or_default() as i32) {
+                        Ok(date) => date,
+                        Err(_) => continue,
+                    };
+                    let open = json_price(data.get(1))?;
+                    let high = json_price(data.get(2))?;
+                    let low = json_price(data.get(3))?;
+                    let close = json_price(data.get(4))?;
                     let volume = data[5].as_i64().unwrap_or_default();
                     let amount = data[6].as_i64().unwrap_or_default();
                     
@@ -193,16 +271,118 @@ impl StockScraper for SSEScraper {
                         close,
                         volume,
                         amount,
+                        ..Default::default()
                     });
                 }
             }
         }
         
         // 按日期降序排序
-        daily_data.sort_by(|a, b| b.date.cmp(&a.date));
+        daily_data.sort_by_key(|d| std::cmp::Reverse(d.date));
+        
+        // K线接口不返回前收盘价，根据前一交易日收盘价计算
+        util::fill_pre_close(&mut daily_data);
         
         debug!("获取到 {} 条K线记录", daily_data.len());
         
         Ok(daily_data)
     }
 }
+
+// 读取JSON中的价格，缺失或不是数值时为0，超出价格范围时返回错误
+fn json_price(value: Option<&Value>) -> Result<Price> {
+    value.and_then(Value::as_f64).map_or(Ok(Price::ZERO), Price::try_from_f64)
+}
+
+// 检查列表接口返回的交易日、行数组及各列类型
+fn check_list(json: &Value, trade_date: TradeDate) -> Vec<String> {
+    let mut issues = Vec::new();
+    match json.get("date").and_then(|d| d.as_i64()) {
+        Some(date) if date == trade_date.to_yyyymmdd() as i64 => {}
+        Some(date) => issues.push(format!("Source returned data for {} instead of {}", date, trade_date.to_yyyymmdd())),
+        None => issues.push(format!("Missing or non-integer date in response: {:?}", json.get("date"))),
+    }
+    match json.get("list").and_then(|l| l.as_array()) {
+        Some(rows) => issues.extend(canary::check_json_rows(rows, &LIST_COLUMNS)),
+        None => issues.push("Missing list array in response".to_string()),
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    
+    fn trade_date() -> TradeDate {
+        "2025-05-16".parse().unwrap()
+    }
+    
+    #[test]
+    fn check_list_accepts_expected_types() {
+        let response = json!({
+            "date": 20250516,
+            "list": [
+                ["600000", "浦发银行", 10.0, 10.5, 9.9, 10.2, 1000000, 10200000, 10.0, 0.2, 2.0],
+                // 停牌
+                ["600001", "邯郸钢铁", 0.0, 0.0, 0.0, 5.0, 0, 0, 5.0, 0.0, 0.0],
+            ],
+        });
+        
+        assert!(check_list(&response, trade_date()).is_empty());
+        let stocks = SSEScraper::new().unwrap().parse_list(&response, trade_date());
+        assert_eq!(stocks.len(), 2);
+        assert_eq!(stocks[0].daily[0].close, Price::from_f64(10.2));
+        assert!(canary::check_value_ranges(&stocks).is_empty());
+    }
+    
+    #[test]
+    fn parse_list_skips_rows_with_out_of_range_prices() {
+        let response = json!({
+            "date": 20250516,
+            "list": [
+                ["600000", "浦发银行", 10.0, 1e12, 9.9, 10.2, 1000000, 10200000, 10.0, 0.2, 2.0],
+                // 涨跌额超出范围时按前收盘价重新计算
+                ["600001", "邯郸钢铁", 5.0, 5.5, 4.9, 5.5, 100, 550, 5.0, -1e12, 10.0],
+            ],
+        });
+        
+        let stocks = SSEScraper::new().unwrap().parse_list(&response, trade_date());
+        assert_eq!(stocks.len(), 1);
+        assert_eq!(stocks[0].symbol, "600001");
+        assert_eq!(stocks[0].daily[0].change, Price::from_milli(500));
+    }
+    
+    #[test]
+    fn check_list_reports_type_drift_and_short_rows() {
+        let response = json!({
+            "date": 20250516,
+            "list": [
+                // 价格变成字符串、成交量变成小数，解析时会被替换为0
+                ["600000", "浦发银行", "10.0", 10.5, 9.9, 10.2, 1000000.5, 10200000, 10.0, 0.2, 2.0],
+                ["600001", "邯郸钢铁", 5.0, 5.0, 5.0, 5.0, null, 100],
+            ],
+        });
+        
+        let issues = check_list(&response, trade_date());
+        assert!(issues.iter().any(|i| i.contains("600000: column open")), "{:?}", issues);
+        assert!(issues.iter().any(|i| i.contains("600000: column volume")), "{:?}", issues);
+        assert!(issues.iter().any(|i| i.contains("600001: 8 columns, expected 11")), "{:?}", issues);
+        assert!(issues.iter().any(|i| i.contains("600001: column volume")), "{:?}", issues);
+        assert!(issues.iter().any(|i| i.contains("600001: column prev_close is missing")), "{:?}", issues);
+        
+        // 取值检查发现成交量被替换为0后仍有成交额
+        let stocks = SSEScraper::new().unwrap().parse_list(&response, trade_date());
+        assert!(canary::check_value_ranges(&stocks).iter().any(|i| i.contains("without volume")));
+    }
+    
+    #[test]
+    fn check_list_reports_other_trading_day() {
+        let response = json!({ "date": 20250515, "list": [] });
+        let issues = check_list(&response, trade_date());
+        assert!(issues[0].contains("20250515 instead of 20250516"), "{:?}", issues);
+        assert!(SSEScraper::new().unwrap().parse_list(&response, trade_date()).is_empty());
+        
+        assert_eq!(check_list(&json!({}), trade_date()).len(), 2);
+    }
+}
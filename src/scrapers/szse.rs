@@ -1,12 +1,19 @@
 use crate::models::stock::{StockData, DailyData};
+use crate::models::price::Price;
+use crate::models::security::Exchange;
+use crate::models::trade_date::TradeDate;
 use crate::scrapers::base::StockScraper;
+use crate::scrapers::cache::ResponseCache;
+use crate::scrapers::canary::{self, SourceCheck};
+use crate::util;
 use crate::errors::{Result, DataHubError};
 use async_trait::async_trait;
-use calamine::{open_workbook_auto_from_rs, Reader, DataType};
+use calamine::{open_workbook_auto_from_rs, Data, Reader, DataType};
 use chrono::NaiveDate;
 use log::info;
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde_json::Value;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
 use std::time::Instant;
@@ -17,6 +24,7 @@ static LAST_REQUEST: Mutex<Option<Instant>> = Mutex::const_new(None);
 pub struct SZSEScraper {
     client: Client,
     request_interval: Duration,
+    cache: Option<Arc<ResponseCache>>,
 }
 
 impl SZSEScraper {
@@ -24,14 +32,141 @@ impl SZSEScraper {
         let client = Client::builder()
             .timeout(Duration::from_secs(30))
             .build()
-            .map_err(|e| DataHubError::RequestError(e))?;
+            .map_err(DataHubError::RequestError)?;
         
         Ok(Self { 
             client,
             request_interval: Duration::from_millis(500),
+            cache: None,
         })
     }
     
+    /// 设置HTTP响应缓存
+    pub fn with_cache(mut self, cache: Option<Arc<ResponseCache>>) -> Self {
+        self.cache = cache;
+        self
+    }
+    
+    /// 发送请求并返回响应内容，启用缓存时优先读取缓存
+    async fn send(&self, request: RequestBuilder) -> Result<Vec<u8>> {
+        ResponseCache::send(self.cache.as_deref(), &self.client, request, self.wait_for_rate_limit()).await
+    }
+    
+    // 获取指定日期的股票快照工作表
+    async fn fetch_snapshot(&self, date: &NaiveDate) -> Result<calamine::Range<Data>> {
+        let date_str = date.format("%Y-%m-%d").to_string();
+        
+        // 发送请求获取股票快照数据
+        let bytes = self.send(self.client
+            .get(format!("https://www.szse.cn/api/report/ShowReport?SHOWTYPE=xlsx&CATALOGID=1815_stock_snapshot&txtBeginDate={}&txtEndDate={}", &date_str, &date_str)))
+            .await?;
+
+        // 使用 calamine 打开工作簿
+        let mut workbook = open_workbook_auto_from_rs(std::io::Cursor::new(bytes))
+            .map_err(DataHubError::ExcelError)?;
+        
+        // 获取第一个工作表
+        workbook
+            .worksheet_range_at(0)
+            .ok_or_else(|| DataHubError::DataError("XLSX文件中没有工作表".to_string()))?
+            .map_err(DataHubError::ExcelError)
+    }
+    
+    // 解析股票快照工作表
+    fn parse_snapshot(&self, range: &calamine::Range<Data>, date: &NaiveDate) -> Result<Vec<StockData>> {
+        let mut rows = range.rows();
+        
+        // 空表（如非交易日）没有表头
+        let columns = match rows.next() {
+            Some(header) => SnapshotColumns::from_header(header)?,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut stocks = Vec::new();
+        let trade_date = TradeDate::from(*date);
+
+        // 表头之后逐行解析
+        for row in rows {
+            let (Some(code), Some(name)) = (row.get(columns.code), row.get(columns.name)) else {
+                continue;
+            };
+            if code.is_empty() {
+                continue;
+            }
+            
+            let mut daily = DailyData {
+                date: trade_date,
+                open: price_cell(row, columns.open)?,
+                high: price_cell(row, columns.high)?,
+                low: price_cell(row, columns.low)?,
+                close: price_cell(row, columns.close)?,
+                volume: wan_cell(row, columns.volume),
+                amount: wan_cell(row, columns.amount),
+                ..Default::default()
+            };
+            
+            if let Some(idx) = columns.pre_close {
+                daily.pre_close = price_cell(row, idx)?;
+                daily.update_change();
+            }
+            // 优先使用交易所返回的涨跌幅
+            if let Some(change_pct) = columns.change_pct.and_then(|idx| row.get(idx)).and_then(parse_number) {
+                daily.change_pct = change_pct as f32;
+            }
+            if let Some(turnover_rate) = columns.turnover_rate.and_then(|idx| row.get(idx)).and_then(parse_number) {
+                daily.turnover_rate = turnover_rate as f32;
+            }
+            
+            stocks.push(StockData {
+                exchange: self.exchange(),
+                symbol: code.to_string(),
+                name: name.to_string(),
+                daily: vec![daily],
+                name_history: Vec::new(),
+            });
+        }
+        
+        Ok(stocks)
+    }
+    
+    // 检查快照工作表的表头、首行数据的单元格类型和取值范围
+    fn check_snapshot(&self, range: &calamine::Range<Data>, date: &NaiveDate) -> Result<SourceCheck> {
+        let mut check = SourceCheck::new(self.exchange_code());
+        
+        // 检查表头是否包含全部预期列
+        let mut rows = range.rows();
+        let columns = match rows.next().map(SnapshotColumns::from_header) {
+            Some(Ok(columns)) => columns,
+            Some(Err(e)) => {
+                check.issues.push(e.to_string());
+                return Ok(check);
+            }
+            None => {
+                check.issues.push(format!("Empty snapshot for {}", date));
+                return Ok(check);
+            }
+        };
+        
+        // 检查首行数据的单元格类型
+        if let Some(row) = rows.next() {
+            for (header, idx) in columns.numeric() {
+                let parsed = row.get(idx).and_then(parse_number);
+                if parsed.is_none() {
+                    check.issues.push(format!(
+                        "Column {} is not numeric: {:?}", header, row.get(idx)
+                    ));
+                }
+            }
+        }
+        
+        // 检查取值范围
+        let stocks = self.parse_snapshot(range, date)?;
+        check.sample_size = stocks.len();
+        check.issues.extend(canary::check_value_ranges(&stocks));
+        
+        Ok(check)
+    }
+    
     // 添加请求限速机制
     async fn wait_for_rate_limit(&self) {
         let now = Instant::now();
@@ -49,126 +184,161 @@ impl SZSEScraper {
     
 }
 
+/// 股票快照表的列位置，按表头中文名称定位
+struct SnapshotColumns {
+    code: usize,
+    name: usize,
+    open: usize,
+    high: usize,
+    low: usize,
+    close: usize,
+    volume: usize,
+    amount: usize,
+    // 可选列，缺失时根据其他字段计算或留空
+    pre_close: Option<usize>,
+    change_pct: Option<usize>,
+    turnover_rate: Option<usize>,
+}
+
+impl SnapshotColumns {
+    const CODE: &'static str = "证券代码";
+    const NAME: &'static str = "证券简称";
+    const OPEN: &'static str = "开盘";
+    const HIGH: &'static str = "最高";
+    const LOW: &'static str = "最低";
+    const CLOSE: &'static str = "今收";
+    const VOLUME: &'static str = "成交量(万股)";
+    const AMOUNT: &'static str = "成交金额(万元)";
+    const PRE_CLOSE: &'static str = "前收";
+    const CHANGE_PCT: &'static str = "涨跌幅(%)";
+    const TURNOVER_RATE: &'static str = "换手率(%)";
+    
+    /// 从表头行定位各列，缺少任何一列时返回错误
+    fn from_header(header: &[Data]) -> Result<Self> {
+        let headers: Vec<String> = header.iter()
+            .map(|cell| normalize_header(&cell.to_string()))
+            .collect();
+        
+        let find_optional = |name: &str| -> Option<usize> {
+            let target = normalize_header(name);
+            headers.iter().position(|h| *h == target)
+        };
+        let find = |name: &str| -> Result<usize> {
+            find_optional(name).ok_or_else(|| DataHubError::DataError(format!(
+                "Column {} not found in SZSE snapshot header: {:?}", name, headers
+            )))
+        };
+        
+        Ok(Self {
+            code: find(Self::CODE)?,
+            name: find(Self::NAME)?,
+            open: find(Self::OPEN)?,
+            high: find(Self::HIGH)?,
+            low: find(Self::LOW)?,
+            close: find(Self::CLOSE)?,
+            volume: find(Self::VOLUME)?,
+            amount: find(Self::AMOUNT)?,
+            pre_close: find_optional(Self::PRE_CLOSE),
+            change_pct: find_optional(Self::CHANGE_PCT),
+            turnover_rate: find_optional(Self::TURNOVER_RATE),
+        })
+    }
+    
+    /// 数值列的表头名称和位置
+    fn numeric(&self) -> [(&'static str, usize); 6] {
+        [
+            (Self::OPEN, self.open),
+            (Self::HIGH, self.high),
+            (Self::LOW, self.low),
+            (Self::CLOSE, self.close),
+            (Self::VOLUME, self.volume),
+            (Self::AMOUNT, self.amount),
+        ]
+    }
+}
+
+// 统一全角括号并去除空白，避免表头格式细微变化导致定位失败
+fn normalize_header(header: &str) -> String {
+    header.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '（' => '(',
+            '）' => ')',
+            c => c,
+        })
+        .collect()
+}
+
+// 解析数值单元格，兼容带千分位逗号的文本
+fn parse_number(cell: &Data) -> Option<f64> {
+    match cell {
+        Data::String(s) => s.replace(',', "").trim().parse::<f64>().ok(),
+        _ => cell.as_f64(),
+    }
+}
+
+// 读取JSON中的价格，兼容字符串和数值
+fn json_price(value: &Value) -> Option<Price> {
+    match value {
+        Value::String(s) => s.parse::<Price>().ok(),
+        _ => value.as_f64().and_then(|v| Price::try_from_f64(v).ok()),
+    }
+}
+
+// 读取JSON中的数值，兼容字符串和数值
+fn json_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => value.as_f64(),
+    }
+}
+
+// 读取价格列，数值超出价格范围时返回错误
+fn price_cell(row: &[Data], idx: usize) -> Result<Price> {
+    match row.get(idx) {
+        Some(Data::String(s)) => Ok(s.parse::<Price>().unwrap_or_default()),
+        Some(cell) => cell.as_f64().map_or(Ok(Price::ZERO), Price::try_from_f64),
+        None => Ok(Price::ZERO),
+    }
+}
+
+// 读取以"万"为单位的数量列
+fn wan_cell(row: &[Data], idx: usize) -> i64 {
+    (row.get(idx).and_then(parse_number).unwrap_or_default() * 10000.0).round() as i64
+}
+
 #[async_trait]
 impl StockScraper for SZSEScraper {
-    fn exchange_code(&self) -> &'static str {
-        "SZSE"
+    fn exchange(&self) -> Exchange {
+        Exchange::Szse
     }
     
     async fn fetch_stock_list(&self, date: &NaiveDate) -> Result<Vec<StockData>> {
         let date_str = date.format("%Y-%m-%d").to_string();
         info!("开始获取深交所股票列表，日期: {}", date_str);
         
-        // 限制请求频率
-        self.wait_for_rate_limit().await;
-        
-        // 发送请求获取股票快照数据
-        let response = self.client
-            .get(format!("https://www.szse.cn/api/report/ShowReport?SHOWTYPE=xlsx&CATALOGID=1815_stock_snapshot&txtBeginDate={}&txtEndDate={}", &date_str, &date_str))
-            .send()
-            .await?;
-
-        // 获取文件内容
-        let bytes = response.bytes().await?;
-
-        // 使用 calamine 打开工作簿
-        let mut workbook = open_workbook_auto_from_rs(std::io::Cursor::new(bytes))
-            .map_err(|e| DataHubError::ExcelError(e))?;
-        
-        // 获取第一个工作表
-        let range = workbook
-            .worksheet_range_at(0)
-            .ok_or_else(|| DataHubError::DataError("XLSX文件中没有工作表".to_string()))?
-            .map_err(|e| DataHubError::ExcelError(e))?;
-
-        let mut stocks = Vec::new();
-        let date_int = date.format("%Y%m%d").to_string().parse::<i32>()?;
-
-        // 跳过表头行，从第二行开始解析
-        for row in range.rows().skip(1) {
-            if row.len() >= 11 {  // 确保有足够的列
-                let code = match row.get(1) {
-                    Some(cell) => cell.to_string(),
-                    None => continue,
-                };
-                
-                let name = match row.get(2) {
-                    Some(cell) => cell.to_string(),
-                    None => continue,
-                };
-                
-                // 使用安全的解析方法
-                let open = match row.get(4) {
-                    Some(cell) => cell.as_f64().unwrap() as f32,
-                    None => 0.0,
-                };
-                
-                let high = match row.get(5) {
-                    Some(cell) => cell.as_f64().unwrap() as f32,
-                    None => 0.0,
-                };
-                
-                let low = match row.get(6) {
-                    Some(cell) => cell.as_f64().unwrap() as f32,
-                    None => 0.0,
-                };
-                
-                let close = match row.get(7) {
-                    Some(cell) => cell.as_f64().unwrap() as f32,
-                    None => 0.0,
-                };
-                
-                let volume = match row.get(9) {
-                    Some(cell) => {
-                        (cell.as_string().unwrap().replace(",", "").parse::<f64>().unwrap() * 10000.0).round() as i64
-                    },
-                    None => 0,
-                };
-                
-                let amount = match row.get(10) {
-                    Some(cell) => {
-                        (cell.as_string().unwrap().replace(",", "").parse::<f64>().unwrap() * 10000.0).round() as i64
-                    },
-                    None => 0,
-                };
-                
-                stocks.push(StockData {
-                    exchange: self.exchange_code().to_string(),
-                    symbol: code,
-                    name,
-                    daily: vec![DailyData {
-                        date: date_int,
-                        open,
-                        high,
-                        low,
-                        close,
-                        volume,
-                        amount,
-                    }],
-                });
-            }
-        }
+        let range = self.fetch_snapshot(date).await?;
+        let stocks = self.parse_snapshot(&range, date)?;
 
         info!("成功获取 {} 的 {} 支股票信息", date_str, stocks.len());
         Ok(stocks)
     }
     
+    async fn check_source(&self, date: &NaiveDate) -> Result<SourceCheck> {
+        let range = self.fetch_snapshot(date).await?;
+        self.check_snapshot(&range, date)
+    }
+    
     async fn fetch_stock_history(&self, symbol: &str) -> Result<Vec<DailyData>> {
         info!("开始获取深交所股票{}的历史数据", symbol);
         
-        // 限制请求频率
-        self.wait_for_rate_limit().await;
-        
         let url = format!(
             "https://www.szse.cn/api/market/ssjjhq/getHistoryData?cycleType=32&marketId=1&code={}",
             symbol
         );
         
-        let response = self.client.get(&url)
-            .send()
-            .await?;
-            
-        let json: Value = response.json().await?;
+        let body = self.send(self.client.get(&url)).await?;
+        let json: Value = serde_json::from_slice(&body)?;
 
         // 创建日线数据向量
         let mut daily_data = Vec::new();
@@ -180,35 +350,32 @@ impl StockScraper for SZSEScraper {
                         continue;
                     }
                     
-                    let date_str = match array[0].as_str() {
-                        Some(s) => s.replace("-", ""),
+                    let date = match array[0].as_str() {
+                        Some(s) => s.parse::<TradeDate>()?,
                         None => continue,
                     };
                     
-                    let date = date_str.parse::<i32>()
-                        .map_err(|_| DataHubError::DataError(format!("Invalid date format: {}", date_str)))?;
-                    
                     // 使用更安全的方式解析价格数据
                     let open = match array[1].as_str() {
-                        Some(s) => s.parse::<f32>()  // 改为f32
+                        Some(s) => s.parse::<Price>()
                             .map_err(|_| DataHubError::DataError("Invalid open price format".to_string()))?,
                         None => continue,
                     };
                     
                     let high = match array[4].as_str() {
-                        Some(s) => s.parse::<f32>()  // 改为f32
+                        Some(s) => s.parse::<Price>()
                             .map_err(|_| DataHubError::DataError("Invalid high price format".to_string()))?,
                         None => continue,
                     };
                     
                     let low = match array[3].as_str() {
-                        Some(s) => s.parse::<f32>()  // 改为f32
+                        Some(s) => s.parse::<Price>()
                             .map_err(|_| DataHubError::DataError("Invalid low price format".to_string()))?,
                         None => continue,
                     };
                     
                     let close = match array[2].as_str() {
-                        Some(s) => s.parse::<f32>()  // 改为f32
+                        Some(s) => s.parse::<Price>()
                             .map_err(|_| DataHubError::DataError("Invalid close price format".to_string()))?,
                         None => continue,
                     };
@@ -216,7 +383,7 @@ impl StockScraper for SZSEScraper {
                     let volume = array[7].as_i64().unwrap_or_default() * 100;
                     let amount = array[8].as_f64().unwrap_or_default() as i64;
                     
-                    daily_data.push(DailyData {
+                    let mut daily = DailyData {
                         date,
                         open,
                         high,
@@ -224,13 +391,28 @@ impl StockScraper for SZSEScraper {
                         close,
                         volume,
                         amount,
-                    });
+                        ..Default::default()
+                    };
+                    
+                    // 第6、7列为涨跌额和涨跌幅，前收盘价 = 收盘价 - 涨跌额
+                    if let Some(change) = json_price(&array[5]) {
+                        daily.change = change;
+                        daily.pre_close = close - change;
+                    }
+                    if let Some(change_pct) = json_number(&array[6]) {
+                        daily.change_pct = change_pct as f32;
+                    }
+                    
+                    daily_data.push(daily);
                 }
             }
         }
         
         // 按日期降序排序
-        daily_data.sort_by(|a, b| b.date.cmp(&a.date));
+        daily_data.sort_by_key(|d| std::cmp::Reverse(d.date));
+        
+        // 缺少涨跌额的记录根据前一交易日收盘价补全
+        util::fill_pre_close(&mut daily_data);
         
         info!("获取到 {} 条K线记录", daily_data.len());
         
@@ -238,3 +420,60 @@ impl StockScraper for SZSEScraper {
         Ok(daily_data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::Range;
+    
+    const HEADER: [&str; 10] = ["证券代码", "证券简称", "前收", "开盘", "最高", "最低", "今收", "涨跌幅（%）", "成交量(万股)", "成交金额(万元)"];
+    
+    fn sheet(header: &[&str], rows: &[Vec<Data>]) -> Range<Data> {
+        let mut range = Range::new((0, 0), (rows.len() as u32, header.len() as u32 - 1));
+        for (col, name) in header.iter().enumerate() {
+            range.set_value((0, col as u32), Data::String(name.to_string()));
+        }
+        for (row, cells) in rows.iter().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                range.set_value((row as u32 + 1, col as u32), cell.clone());
+            }
+        }
+        range
+    }
+    
+    fn row(code: &str, close: Data, volume: Data) -> Vec<Data> {
+        vec![
+            Data::String(code.to_string()), Data::String("平安银行".to_string()),
+            Data::Float(10.0), Data::Float(10.0), Data::Float(10.5), Data::Float(9.9), close,
+            Data::Float(2.0), volume, Data::String("1,020".to_string()),
+        ]
+    }
+    
+    fn date() -> NaiveDate {
+        "2025-05-16".parse().unwrap()
+    }
+    
+    #[test]
+    fn check_snapshot_accepts_expected_sheet() {
+        let range = sheet(&HEADER, &[row("000001", Data::Float(10.2), Data::String("100".to_string()))]);
+        let check = SZSEScraper::new().unwrap().check_snapshot(&range, &date()).unwrap();
+        assert!(check.is_ok(), "{:?}", check.issues);
+        assert_eq!(check.sample_size, 1);
+    }
+    
+    #[test]
+    fn check_snapshot_reports_missing_columns_and_non_numeric_cells() {
+        let mut header = HEADER;
+        header[6] = "收盘";
+        let range = sheet(&header, &[row("000001", Data::Float(10.2), Data::Float(100.0))]);
+        let check = SZSEScraper::new().unwrap().check_snapshot(&range, &date()).unwrap();
+        assert!(check.issues[0].contains("Column 今收 not found"), "{:?}", check.issues);
+        
+        let range = sheet(&HEADER, &[row("000001", Data::String("--".to_string()), Data::Float(100.0))]);
+        let check = SZSEScraper::new().unwrap().check_snapshot(&range, &date()).unwrap();
+        assert!(check.issues.iter().any(|i| i.contains("Column 今收 is not numeric")), "{:?}", check.issues);
+        
+        let check = SZSEScraper::new().unwrap().check_snapshot(&Range::empty(), &date()).unwrap();
+        assert!(check.issues[0].contains("Empty snapshot"), "{:?}", check.issues);
+    }
+}
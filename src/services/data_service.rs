@@ -1,18 +1,38 @@
 use crate::models::stock::StockData;
+use crate::models::security::{Exchange, SecurityId};
+use crate::models::trade_date::TradeDate;
 use crate::scrapers::base::StockScraper;
 use crate::errors::{Result, DataHubError};
 use crate::config::Config;
 use crate::data_provider::StockDataProvider;
+use crate::storage::{save_dataset, ArchiveStore, DeltaStore, PartitionFilter, PartitionedStore, SnapshotStore};
+use crate::storage::delta::apply_delta;
+#[cfg(feature = "sqlite")]
+use crate::storage::SqliteStore;
 use crate::util;
 use chrono::NaiveDate;
 use log::{info, warn};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+/// 历史快照回补结果
+#[derive(Debug, Default)]
+pub struct BackfillReport {
+    /// 成功合并快照的交易日
+    pub processed_days: Vec<NaiveDate>,
+    /// 上游未返回数据的 (交易日, 交易所)
+    pub missing_days: Vec<(NaiveDate, Exchange)>,
+    /// 上游只提供当天快照、未参与回补的交易所，如上交所
+    pub skipped_exchanges: Vec<Exchange>,
+}
 
 /// 数据服务，处理数据的获取、合并和存储
 pub struct DataService {
     config: Config,
     scrapers: Vec<Arc<dyn StockScraper + Send + Sync>>,
+    // 本次运行中被截断的较早日线，保存时写入归档
+    truncated: Mutex<Vec<StockData>>,
 }
 
 impl DataService {
@@ -21,12 +41,34 @@ impl DataService {
         Self {
             config,
             scrapers,
+            truncated: Mutex::new(Vec::new()),
+        }
+    }
+    
+    // 应用K线记录数量限制，启用归档时暂存被截断的日线
+    fn limit_kline_records(&self, stock: &mut StockData) {
+        let truncated = util::limit_kline_records(&mut stock.daily, self.config.max_kline_records, &stock.symbol);
+        if self.config.archive_dir.is_some() && !truncated.is_empty() {
+            let mut pending = self.truncated.lock().unwrap_or_else(|e| e.into_inner());
+            pending.push(StockData {
+                exchange: stock.exchange,
+                symbol: stock.symbol.clone(),
+                name: stock.name.clone(),
+                daily: truncated,
+                name_history: stock.name_history.clone(),
+            });
         }
     }
     
     /// 加载数据提供者
     pub async fn load_provider(&self) -> Result<StockDataProvider> {
-        StockDataProvider::load_from_file("docs/data/stock.arrow")
+        if self.config.partitioned {
+            return StockDataProvider::load_partitioned(&self.config.data_dir, &PartitionFilter::all());
+        }
+        if self.config.delta {
+            return StockDataProvider::load_with_deltas(&self.config.data_file());
+        }
+        StockDataProvider::load_from_file(&self.config.data_file())
     }
     
     /// 处理单个股票
@@ -34,6 +76,10 @@ impl DataService {
         let actual_date = date.cloned().unwrap_or_else(|| chrono::Local::now().naive_local().date());
         info!("Processing stock: {} for date: {}", symbol, actual_date);
         
+        // 解析证券标识，纯代码按号段推断交易所
+        let id = SecurityId::parse(symbol)?;
+        let symbol = id.symbol.as_str();
+        
         // 加载现有数据
         let provider = self.load_provider().await?;
         let mut stocks_to_update = Vec::new();
@@ -41,7 +87,7 @@ impl DataService {
         // 查找匹配的交易所和股票名称
         let mut found_stock = false;
         
-        for scraper in &self.scrapers {
+        for scraper in self.scrapers.iter().filter(|s| s.exchange() == id.exchange) {
             // 获取股票列表，查找匹配的股票
             let stock_list = scraper.fetch_stock_list(&actual_date).await?;
             
@@ -50,11 +96,11 @@ impl DataService {
                     found_stock = true;
                     
                     // 检查现有数据中是否已有该股票
-                    if let Some(existing_stock) = provider.get_stock_by_symbol(symbol) {
+                    if let Some(existing_stock) = provider.get_stock(&id) {
                         let mut updated_stock = existing_stock.clone();
                         
-                        // 始终使用最新的股票名称
-                        updated_stock.name = stock.name;
+                        // 使用最新的股票名称，名称变化时记录名称历史
+                        updated_stock.record_name(&stock.name, TradeDate::from(actual_date));
                         
                         // 如果强制获取全量历史数据，或者现有数据为空
                         if self.config.force_full_history || updated_stock.daily.is_empty() {
@@ -65,7 +111,7 @@ impl DataService {
                                 updated_stock.daily = daily_data;
                                 
                                 // 应用K线记录数量限制
-                                util::limit_kline_records(&mut updated_stock.daily, self.config.max_kline_records, symbol);
+                                self.limit_kline_records(&mut updated_stock);
                             }
                         } else if !stock.daily.is_empty() {
                             // 增量更新：检查是否已有该日期的数据
@@ -79,10 +125,10 @@ impl DataService {
                                 updated_stock.daily.insert(0, new_daily.clone());
                                 
                                 // 重新排序（确保按日期降序）
-                                updated_stock.daily.sort_by(|a, b| b.date.cmp(&a.date));
+                                updated_stock.daily.sort_by_key(|d| std::cmp::Reverse(d.date));
                                 
                                 // 应用K线记录数量限制
-                                util::limit_kline_records(&mut updated_stock.daily, self.config.max_kline_records, symbol);
+                                self.limit_kline_records(&mut updated_stock);
                             }
                         }
                         
@@ -99,7 +145,7 @@ impl DataService {
                                 new_stock.daily = daily_data;
                                 
                                 // 应用K线记录数量限制
-                                util::limit_kline_records(&mut new_stock.daily, self.config.max_kline_records, symbol);
+                                self.limit_kline_records(&mut new_stock);
                             }
                         }
                         
@@ -117,7 +163,7 @@ impl DataService {
         
         if !found_stock {
             return Err(DataHubError::DataError(format!(
-                "Stock {} not found in any exchange for date {}", symbol, actual_date
+                "Stock {} not found in any exchange for date {}", id, actual_date
             )));
         }
         
@@ -127,7 +173,7 @@ impl DataService {
         // 更新或添加股票
         for stock_to_update in stocks_to_update {
             let index = all_stocks.iter().position(|s| 
-                s.exchange == stock_to_update.exchange && s.symbol == stock_to_update.symbol
+                s.security_id() == stock_to_update.security_id()
             );
             
             if let Some(idx) = index {
@@ -155,8 +201,7 @@ impl DataService {
         // 创建一个映射，用于快速查找现有数据
         let mut existing_map = HashMap::new();
         for (i, stock) in all_stocks.iter().enumerate() {
-            let key = format!("{}:{}", stock.exchange, stock.symbol);
-            existing_map.insert(key, i);
+            existing_map.insert(stock.security_id(), i);
         }
         
         // 一次性获取所有交易所的股票列表
@@ -186,7 +231,7 @@ impl DataService {
         // 创建一个映射，用于快速查找当日股票数据
         let mut daily_stock_map = HashMap::new();
         for stock in daily_stocks {
-            daily_stock_map.insert(format!("{}:{}", stock.exchange, stock.symbol), stock);
+            daily_stock_map.insert(stock.security_id(), stock);
         }
         
         // 处理每个股票
@@ -194,7 +239,7 @@ impl DataService {
         
         for (key, stock) in daily_stock_map.iter() {
             let symbol = &stock.symbol;
-            let exchange = &stock.exchange;
+            let exchange = stock.exchange;
             
             // 检查是否需要获取完整历史数据
             let need_full_history = if let Some(&idx) = existing_map.get(key) {
@@ -208,7 +253,7 @@ impl DataService {
             let mut updated_stock = if let Some(&idx) = existing_map.get(key) {
                 // 股票已存在，更新名称
                 let mut updated = all_stocks[idx].clone();
-                updated.name = stock.name.clone(); // 始终使用最新的股票名称
+                updated.record_name(&stock.name, TradeDate::from(*date)); // 名称变化时记录名称历史
                 updated
             } else {
                 // 创建新的股票数据
@@ -218,13 +263,13 @@ impl DataService {
             if need_full_history {
                 // 需要获取完整历史数据
                 for scraper in &self.scrapers {
-                    if scraper.exchange_code() == exchange {
+                    if scraper.exchange() == exchange {
                         match scraper.fetch_stock_history(symbol).await {
                             Ok(daily_data) => {
                                 if !daily_data.is_empty() {
                                     updated_stock.daily = daily_data;
                                     // 应用K线记录数量限制
-                                    util::limit_kline_records(&mut updated_stock.daily, self.config.max_kline_records, symbol);
+                                    self.limit_kline_records(&mut updated_stock);
                                 }
                             },
                             Err(e) => {
@@ -244,10 +289,10 @@ impl DataService {
                     updated_stock.daily.insert(0, new_daily.clone());
                     
                     // 重新排序（确保按日期降序）
-                    updated_stock.daily.sort_by(|a, b| b.date.cmp(&a.date));
+                    updated_stock.daily.sort_by_key(|d| std::cmp::Reverse(d.date));
                     
                     // 应���K线记录数量限制
-                    util::limit_kline_records(&mut updated_stock.daily, self.config.max_kline_records, symbol);
+                    self.limit_kline_records(&mut updated_stock);
                 }
             }
             
@@ -256,8 +301,7 @@ impl DataService {
         
         // 更新所有股票
         for stock in &stocks_to_update {
-            let key = format!("{}:{}", stock.exchange, stock.symbol);
-            if let Some(&idx) = existing_map.get(&key) {
+            if let Some(&idx) = existing_map.get(&stock.security_id()) {
                 all_stocks[idx] = stock.clone();
             } else {
                 all_stocks.push(stock.clone());
@@ -271,14 +315,147 @@ impl DataService {
         Ok(())
     }
     
-    /// 保存数据
+    /// 回补日期区间内每个交易日的快照数据
+    pub async fn backfill(&self, range: RangeInclusive<NaiveDate>) -> Result<BackfillReport> {
+        let (start, end) = range.into_inner();
+        if start > end {
+            return Err(DataHubError::DataError(format!(
+                "Invalid backfill range: {} > {}", start, end
+            )));
+        }
+        info!("Backfilling stocks from {} to {}", start, end);
+        
+        // 加载现有数据
+        let provider = self.load_provider().await?;
+        let mut all_stocks = provider.get_all_stocks().to_vec();
+        
+        let mut existing_map = HashMap::new();
+        for (i, stock) in all_stocks.iter().enumerate() {
+            existing_map.insert(stock.security_id(), i);
+        }
+        
+        let mut report = BackfillReport::default();
+        
+        // 只提供当天快照的交易所无法按日期回补，历史数据需要按股票抓取
+        let (scrapers, skipped): (Vec<_>, Vec<_>) = self.scrapers.iter()
+            .partition(|scraper| scraper.has_historical_snapshots());
+        for scraper in skipped {
+            warn!("{} only serves the current trading day, skipping backfill", scraper.exchange_code());
+            report.skipped_exchanges.push(scraper.exchange());
+        }
+        
+        for date in util::trading_days(&start, &end) {
+            let mut day_found = false;
+            
+            for scraper in &scrapers {
+                let stocks = match scraper.fetch_stock_list(&date).await {
+                    Ok(stocks) => stocks,
+                    Err(e) => {
+                        warn!("Failed to fetch {} snapshot for {}: {}", scraper.exchange_code(), date, e);
+                        Vec::new()
+                    }
+                };
+                
+                if stocks.is_empty() {
+                    warn!("No {} snapshot for {}", scraper.exchange_code(), date);
+                    report.missing_days.push((date, scraper.exchange()));
+                    continue;
+                }
+                
+                info!("Merging {} stocks from {} for {}", stocks.len(), scraper.exchange_code(), date);
+                day_found = true;
+                
+                for stock in stocks {
+                    let key = stock.security_id();
+                    if let Some(&idx) = existing_map.get(&key) {
+                        let existing = &mut all_stocks[idx];
+                        for daily in &stock.daily {
+                            util::merge_daily(&mut existing.daily, daily);
+                        }
+                        // 记录该交易日的名称，回补较早日期不会覆盖当前名称
+                        existing.record_name(&stock.name, TradeDate::from(date));
+                    } else {
+                        existing_map.insert(key, all_stocks.len());
+                        all_stocks.push(stock);
+                    }
+                }
+            }
+            
+            if day_found {
+                report.processed_days.push(date);
+            }
+        }
+        
+        // 应用K线记录数量限制
+        for stock in &mut all_stocks {
+            self.limit_kline_records(stock);
+        }
+        
+        if !report.processed_days.is_empty() {
+            self.save_data(&all_stocks).await?;
+        }
+        
+        info!("Backfilled {} trading days, {} missing upstream", 
+              report.processed_days.len(), report.missing_days.len());
+        Ok(report)
+    }
+    
+    /// 保存数据，分区模式下只重写发生变化的分区，增量模式下只写入当日的增量文件
+    ///
+    /// 先写入主数据和清单，再同步 SQLite 数据库、归档和当天的快照，附属存储出错时主数据已经发布，
+    /// 归档失败时本次截断的日线保留到下次保存。
     pub async fn save_data(&self, data: &[StockData]) -> Result<()> {
-        // 保存到主数据文件
-        let provider = StockDataProvider::new_with_data(data.to_vec())?;
-        provider.save_to_file("docs/data/stock.arrow")?;
+        self.save_primary(data)?;
+        
+        // 同步写入 SQLite 数据库，供使用 SQL 的工具查询
+        if let Some(path) = &self.config.sqlite_path {
+            #[cfg(feature = "sqlite")]
+            SqliteStore::new(path).save(data)?;
+            #[cfg(not(feature = "sqlite"))]
+            return Err(DataHubError::DataError(format!(
+                "Writing {} requires building with `--features sqlite`", path
+            )));
+        }
+        
+        // 归档保存完整历史：本次截断的日线和当前数据一起合并进去
+        if let Some(dir) = &self.config.archive_dir {
+            let truncated = std::mem::take(&mut *self.truncated.lock().unwrap_or_else(|e| e.into_inner()));
+            let mut pending = truncated.clone();
+            apply_delta(&mut pending, data.to_vec());
+            if let Err(e) = ArchiveStore::new(dir).append(pending) {
+                // 放回截断的日线，下次保存时重试，期间新截断的日线合并在后面
+                let mut guard = self.truncated.lock().unwrap_or_else(|e| e.into_inner());
+                let newer = std::mem::replace(&mut *guard, truncated);
+                apply_delta(&mut guard, newer);
+                return Err(e);
+            }
+        }
         
-        // 保存数据
-        util::arrow_utils::save_stock_data_to_arrow(data, "docs/data/stock.arrow")?;
+        // 标记当天的快照，出现错误数据时可以恢复到之前的版本
+        if self.config.snapshot {
+            let today = TradeDate::from(chrono::Local::now().date_naive());
+            SnapshotStore::from_config(&self.config).tag(data, today)?;
+        }
+        
+        Ok(())
+    }
+    
+    // 写入主数据：分区数据集、增量文件或完整的数据文件，并发布对应的清单
+    fn save_primary(&self, data: &[StockData]) -> Result<()> {
+        if self.config.partitioned {
+            PartitionedStore::from_config(&self.config).save(data)?;
+            return Ok(());
+        }
+        let data_file = self.config.data_file();
+        if self.config.delta {
+            DeltaStore::new(&data_file)
+                .with_compressed_variant(self.config.compressed_variant)
+                .save(data)?;
+            return Ok(());
+        }
+        
+        // 原子替换主数据文件并轮换备份，发布清单供下游判断更新并校验下载，同时发布压缩版本
+        save_dataset(data, &data_file, self.config.backups, self.config.compressed_variant)?;
         
         Ok(())
     }
@@ -310,3 +487,162 @@ impl DataService {
         Ok(all_stocks)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::price::Price;
+    use crate::models::stock::DailyData;
+    use crate::scrapers::registry::ScraperRegistry;
+    use async_trait::async_trait;
+    use chrono::Datelike;
+    
+    // 按交易日返回一只股票当日日线的抓取器
+    struct MockScraper {
+        exchange: Exchange,
+        symbol: &'static str,
+        trading_days: Vec<NaiveDate>,
+        historical: bool,
+    }
+    
+    impl MockScraper {
+        fn bar(date: NaiveDate) -> DailyData {
+            let close = Price::from_f64(10.0 + date.day0() as f64);
+            DailyData { date: TradeDate::from(date), open: close, high: close, low: close, close, ..DailyData::default() }
+        }
+    }
+    
+    #[async_trait]
+    impl StockScraper for MockScraper {
+        fn exchange(&self) -> Exchange {
+            self.exchange
+        }
+        
+        async fn fetch_stock_list(&self, date: &NaiveDate) -> Result<Vec<StockData>> {
+            if !self.trading_days.contains(date) {
+                return Ok(Vec::new());
+            }
+            Ok(vec![StockData {
+                exchange: self.exchange,
+                symbol: self.symbol.to_string(),
+                name: format!("{} {}", self.exchange, self.symbol),
+                daily: vec![Self::bar(*date)],
+                name_history: Vec::new(),
+            }])
+        }
+        
+        fn has_historical_snapshots(&self) -> bool {
+            self.historical
+        }
+        
+        async fn fetch_stock_history(&self, _symbol: &str) -> Result<Vec<DailyData>> {
+            Ok(self.trading_days.iter().rev().map(|&d| Self::bar(d)).collect())
+        }
+    }
+    
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+    
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let root = std::env::temp_dir().join(format!("egostrategy_service_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        root
+    }
+    
+    #[tokio::test]
+    async fn custom_exchange_scraper_runs_through_registry() {
+        let root = temp_dir("custom");
+        let config = Config::new().with_data_dir(root.to_str().unwrap());
+        let data_file = config.data_file();
+        
+        let mut registry = ScraperRegistry::new();
+        registry.register("hkex", "香港交易所", |_| {
+            let scraper = MockScraper {
+                exchange: Exchange::custom("hkex")?,
+                symbol: "00700",
+                trading_days: vec![date("2025-05-15"), date("2025-05-16")],
+                historical: true,
+            };
+            Ok(Arc::new(scraper) as Arc<dyn StockScraper + Send + Sync>)
+        });
+        let scrapers = registry.resolve("HKEX", &config).unwrap();
+        assert_eq!(scrapers[0].exchange_code(), "HKEX");
+        
+        let service = DataService::new(config, scrapers);
+        service.process_daily_stocks(&date("2025-05-16")).await.unwrap();
+        
+        let provider = StockDataProvider::load_from_file(&data_file).unwrap();
+        let id: SecurityId = "00700.HKEX".parse().unwrap();
+        assert_eq!(id.exchange, Exchange::custom("HKEX").unwrap());
+        let stock = provider.get_stock(&id).unwrap();
+        assert_eq!(stock.exchange.code(), "HKEX");
+        assert_eq!(stock.daily.len(), 2);
+        assert_eq!(stock.daily[0].date, TradeDate::from(date("2025-05-16")));
+        
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn backfill_skips_current_day_sources_and_reports_holidays() {
+        let root = temp_dir("backfill");
+        let config = Config::new().with_data_dir(root.to_str().unwrap());
+        let data_file = config.data_file();
+        // 2025-05-14 是周三，上游没有数据
+        let trading_days = vec![date("2025-05-12"), date("2025-05-13"), date("2025-05-15"), date("2025-05-16")];
+        let scrapers: Vec<Arc<dyn StockScraper + Send + Sync>> = vec![
+            Arc::new(MockScraper { exchange: Exchange::Sse, symbol: "600000", trading_days: trading_days.clone(), historical: false }),
+            Arc::new(MockScraper { exchange: Exchange::Szse, symbol: "000001", trading_days, historical: true }),
+        ];
+        let service = DataService::new(config, scrapers);
+        
+        let report = service.backfill(date("2025-05-12")..=date("2025-05-18")).await.unwrap();
+        assert_eq!(report.skipped_exchanges, vec![Exchange::Sse]);
+        assert_eq!(report.missing_days, vec![(date("2025-05-14"), Exchange::Szse)]);
+        assert_eq!(report.processed_days, vec![date("2025-05-12"), date("2025-05-13"), date("2025-05-15"), date("2025-05-16")]);
+        
+        let provider = StockDataProvider::load_from_file(&data_file).unwrap();
+        assert_eq!(provider.get_all_stocks().len(), 1);
+        let stock = provider.get_stock(&SecurityId::new(Exchange::Szse, "000001")).unwrap();
+        let dates: Vec<_> = stock.daily.iter().map(|d| d.date.naive_date()).collect();
+        assert_eq!(dates, vec![date("2025-05-16"), date("2025-05-15"), date("2025-05-13"), date("2025-05-12")]);
+        
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn failed_archive_keeps_primary_file_and_truncated_bars() {
+        let root = temp_dir("archive");
+        // 归档目录是普通文件，追加会失败
+        let archive = root.join("archive");
+        std::fs::write(&archive, b"").unwrap();
+        
+        let config = Config::new()
+            .with_data_dir(root.to_str().unwrap())
+            .with_max_kline_records(1)
+            .with_archive_dir(archive.to_str());
+        let data_file = root.join(crate::config::DATA_FILE);
+        let service = DataService::new(config, Vec::new());
+        let mut stock = StockData {
+            exchange: Exchange::Sse,
+            symbol: "600000".to_string(),
+            name: "A".to_string(),
+            daily: vec![
+                DailyData { date: "2025-05-05".parse().unwrap(), ..DailyData::default() },
+                DailyData { date: "2025-05-02".parse().unwrap(), ..DailyData::default() },
+            ],
+            name_history: Vec::new(),
+        };
+        service.limit_kline_records(&mut stock);
+        
+        assert!(service.save_data(&[stock]).await.is_err());
+        assert!(data_file.exists());
+        let truncated = service.truncated.lock().unwrap();
+        assert_eq!(truncated.len(), 1);
+        assert_eq!(truncated[0].daily.len(), 1);
+        drop(truncated);
+        
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,55 @@
+use crate::errors::Result;
+use crate::models::stock::StockData;
+use crate::storage::delta::apply_delta;
+use crate::storage::partition::{PartitionFilter, PartitionKey, PartitionedStore};
+use chrono::Datelike;
+use log::info;
+use std::path::{Path, PathBuf};
+
+/// 完整历史归档
+///
+/// 主数据文件只保留最近 `max_kline_records` 条K线，被截断的较早日线合并到归档中，
+/// 归档按交易所和年份分区保存，不做数量限制。追加时只重写内容变化的分区。
+pub struct ArchiveStore {
+    store: PartitionedStore,
+}
+
+impl ArchiveStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { store: PartitionedStore::new(root) }
+    }
+    
+    pub fn root(&self) -> &Path {
+        self.store.root()
+    }
+    
+    /// 把股票数据合并到归档：日线按日期覆盖或插入，名称以新数据为准，返回重写的分区
+    pub fn append(&self, data: Vec<StockData>) -> Result<Vec<PartitionKey>> {
+        let rows: usize = data.iter().map(|s| s.daily.len()).sum();
+        let mut archive = self.store.load(&PartitionFilter::all())?;
+        apply_delta(&mut archive, data);
+        
+        let written = self.store.save(&archive)?;
+        info!("Archived {} daily bars into {} ({} stocks)", rows, self.root().display(), archive.len());
+        Ok(written)
+    }
+    
+    /// 读取匹配过滤条件的归档数据，日线按日期降序排列
+    pub fn load(&self, filter: &PartitionFilter) -> Result<Vec<StockData>> {
+        self.store.load(filter)
+    }
+    
+    /// 读取归档并用主数据覆盖，得到包含最新日线的完整历史，主数据同样按过滤条件筛选
+    pub fn load_merged(&self, hot: Vec<StockData>, filter: &PartitionFilter) -> Result<Vec<StockData>> {
+        let mut data = self.load(filter)?;
+        let hot = hot.into_iter()
+            .filter_map(|mut stock| {
+                let exchange = stock.exchange;
+                stock.daily.retain(|d| filter.matches(PartitionKey::new(exchange, d.date.naive_date().year())));
+                (!stock.daily.is_empty()).then_some(stock)
+            })
+            .collect();
+        apply_delta(&mut data, hot);
+        Ok(data)
+    }
+}
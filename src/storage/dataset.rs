@@ -0,0 +1,316 @@
+use crate::errors::{Result, DataHubError};
+use crate::models::stock::StockData;
+use crate::models::trade_date::TradeDate;
+use crate::util::{arrow_utils, fs_utils};
+use crate::util::arrow_utils::{ArrowOptions, IpcCompression};
+use arrow::ipc::reader::FileReader;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+/// 数据集清单格式版本
+pub const DATASET_MANIFEST_VERSION: u32 = 1;
+
+const MANIFEST_SUFFIX: &str = ".manifest.json";
+
+/// 与数据文件一同发布的清单，如 `stock.arrow` 旁的 `stock.manifest.json`
+///
+/// 记录文件的 sha256、字节数、Schema 版本、最新交易日和股票数量，
+/// 用于判断是否需要更新以及在替换本地文件前校验下载内容。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetManifest {
+    pub version: u32,
+    pub file: String,
+    pub sha256: String,
+    pub size: u64,
+    pub schema_version: u32,
+    pub latest_date: Option<TradeDate>,
+    pub stocks: usize,
+    pub generated_at: String,
+    /// 增量模式下需要依次合并到数据文件上的增量文件，按日期升序
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deltas: Vec<FileEntry>,
+}
+
+/// 清单中记录的附属文件，位于数据文件的同一目录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub file: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl FileEntry {
+    pub fn from_bytes(file: &str, bytes: &[u8]) -> Self {
+        Self {
+            file: file.to_string(),
+            sha256: sha256_hex(bytes),
+            size: bytes.len() as u64,
+        }
+    }
+    
+    pub fn for_file(path: &Path) -> Result<Self> {
+        Ok(Self::from_bytes(file_name(path)?, &fs::read(path)?))
+    }
+    
+    /// 同目录下的本地路径，文件名包含路径分隔符时返回错误
+    pub fn local_path(&self, dir: &Path) -> Result<PathBuf> {
+        if Path::new(&self.file).file_name().and_then(|n| n.to_str()) != Some(self.file.as_str()) {
+            return Err(DataHubError::DataError(format!("Invalid file name in manifest: {}", self.file)));
+        }
+        Ok(dir.join(&self.file))
+    }
+    
+    /// 内容与记录一致
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        bytes.len() as u64 == self.size && sha256_hex(bytes) == self.sha256
+    }
+    
+    /// 校验内容的字节数和 sha256
+    pub fn verify(&self, bytes: &[u8]) -> Result<()> {
+        if bytes.len() as u64 != self.size {
+            return Err(DataHubError::DataError(format!(
+                "Size mismatch for {}: expected {} bytes, got {}", self.file, self.size, bytes.len()
+            )));
+        }
+        let sha256 = sha256_hex(bytes);
+        if sha256 != self.sha256 {
+            return Err(DataHubError::DataError(format!(
+                "Checksum mismatch for {}: expected {}, got {}", self.file, self.sha256, sha256
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn file_name(path: &Path) -> Result<&str> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| DataHubError::DataError(format!("Invalid path: {}", path.display())))
+}
+
+impl DatasetManifest {
+    /// 根据数据文件内容生成清单
+    pub fn from_bytes(file: &str, bytes: &[u8]) -> Result<Self> {
+        let schema = FileReader::try_new(Cursor::new(bytes), None)
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?
+            .schema();
+        let schema_version = arrow_utils::schema_version(&schema)?;
+        let data = arrow_utils::read_stock_data_from_memory(bytes)?;
+        
+        Ok(Self {
+            version: DATASET_MANIFEST_VERSION,
+            file: file.to_string(),
+            sha256: sha256_hex(bytes),
+            size: bytes.len() as u64,
+            schema_version,
+            latest_date: data.iter().filter_map(|s| s.daily.first().map(|d| d.date)).max(),
+            stocks: data.len(),
+            generated_at: chrono::Local::now().to_rfc3339(),
+            deltas: Vec::new(),
+        })
+    }
+    
+    /// 读取数据文件并生成清单
+    pub fn for_file(path: &Path) -> Result<Self> {
+        Self::from_bytes(file_name(path)?, &fs::read(path)?)
+    }
+    
+    /// 清单文件名，`stock.arrow` 对应 `stock.manifest.json`
+    pub fn file_name_for(data_file: &str) -> String {
+        let stem = data_file.rsplit_once('.').map_or(data_file, |(stem, _)| stem);
+        format!("{}{}", stem, MANIFEST_SUFFIX)
+    }
+    
+    /// 数据文件旁的清单路径
+    pub fn path_for(data_file: &Path) -> PathBuf {
+        let name = data_file.file_name().and_then(|n| n.to_str()).unwrap_or("stock.arrow");
+        data_file.with_file_name(Self::file_name_for(name))
+    }
+    
+    /// 数据文件下载地址对应的清单地址
+    pub fn url_for(data_url: &str) -> String {
+        match data_url.rsplit_once('/') {
+            Some((base, name)) => format!("{}/{}", base, Self::file_name_for(name)),
+            None => Self::file_name_for(data_url),
+        }
+    }
+    
+    pub fn from_json(bytes: &[u8]) -> Result<Self> {
+        let manifest: Self = serde_json::from_slice(bytes)?;
+        if manifest.version > DATASET_MANIFEST_VERSION {
+            return Err(DataHubError::DataError(format!(
+                "Unsupported dataset manifest version {} (max supported {})", manifest.version, DATASET_MANIFEST_VERSION
+            )));
+        }
+        Ok(manifest)
+    }
+    
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&fs::read(path)?)
+    }
+    
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let bytes = serde_json::to_vec_pretty(self)?;
+        fs_utils::write_atomic(path, 0, |file| Ok(file.write_all(&bytes)?), |_| Ok(()))
+    }
+    
+    /// 为数据文件生成清单并写到其旁边
+    pub fn publish(data_file: &Path) -> Result<Self> {
+        let manifest = Self::for_file(data_file)?;
+        manifest.save(&Self::path_for(data_file))?;
+        Ok(manifest)
+    }
+    
+    /// 数据文件本身的校验记录
+    pub fn file_entry(&self) -> FileEntry {
+        FileEntry {
+            file: self.file.clone(),
+            sha256: self.sha256.clone(),
+            size: self.size,
+        }
+    }
+    
+    /// 内容与清单一致
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        self.file_entry().matches(bytes)
+    }
+    
+    /// 校验内容的字节数和 sha256
+    pub fn verify(&self, bytes: &[u8]) -> Result<()> {
+        self.file_entry().verify(bytes)
+    }
+}
+
+/// 计算 sha256 并以小写十六进制表示
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 保存并发布数据文件：原子替换并轮换备份，生成清单，按需同时发布压缩版本及其清单，返回数据文件的清单
+pub fn save_dataset(data: &[StockData], path: &str, backups: usize, compressed_variant: IpcCompression) -> Result<DatasetManifest> {
+    arrow_utils::save_stock_data_to_arrow_with_backups(data, path, backups)?;
+    let manifest = DatasetManifest::publish(Path::new(path))?;
+    
+    // 能解压的客户端优先下载压缩版本
+    if compressed_variant != IpcCompression::None {
+        let variant = compressed_variant.variant_path(path);
+        let options = ArrowOptions::new().with_compression(compressed_variant);
+        arrow_utils::save_stock_data_to_arrow_with_options(data, &variant, &options)?;
+        DatasetManifest::publish(Path::new(&variant))?;
+    }
+    
+    Ok(manifest)
+}
+
+/// 用第 n 个备份恢复数据文件：原子替换并按 `backups` 轮换备份，再重新生成清单
+///
+/// 被替换的文件成为第一个备份，可以再恢复回来。压缩版本不随之恢复。
+pub fn restore_dataset(path: &Path, n: usize, backups: usize) -> Result<DatasetManifest> {
+    fs_utils::restore_backup(path, n, backups, |tmp| arrow_utils::validate_arrow_file(tmp).map(|_| ()))?;
+    DatasetManifest::publish(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::price::Price;
+    use crate::models::security::Exchange;
+    use crate::models::stock::DailyData;
+    
+    fn data(close: f64) -> Vec<StockData> {
+        vec![StockData {
+            exchange: Exchange::Sse,
+            symbol: "600000".to_string(),
+            name: "A".to_string(),
+            daily: vec![DailyData { date: "2025-05-06".parse().unwrap(), close: Price::from_f64(close), ..DailyData::default() }],
+            name_history: Vec::new(),
+        }]
+    }
+    
+    fn close(path: &Path) -> Price {
+        arrow_utils::read_stock_data_from_arrow(path.to_str().unwrap()).unwrap()[0].daily[0].close
+    }
+    
+    #[test]
+    fn restore_dataset_rotates_backups_and_republishes_manifest() {
+        let dir = std::env::temp_dir().join(format!("egostrategy_dataset_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stock.arrow");
+        let path_str = path.to_str().unwrap();
+        
+        save_dataset(&data(10.0), path_str, 2, IpcCompression::None).unwrap();
+        save_dataset(&data(0.01), path_str, 2, IpcCompression::None).unwrap();
+        
+        let manifest = restore_dataset(&path, 1, 2).unwrap();
+        assert_eq!(close(&path), Price::from_f64(10.0));
+        assert_eq!(DatasetManifest::load(&DatasetManifest::path_for(&path)).unwrap(), manifest);
+        manifest.verify(&fs::read(&path).unwrap()).unwrap();
+        // 被替换的文件成为第一个备份，原来的备份后移
+        assert_eq!(close(&fs_utils::backup_path(&path, 1)), Price::from_f64(0.01));
+        assert_eq!(close(&fs_utils::backup_path(&path, 2)), Price::from_f64(10.0));
+        
+        // 再恢复回来
+        restore_dataset(&path, 1, 2).unwrap();
+        assert_eq!(close(&path), Price::from_f64(0.01));
+        DatasetManifest::load(&DatasetManifest::path_for(&path)).unwrap()
+            .verify(&fs::read(&path).unwrap()).unwrap();
+        
+        assert!(restore_dataset(&path, 3, 2).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn manifest_checks_size_and_checksum() {
+        let bytes = arrow_utils::stock_data_to_arrow_bytes(&data(10.0)).unwrap();
+        let manifest = DatasetManifest::from_bytes("stock.arrow", &bytes).unwrap();
+        assert_eq!(manifest.file_entry(), FileEntry::from_bytes("stock.arrow", &bytes));
+        assert!(manifest.matches(&bytes));
+        manifest.verify(&bytes).unwrap();
+        
+        let mut modified = bytes.clone();
+        *modified.last_mut().unwrap() ^= 1;
+        assert!(!manifest.matches(&modified));
+        assert!(manifest.verify(&modified).unwrap_err().to_string().contains("Checksum mismatch"));
+        assert!(manifest.verify(&bytes[1..]).unwrap_err().to_string().contains("Size mismatch"));
+    }
+    
+    #[cfg(feature = "ipc_compression")]
+    #[test]
+    fn save_dataset_publishes_compressed_variant() {
+        let dir = std::env::temp_dir().join(format!("egostrategy_dataset_variant_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stock.arrow");
+        
+        save_dataset(&data(10.0), path.to_str().unwrap(), 0, IpcCompression::Zstd).unwrap();
+        let variant = dir.join("stock.zstd.arrow");
+        assert_eq!(close(&variant), Price::from_f64(10.0));
+        let manifest = DatasetManifest::load(&DatasetManifest::path_for(&variant)).unwrap();
+        assert_eq!(manifest.file, "stock.zstd.arrow");
+        manifest.verify(&fs::read(&variant).unwrap()).unwrap();
+        // 基础文件和清单照常发布
+        DatasetManifest::load(&DatasetManifest::path_for(&path)).unwrap()
+            .verify(&fs::read(&path).unwrap()).unwrap();
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[cfg(not(feature = "ipc_compression"))]
+    #[test]
+    fn save_dataset_requires_feature_for_compressed_variant() {
+        let dir = std::env::temp_dir().join(format!("egostrategy_dataset_variant_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stock.arrow");
+        
+        let err = save_dataset(&data(10.0), path.to_str().unwrap(), 0, IpcCompression::Zstd).unwrap_err();
+        assert!(err.to_string().contains("--features ipc_compression"));
+        assert!(!dir.join("stock.zstd.arrow").exists());
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
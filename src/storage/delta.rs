@@ -0,0 +1,585 @@
+use crate::errors::{Result, DataHubError};
+use crate::models::security::SecurityId;
+use crate::models::stock::StockData;
+use crate::models::trade_date::TradeDate;
+use crate::util::{self, arrow_utils, fs_utils};
+use crate::util::arrow_utils::IpcCompression;
+use crate::storage::dataset::{save_dataset, DatasetManifest, FileEntry};
+use arrow::array::{Array, BooleanArray, Date32Array, ListArray, RecordBatch};
+use arrow::buffer::OffsetBuffer;
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::ipc::reader::FileReader;
+use arrow::ipc::writer::FileWriter;
+use log::info;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const DELTA_PREFIX: &str = "delta-";
+const DELTA_SUFFIX: &str = ".arrow";
+
+/// 增量文件中记录删除的日线日期的列
+const REMOVED_DATES_COLUMN: &str = "removed_dates";
+/// 增量文件中标记整只股票已删除的列
+const DELETED_COLUMN: &str = "deleted";
+
+/// 增量存储：基础文件加上按交易日命名的增量文件
+///
+/// 每次保存只把新增、变化和删除的日线写入与基础文件同目录的 `delta-YYYYMMDD.arrow`，
+/// 日期为数据中的最新交易日。读取时按日期顺序把增量合并到基础文件上，`compact` 再把增量折叠回基础文件。
+/// 超出K线数量限制被截掉的日线和不再出现的股票以删除记录写入增量。
+///
+/// 基础文件的清单在 `deltas` 中列出当前的增量文件及其 sha256，下游据此下载并校验增量。
+pub struct DeltaStore {
+    base: PathBuf,
+    compressed_variant: IpcCompression,
+}
+
+impl DeltaStore {
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self { base: base.into(), compressed_variant: IpcCompression::None }
+    }
+    
+    /// 同时发布基础文件的压缩版本，清单中列出相同的增量文件
+    pub fn with_compressed_variant(mut self, compression: IpcCompression) -> Self {
+        self.compressed_variant = compression;
+        self
+    }
+    
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+    
+    fn dir(&self) -> &Path {
+        self.base.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."))
+    }
+    
+    /// 指定日期的增量文件路径
+    pub fn delta_path(&self, date: TradeDate) -> PathBuf {
+        self.dir().join(format!("{}{}{}", DELTA_PREFIX, date.to_yyyymmdd(), DELTA_SUFFIX))
+    }
+    
+    /// 按日期升序列出现有的增量文件
+    pub fn deltas(&self) -> Result<Vec<(TradeDate, PathBuf)>> {
+        let dir = self.dir();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        
+        let mut deltas = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let date = path.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix(DELTA_PREFIX)?.strip_suffix(DELTA_SUFFIX))
+                .and_then(|d| d.parse::<TradeDate>().ok());
+            if let Some(date) = date {
+                deltas.push((date, path));
+            }
+        }
+        deltas.sort();
+        
+        Ok(deltas)
+    }
+    
+    /// 读取基础文件并依次合并所有增量
+    pub fn load(&self) -> Result<Vec<StockData>> {
+        let mut data = self.load_base()?;
+        for (_, path) in self.deltas()? {
+            apply_stock_deltas(&mut data, read_delta(&path)?);
+        }
+        
+        Ok(data)
+    }
+    
+    fn load_base(&self) -> Result<Vec<StockData>> {
+        if self.base.exists() {
+            arrow_utils::read_stock_data_from_arrow(&self.base)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+    
+    /// 保存数据：与之前的合并结果比较，把新增、变化和删除的日线写入增量文件
+    ///
+    /// 增量文件按数据中的最新交易日命名，不早于已有的最后一个增量。同一交易日多次运行时重新计算该交易日的增量并覆盖，
+    /// 写入后重新发布清单。没有变化时不写文件，返回 None。基础文件不存在时直接写入基础文件。
+    pub fn save(&self, data: &[StockData]) -> Result<Option<PathBuf>> {
+        if !self.base.exists() {
+            self.save_base(data)?;
+            return Ok(Some(self.base.clone()));
+        }
+        
+        let deltas = self.deltas()?;
+        let mut previous = self.load_base()?;
+        let date = latest_date(data)
+            .max(deltas.last().map(|(date, _)| *date))
+            .or_else(|| latest_date(&previous))
+            .ok_or_else(|| DataHubError::DataError("No trading date to name the delta file".to_string()))?;
+        for (_, path) in deltas.iter().filter(|(d, _)| *d < date) {
+            apply_stock_deltas(&mut previous, read_delta(path)?);
+        }
+        
+        let path = self.delta_path(date);
+        let changes = diff_with_removals(&previous, data);
+        if changes.is_empty() {
+            info!("No changes since last run, skipping delta file");
+            if path.exists() {
+                fs::remove_file(&path)?;
+                self.publish()?;
+            }
+            return Ok(None);
+        }
+        
+        let rows: usize = changes.iter().map(|d| d.stock.daily.len()).sum();
+        let removed: usize = changes.iter().map(|d| d.removed.len()).sum();
+        write_delta(&changes, &path)?;
+        info!("Wrote {} changed and {} removed bars to {}", rows, removed, path.display());
+        self.publish()?;
+        
+        Ok(Some(path))
+    }
+    
+    /// 为基础文件及其压缩版本生成清单，列出当前的增量文件，返回基础文件的清单
+    pub fn publish(&self) -> Result<DatasetManifest> {
+        let deltas = self.deltas()?
+            .iter()
+            .map(|(_, path)| FileEntry::for_file(path))
+            .collect::<Result<Vec<_>>>()?;
+        
+        let variant = PathBuf::from(self.compressed_variant.variant_path(path_str(&self.base)?));
+        if self.compressed_variant != IpcCompression::None && variant.exists() {
+            let mut manifest = DatasetManifest::for_file(&variant)?;
+            manifest.deltas = deltas.clone();
+            manifest.save(&DatasetManifest::path_for(&variant))?;
+        }
+        
+        let mut manifest = DatasetManifest::for_file(&self.base)?;
+        manifest.deltas = deltas;
+        manifest.save(&DatasetManifest::path_for(&self.base))?;
+        Ok(manifest)
+    }
+    
+    // 写入基础文件及其清单，按需同时写入压缩版本
+    fn save_base(&self, data: &[StockData]) -> Result<()> {
+        if let Some(parent) = self.base.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        save_dataset(data, path_str(&self.base)?, 0, self.compressed_variant)?;
+        Ok(())
+    }
+    
+    /// 把所有增量折叠回基础文件并删除增量文件，返回折叠的增量文件数量
+    ///
+    /// 指定 `max_records` 时同时限制每只股票的K线数量。删除增量后重新发布清单。
+    pub fn compact(&self, max_records: Option<usize>) -> Result<usize> {
+        let deltas = self.deltas()?;
+        let mut data = self.load()?;
+        if let Some(max_records) = max_records {
+            for stock in &mut data {
+                util::limit_kline_records(&mut stock.daily, max_records, &stock.symbol);
+            }
+        }
+        
+        self.save_base(&data)?;
+        for (_, path) in &deltas {
+            fs::remove_file(path)?;
+        }
+        self.publish()?;
+        
+        info!("Compacted {} delta files into {}", deltas.len(), self.base.display());
+        Ok(deltas.len())
+    }
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| DataHubError::DataError(format!("Invalid path: {}", path.display())))
+}
+
+// 数据中的最新交易日
+fn latest_date(data: &[StockData]) -> Option<TradeDate> {
+    data.iter().filter_map(|s| s.daily.first()).map(|d| d.date).max()
+}
+
+/// 增量中的一只股票
+///
+/// `stock` 中是新增和变化的日线，名称和名称历史以它为准；`removed` 是需要删除的日线日期，
+/// `deleted` 表示整只股票已从数据中删除。
+#[derive(Debug, Clone)]
+pub struct StockDelta {
+    pub stock: StockData,
+    pub removed: Vec<TradeDate>,
+    pub deleted: bool,
+}
+
+// 不含日线的股票记录
+fn without_bars(stock: &StockData) -> StockData {
+    StockData {
+        exchange: stock.exchange,
+        symbol: stock.symbol.clone(),
+        name: stock.name.clone(),
+        daily: Vec::new(),
+        name_history: stock.name_history.clone(),
+    }
+}
+
+/// 计算从 `previous` 到 `current` 的增量，除新增和变化的日线外还记录删除的日线和股票
+pub fn diff_with_removals(previous: &[StockData], current: &[StockData]) -> Vec<StockDelta> {
+    let current_index: HashMap<SecurityId, &StockData> = current.iter()
+        .map(|s| (s.security_id(), s))
+        .collect();
+    let mut changes: HashMap<SecurityId, StockData> = diff(previous, current).into_iter()
+        .map(|s| (s.security_id(), s))
+        .collect();
+    
+    let mut deltas = Vec::new();
+    for old in previous {
+        let id = old.security_id();
+        let Some(new) = current_index.get(&id) else {
+            deltas.push(StockDelta { stock: without_bars(old), removed: Vec::new(), deleted: true });
+            continue;
+        };
+        let removed: Vec<TradeDate> = old.daily.iter()
+            .map(|d| d.date)
+            .filter(|date| new.daily.binary_search_by(|x| date.cmp(&x.date)).is_err())
+            .collect();
+        match changes.remove(&id) {
+            Some(stock) => deltas.push(StockDelta { stock, removed, deleted: false }),
+            None if !removed.is_empty() => deltas.push(StockDelta { stock: without_bars(new), removed, deleted: false }),
+            None => {}
+        }
+    }
+    
+    // 新增的股票
+    for stock in current {
+        if let Some(stock) = changes.remove(&stock.security_id()) {
+            deltas.push(StockDelta { stock, removed: Vec::new(), deleted: false });
+        }
+    }
+    
+    deltas
+}
+
+/// 将带删除记录的增量合并到数据上：先删除股票和日线，再按 `apply_delta` 合并新增和变化的日线
+pub fn apply_stock_deltas(data: &mut Vec<StockData>, deltas: Vec<StockDelta>) {
+    let deleted: HashSet<SecurityId> = deltas.iter()
+        .filter(|d| d.deleted)
+        .map(|d| d.stock.security_id())
+        .collect();
+    if !deleted.is_empty() {
+        data.retain(|s| !deleted.contains(&s.security_id()));
+    }
+    
+    let removed: HashMap<SecurityId, &[TradeDate]> = deltas.iter()
+        .filter(|d| !d.removed.is_empty())
+        .map(|d| (d.stock.security_id(), d.removed.as_slice()))
+        .collect();
+    if !removed.is_empty() {
+        for stock in data.iter_mut() {
+            if let Some(dates) = removed.get(&stock.security_id()) {
+                stock.daily.retain(|d| !dates.contains(&d.date));
+            }
+        }
+    }
+    
+    let changes = deltas.into_iter()
+        .filter(|d| !d.deleted)
+        .map(|d| d.stock)
+        .collect();
+    apply_delta(data, changes);
+}
+
+/// 读取增量文件，没有删除记录列的旧增量文件只包含新增和变化的日线
+pub fn read_delta(path: &Path) -> Result<Vec<StockDelta>> {
+    let stocks = arrow_utils::read_stock_data_from_arrow(path)?;
+    let reader = FileReader::try_new(File::open(path)?, None)
+        .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+    
+    let mut removals = Vec::with_capacity(stocks.len());
+    for batch in reader {
+        let batch = batch.map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        let removed = batch.column_by_name(REMOVED_DATES_COLUMN)
+            .map(|c| c.as_any().downcast_ref::<ListArray>()
+                .ok_or_else(|| DataHubError::ArrowError(format!("Failed to downcast {} column", REMOVED_DATES_COLUMN))))
+            .transpose()?;
+        let deleted = batch.column_by_name(DELETED_COLUMN)
+            .map(|c| c.as_any().downcast_ref::<BooleanArray>()
+                .ok_or_else(|| DataHubError::ArrowError(format!("Failed to downcast {} column", DELETED_COLUMN))))
+            .transpose()?;
+        
+        for row in 0..batch.num_rows() {
+            let dates = match removed {
+                Some(list) if list.is_valid(row) => arrow_utils::date_values(&list.value(row))?,
+                _ => Vec::new(),
+            };
+            removals.push((dates, deleted.is_some_and(|d| d.is_valid(row) && d.value(row))));
+        }
+    }
+    if removals.len() != stocks.len() {
+        return Err(DataHubError::DataError(format!(
+            "Delta file {} has {} rows but {} stocks", path.display(), removals.len(), stocks.len()
+        )));
+    }
+    
+    Ok(stocks.into_iter()
+        .zip(removals)
+        .map(|(stock, (removed, deleted))| StockDelta { stock, removed, deleted })
+        .collect())
+}
+
+/// 原子写入增量文件：当前 Schema 的嵌套布局，追加删除的日线日期和删除标记两列
+pub fn write_delta(deltas: &[StockDelta], path: &Path) -> Result<()> {
+    let stocks: Vec<StockData> = deltas.iter().map(|d| d.stock.clone()).collect();
+    let batch = arrow_utils::stock_data_to_record_batch(&stocks)?;
+    
+    let dates = Date32Array::from_iter_values(deltas.iter().flat_map(|d| d.removed.iter().map(|date| date.to_date32())));
+    let removed = ListArray::try_new(
+        Arc::new(Field::new("item", DataType::Date32, false)),
+        OffsetBuffer::from_lengths(deltas.iter().map(|d| d.removed.len())),
+        Arc::new(dates),
+        None,
+    ).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+    let deleted = BooleanArray::from(deltas.iter().map(|d| d.deleted).collect::<Vec<_>>());
+    
+    let schema = batch.schema();
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    fields.push(Field::new(REMOVED_DATES_COLUMN, removed.data_type().clone(), false));
+    fields.push(Field::new(DELETED_COLUMN, DataType::Boolean, false));
+    let mut columns = batch.columns().to_vec();
+    columns.push(Arc::new(removed));
+    columns.push(Arc::new(deleted));
+    let batch = RecordBatch::try_new(Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone())), columns)
+        .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+    
+    fs_utils::write_atomic(
+        path,
+        0,
+        |file| {
+            let mut writer = FileWriter::try_new(file, &batch.schema())
+                .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+            writer.write(&batch).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+            writer.finish().map_err(|e| DataHubError::ArrowError(e.to_string()))
+        },
+        |tmp| read_delta(tmp).map(|_| ()),
+    )
+}
+
+/// 将增量合并到数据上：日线按日期覆盖或插入，名称和名称历史以增量为准
+pub fn apply_delta(data: &mut Vec<StockData>, delta: Vec<StockData>) {
+    let mut index: HashMap<SecurityId, usize> = data.iter()
+        .enumerate()
+        .map(|(i, s)| (s.security_id(), i))
+        .collect();
+    
+    for stock in delta {
+        match index.get(&stock.security_id()) {
+            Some(&idx) => {
+                let existing = &mut data[idx];
+                for daily in &stock.daily {
+                    util::merge_daily(&mut existing.daily, daily);
+                }
+                existing.name = stock.name;
+                existing.name_history = stock.name_history;
+            }
+            None => {
+                index.insert(stock.security_id(), data.len());
+                data.push(stock);
+            }
+        }
+    }
+}
+
+/// 计算新增和变化的日线，名称或名称历史变化的股票即使没有日线变化也会包含在内
+pub fn diff(previous: &[StockData], current: &[StockData]) -> Vec<StockData> {
+    let previous: HashMap<SecurityId, &StockData> = previous.iter()
+        .map(|s| (s.security_id(), s))
+        .collect();
+    
+    let mut changes = Vec::new();
+    for stock in current {
+        let old = previous.get(&stock.security_id());
+        let daily: Vec<_> = stock.daily.iter()
+            .filter(|d| {
+                let old_daily = old.and_then(|o| {
+                    o.daily.binary_search_by(|x| d.date.cmp(&x.date)).ok().map(|pos| &o.daily[pos])
+                });
+                old_daily != Some(*d)
+            })
+            .cloned()
+            .collect();
+        let renamed = old.is_none_or(|o| o.name != stock.name || o.name_history != stock.name_history);
+        
+        if !daily.is_empty() || renamed {
+            changes.push(StockData {
+                exchange: stock.exchange,
+                symbol: stock.symbol.clone(),
+                name: stock.name.clone(),
+                daily,
+                name_history: stock.name_history.clone(),
+            });
+        }
+    }
+    
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::security::Exchange;
+    use crate::models::price::Price;
+    use crate::models::stock::DailyData;
+    
+    fn bar(date: &str, close: f64) -> DailyData {
+        DailyData { date: date.parse().unwrap(), close: Price::from_f64(close), ..DailyData::default() }
+    }
+    
+    fn stock(symbol: &str, name: &str, daily: Vec<DailyData>) -> StockData {
+        StockData {
+            exchange: Exchange::Sse,
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+            daily,
+            name_history: Vec::new(),
+        }
+    }
+    
+    fn json(data: &[StockData]) -> serde_json::Value {
+        serde_json::to_value(data).unwrap()
+    }
+    
+    #[test]
+    fn apply_diff_round_trip() {
+        let previous = vec![
+            stock("600000", "A", vec![bar("2025-05-02", 10.0), bar("2025-05-01", 9.0)]),
+            stock("600001", "B", vec![bar("2025-05-02", 5.0)]),
+        ];
+        let current = vec![
+            // 新增一条日线并修正一条已有日线
+            stock("600000", "A", vec![bar("2025-05-05", 11.0), bar("2025-05-02", 10.5), bar("2025-05-01", 9.0)]),
+            // 只改名
+            stock("600001", "B2", vec![bar("2025-05-02", 5.0)]),
+            // 新股票
+            stock("600002", "C", vec![bar("2025-05-05", 20.0)]),
+        ];
+        
+        let changes = diff(&previous, &current);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].daily.len(), 2);
+        assert!(changes[1].daily.is_empty());
+        
+        let mut merged = previous.clone();
+        apply_delta(&mut merged, changes);
+        assert_eq!(json(&merged), json(&current));
+        assert!(diff(&merged, &current).is_empty());
+    }
+    
+    #[test]
+    fn manifest_lists_deltas_until_compacted() {
+        let dir = std::env::temp_dir().join(format!("egostrategy_delta_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = DeltaStore::new(dir.join("stock.arrow"));
+        let first = vec![stock("600000", "A", vec![bar("2025-05-02", 10.0)])];
+        let second = vec![stock("600000", "A", vec![bar("2025-05-05", 11.0), bar("2025-05-02", 10.0)])];
+        
+        assert_eq!(store.save(&first).unwrap(), Some(store.base().to_path_buf()));
+        let delta = store.save(&second).unwrap().unwrap();
+        assert_eq!(delta, store.delta_path("2025-05-05".parse().unwrap()));
+        
+        let manifest = DatasetManifest::load(&DatasetManifest::path_for(store.base())).unwrap();
+        assert_eq!(manifest.deltas, vec![FileEntry::for_file(&delta).unwrap()]);
+        manifest.deltas[0].verify(&fs::read(&delta).unwrap()).unwrap();
+        assert_eq!(json(&store.load().unwrap()), json(&second));
+        
+        assert_eq!(store.compact(None).unwrap(), 1);
+        let manifest = DatasetManifest::load(&DatasetManifest::path_for(store.base())).unwrap();
+        assert!(manifest.deltas.is_empty());
+        manifest.verify(&fs::read(store.base()).unwrap()).unwrap();
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn deltas_record_truncated_bars_and_removed_stocks() {
+        let dir = std::env::temp_dir().join(format!("egostrategy_delta_removals_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = DeltaStore::new(dir.join("stock.arrow"));
+        let first = vec![
+            stock("600000", "A", vec![bar("2025-05-02", 10.0), bar("2025-05-01", 9.0), bar("2025-04-30", 8.0)]),
+            stock("600001", "B", vec![bar("2025-05-02", 5.0)]),
+            stock("600002", "C", vec![bar("2025-05-02", 7.0), bar("2025-05-01", 7.1)]),
+        ];
+        let second = vec![
+            // 新增一条日线，最早的一条超出K线数量限制被截掉
+            stock("600000", "A", vec![bar("2025-05-05", 11.0), bar("2025-05-02", 10.0), bar("2025-05-01", 9.0)]),
+            // 只删除了一条日线
+            stock("600002", "C", vec![bar("2025-05-02", 7.0)]),
+            // 600001 退市
+        ];
+        
+        store.save(&first).unwrap();
+        let path = store.save(&second).unwrap().unwrap();
+        let delta = read_delta(&path).unwrap();
+        assert_eq!(delta.len(), 3);
+        assert_eq!(delta[0].removed, vec!["2025-04-30".parse::<TradeDate>().unwrap()]);
+        assert_eq!(delta[0].stock.daily.len(), 1);
+        assert!(delta[1].deleted);
+        assert_eq!(delta[2].removed.len(), 1);
+        assert!(delta[2].stock.daily.is_empty());
+        assert_eq!(json(&store.load().unwrap()), json(&second));
+        
+        store.compact(None).unwrap();
+        assert_eq!(json(&store.load().unwrap()), json(&second));
+        assert_eq!(json(&arrow_utils::read_stock_data_from_arrow(store.base()).unwrap()), json(&second));
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn delta_files_are_named_by_trading_date_and_rewritten_on_rerun() {
+        let dir = std::env::temp_dir().join(format!("egostrategy_delta_rerun_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let store = DeltaStore::new(dir.join("stock.arrow"));
+        let first = vec![stock("600000", "A", vec![bar("2025-05-02", 10.0)])];
+        let second = vec![stock("600000", "A", vec![bar("2025-05-05", 11.0), bar("2025-05-02", 10.0)])];
+        let corrected = vec![stock("600000", "A", vec![bar("2025-05-05", 11.5), bar("2025-05-02", 10.0)])];
+        store.save(&first).unwrap();
+        
+        // 交易日 05-05 的数据无论何时保存都写入 delta-20250505.arrow
+        let path = store.save(&second).unwrap().unwrap();
+        assert_eq!(path, store.delta_path("2025-05-05".parse().unwrap()));
+        
+        // 同一交易日再次运行时重新计算并覆盖，而不是累积
+        assert_eq!(store.save(&corrected).unwrap(), Some(path.clone()));
+        assert_eq!(store.deltas().unwrap().len(), 1);
+        assert_eq!(read_delta(&path).unwrap()[0].stock.daily.len(), 1);
+        assert_eq!(json(&store.load().unwrap()), json(&corrected));
+        
+        // 修正回基础文件的内容后当日增量为空，删除增量文件并重新发布清单
+        assert_eq!(store.save(&first).unwrap(), None);
+        assert!(!path.exists());
+        assert!(DatasetManifest::load(&DatasetManifest::path_for(store.base())).unwrap().deltas.is_empty());
+        assert_eq!(json(&store.load().unwrap()), json(&first));
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn reads_delta_files_without_removal_columns() {
+        let dir = std::env::temp_dir().join(format!("egostrategy_delta_legacy_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("delta-20250505.arrow");
+        let data = vec![stock("600000", "A", vec![bar("2025-05-05", 11.0)])];
+        arrow_utils::save_stock_data_to_arrow(&data, path.to_str().unwrap()).unwrap();
+        
+        let delta = read_delta(&path).unwrap();
+        assert_eq!(delta.len(), 1);
+        assert!(delta[0].removed.is_empty() && !delta[0].deleted);
+        assert_eq!(json(&[delta[0].stock.clone()]), json(&data));
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
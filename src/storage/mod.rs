@@ -0,0 +1,15 @@
+pub mod archive;
+pub mod dataset;
+pub mod delta;
+pub mod partition;
+pub mod snapshot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+pub use archive::ArchiveStore;
+pub use dataset::{restore_dataset, save_dataset, DatasetManifest, FileEntry};
+pub use delta::DeltaStore;
+pub use partition::{Manifest, PartitionEntry, PartitionFilter, PartitionKey, PartitionedStore};
+pub use snapshot::{BarChange, SnapshotDiff, SnapshotStore};
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
@@ -0,0 +1,437 @@
+use crate::config::Config;
+use crate::errors::{Result, DataHubError};
+use crate::models::security::{Exchange, SecurityId};
+use crate::models::stock::StockData;
+use crate::models::trade_date::TradeDate;
+use crate::storage::dataset::{sha256_hex, FileEntry};
+use crate::util::{self, arrow_utils, fs_utils};
+use chrono::Datelike;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
+
+/// 清单文件名
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// 清单格式版本
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// 分区键：交易所 + 年份
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PartitionKey {
+    pub exchange: Exchange,
+    pub year: i32,
+}
+
+impl PartitionKey {
+    pub fn new(exchange: Exchange, year: i32) -> Self {
+        Self { exchange, year }
+    }
+    
+    /// 相对于数据集根目录的路径，如 `sse/2025.arrow`
+    pub fn relative_path(&self) -> String {
+        format!("{}/{}.arrow", self.exchange.code().to_lowercase(), self.year)
+    }
+}
+
+/// 清单中的分区记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartitionEntry {
+    pub exchange: Exchange,
+    pub year: i32,
+    pub path: String,
+    pub stocks: usize,
+    pub rows: usize,
+    pub min_date: Option<TradeDate>,
+    pub max_date: Option<TradeDate>,
+    /// 分区文件内容的 FNV-1a 指纹，内容不变时不重写
+    pub fingerprint: String,
+    /// 分区文件的 sha256 和字节数，下游下载分区时校验
+    #[serde(default)]
+    pub sha256: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+impl PartitionEntry {
+    pub fn key(&self) -> PartitionKey {
+        PartitionKey::new(self.exchange, self.year)
+    }
+    
+    /// 用于校验分区文件内容的记录
+    pub fn file_entry(&self) -> FileEntry {
+        FileEntry {
+            file: self.path.clone(),
+            sha256: self.sha256.clone(),
+            size: self.size,
+        }
+    }
+}
+
+/// 分区数据集清单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub schema_version: u32,
+    pub updated_at: String,
+    pub partitions: Vec<PartitionEntry>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            schema_version: arrow_utils::SCHEMA_VERSION,
+            updated_at: String::new(),
+            partitions: Vec::new(),
+        }
+    }
+}
+
+impl Manifest {
+    /// 读取清单，文件不存在时返回空清单
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::from_json(&fs::read(path)?)
+    }
+    
+    pub fn from_json(bytes: &[u8]) -> Result<Self> {
+        let manifest: Manifest = serde_json::from_slice(bytes)?;
+        if manifest.version > MANIFEST_VERSION {
+            return Err(DataHubError::DataError(format!(
+                "Unsupported manifest version {} (max supported {})", manifest.version, MANIFEST_VERSION
+            )));
+        }
+        Ok(manifest)
+    }
+    
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let bytes = serde_json::to_vec_pretty(self)?;
+        fs_utils::write_atomic(path, 0, |file| Ok(file.write_all(&bytes)?), |_| Ok(()))
+    }
+    
+    pub fn get(&self, key: PartitionKey) -> Option<&PartitionEntry> {
+        self.partitions.iter().find(|p| p.key() == key)
+    }
+}
+
+/// 分区过滤条件，默认匹配全部分区
+#[derive(Debug, Clone, Default)]
+pub struct PartitionFilter {
+    exchanges: Option<Vec<Exchange>>,
+    years: Option<RangeInclusive<i32>>,
+}
+
+impl PartitionFilter {
+    pub fn all() -> Self {
+        Self::default()
+    }
+    
+    /// 只加载指定交易所，可多次调用
+    pub fn with_exchange(mut self, exchange: Exchange) -> Self {
+        self.exchanges.get_or_insert_with(Vec::new).push(exchange);
+        self
+    }
+    
+    pub fn with_years(mut self, years: RangeInclusive<i32>) -> Self {
+        self.years = Some(years);
+        self
+    }
+    
+    /// 只加载覆盖日期区间的年份
+    pub fn with_dates(self, start: TradeDate, end: TradeDate) -> Self {
+        self.with_years(start.naive_date().year()..=end.naive_date().year())
+    }
+    
+    pub fn matches(&self, key: PartitionKey) -> bool {
+        self.exchanges.as_ref().is_none_or(|e| e.contains(&key.exchange))
+            && self.years.as_ref().is_none_or(|y| y.contains(&key.year))
+    }
+}
+
+/// 按交易所和年份分区的数据集
+///
+/// 目录结构为 `{root}/{exchange}/{year}.arrow`，根目录下的 `manifest.json` 记录各分区及其 sha256，
+/// 下游可以只下载过滤条件选中的分区。
+/// 每个分区保存股票在该年份内的日线及截至当年的名称历史，加载时名称以最新年份为准。
+pub struct PartitionedStore {
+    root: PathBuf,
+}
+
+impl PartitionedStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+    
+    /// 使用配置中的数据目录作为根目录
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(&config.data_dir)
+    }
+    
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    
+    pub fn manifest_path(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILE)
+    }
+    
+    pub fn manifest(&self) -> Result<Manifest> {
+        Manifest::load(&self.manifest_path())
+    }
+    
+    /// 分区文件的本地路径，清单中的路径必须是根目录下的相对路径
+    pub fn partition_path(&self, entry: &PartitionEntry) -> Result<PathBuf> {
+        let relative = Path::new(&entry.path);
+        if entry.path.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(DataHubError::DataError(format!("Invalid partition path: {}", entry.path)));
+        }
+        Ok(self.root.join(relative))
+    }
+    
+    /// 将股票数据拆分到各分区
+    ///
+    /// 没有日线的股票放入所属交易所的最新年份分区。
+    pub fn split(data: &[StockData]) -> BTreeMap<PartitionKey, Vec<StockData>> {
+        let mut partitions: BTreeMap<PartitionKey, Vec<StockData>> = BTreeMap::new();
+        let mut latest_year: HashMap<Exchange, i32> = HashMap::new();
+        
+        for stock in data {
+            let mut by_year: BTreeMap<i32, Vec<_>> = BTreeMap::new();
+            for daily in &stock.daily {
+                by_year.entry(daily.date.naive_date().year()).or_default().push(daily.clone());
+            }
+            if let Some(&year) = by_year.keys().next_back() {
+                let latest = latest_year.entry(stock.exchange).or_insert(year);
+                *latest = (*latest).max(year);
+            }
+            
+            let last_year = by_year.keys().next_back().copied();
+            for (year, daily) in by_year {
+                // 较早的分区只记录截至当年的名称，改名时不必重写历史分区
+                let (name, name_history) = if Some(year) == last_year {
+                    (stock.name.clone(), stock.name_history.clone())
+                } else {
+                    let year_end = daily.iter().map(|d| d.date).max().unwrap_or_default();
+                    let history = stock.name_history.iter()
+                        .filter(|c| c.effective_from <= year_end)
+                        .cloned()
+                        .collect();
+                    (stock.name_as_of(year_end).to_string(), history)
+                };
+                
+                partitions.entry(PartitionKey::new(stock.exchange, year)).or_default().push(StockData {
+                    exchange: stock.exchange,
+                    symbol: stock.symbol.clone(),
+                    name,
+                    daily,
+                    name_history,
+                });
+            }
+        }
+        
+        for stock in data.iter().filter(|s| s.daily.is_empty()) {
+            let year = latest_year.get(&stock.exchange).copied()
+                .unwrap_or_else(|| chrono::Local::now().year());
+            partitions.entry(PartitionKey::new(stock.exchange, year)).or_default().push(stock.clone());
+        }
+        
+        partitions
+    }
+    
+    /// 保存数据集，只重写内容发生变化的分区，并删除不再存在的分区，返回重写的分区
+    pub fn save(&self, data: &[StockData]) -> Result<Vec<PartitionKey>> {
+        let previous = self.manifest()?;
+        let mut manifest = Manifest {
+            updated_at: chrono::Local::now().to_rfc3339(),
+            ..Manifest::default()
+        };
+        let mut written = Vec::new();
+        
+        for (key, stocks) in Self::split(data) {
+            let bytes = arrow_utils::stock_data_to_arrow_bytes(&stocks)?;
+            let fingerprint = format!("{:016x}", util::fnv1a64(&bytes));
+            let path = self.root.join(key.relative_path());
+            
+            let unchanged = previous.get(key).is_some_and(|p| p.fingerprint == fingerprint) && path.exists();
+            if !unchanged {
+                fs_utils::write_atomic(
+                    &path,
+                    0,
+                    |file| Ok(file.write_all(&bytes)?),
+                    |tmp| arrow_utils::validate_arrow_file(tmp).map(|_| ()),
+                )?;
+                written.push(key);
+            }
+            
+            let dates = || stocks.iter().flat_map(|s| s.daily.iter().map(|d| d.date));
+            manifest.partitions.push(PartitionEntry {
+                exchange: key.exchange,
+                year: key.year,
+                path: key.relative_path(),
+                stocks: stocks.len(),
+                rows: stocks.iter().map(|s| s.daily.len()).sum(),
+                min_date: dates().min(),
+                max_date: dates().max(),
+                fingerprint,
+                sha256: sha256_hex(&bytes),
+                size: bytes.len() as u64,
+            });
+        }
+        
+        for stale in previous.partitions.iter().filter(|p| manifest.get(p.key()).is_none()) {
+            let path = self.root.join(&stale.path);
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            info!("Removed stale partition {}", stale.path);
+        }
+        
+        fs::create_dir_all(&self.root)?;
+        manifest.save(&self.manifest_path())?;
+        info!("Saved {} partitions to {} ({} rewritten)",
+              manifest.partitions.len(), self.root.display(), written.len());
+        
+        Ok(written)
+    }
+    
+    /// 加载匹配过滤条件的分区并按股票合并，日线按日期降序排列
+    ///
+    /// 每个分区文件都按清单中的 sha256 和字节数校验，不一致时返回错误。
+    pub fn load(&self, filter: &PartitionFilter) -> Result<Vec<StockData>> {
+        let manifest = self.manifest()?;
+        let mut entries: Vec<&PartitionEntry> = manifest.partitions.iter()
+            .filter(|p| filter.matches(p.key()))
+            .collect();
+        entries.sort_by_key(|p| p.key());
+        
+        let mut result: Vec<StockData> = Vec::new();
+        let mut index: HashMap<SecurityId, usize> = HashMap::new();
+        
+        for entry in entries {
+            let bytes = fs::read(self.partition_path(entry)?)?;
+            // 旧版清单没有记录 sha256，只能跳过校验
+            if !entry.sha256.is_empty() {
+                entry.file_entry().verify(&bytes)?;
+            }
+            
+            for stock in arrow_utils::read_stock_data_from_memory(&bytes)? {
+                match index.get(&stock.security_id()) {
+                    Some(&idx) => {
+                        // 分区按年份升序加载，名称以较新的分区为准
+                        let existing = &mut result[idx];
+                        existing.daily.extend(stock.daily);
+                        existing.name = stock.name;
+                        existing.name_history = stock.name_history;
+                    }
+                    None => {
+                        index.insert(stock.security_id(), result.len());
+                        result.push(stock);
+                    }
+                }
+            }
+        }
+        
+        for stock in &mut result {
+            stock.daily.sort_by_key(|d| std::cmp::Reverse(d.date));
+        }
+        
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::stock::DailyData;
+    
+    fn entry(path: &str) -> PartitionEntry {
+        PartitionEntry {
+            exchange: Exchange::Sse,
+            year: 2025,
+            path: path.to_string(),
+            stocks: 0,
+            rows: 0,
+            min_date: None,
+            max_date: None,
+            fingerprint: String::new(),
+            sha256: String::new(),
+            size: 0,
+        }
+    }
+    
+    #[test]
+    fn partition_path_rejects_paths_outside_root() {
+        let store = PartitionedStore::new("data");
+        assert_eq!(store.partition_path(&entry("sse/2025.arrow")).unwrap(), Path::new("data/sse/2025.arrow"));
+        for path in ["", "../stock.arrow", "sse/../../x.arrow", "/etc/passwd", "./sse/2025.arrow"] {
+            assert!(store.partition_path(&entry(path)).is_err(), "{} was accepted", path);
+        }
+    }
+    
+    #[test]
+    fn manifest_records_partition_checksums() {
+        let root = std::env::temp_dir().join(format!("egostrategy_partition_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let store = PartitionedStore::new(&root);
+        let data = vec![StockData {
+            exchange: Exchange::Sse,
+            symbol: "600000".to_string(),
+            name: "A".to_string(),
+            daily: vec![
+                DailyData { date: "2025-01-02".parse().unwrap(), ..DailyData::default() },
+                DailyData { date: "2024-12-31".parse().unwrap(), ..DailyData::default() },
+            ],
+            name_history: Vec::new(),
+        }];
+        
+        store.save(&data).unwrap();
+        let manifest = store.manifest().unwrap();
+        assert_eq!(manifest.partitions.len(), 2);
+        for entry in &manifest.partitions {
+            let bytes = fs::read(store.partition_path(entry).unwrap()).unwrap();
+            entry.file_entry().verify(&bytes).unwrap();
+        }
+        
+        fs::remove_dir_all(&root).unwrap();
+    }
+    
+    #[test]
+    fn load_rejects_modified_partition() {
+        let root = std::env::temp_dir().join(format!("egostrategy_partition_verify_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let store = PartitionedStore::new(&root);
+        let stock = |symbol: &str, date: &str| StockData {
+            exchange: Exchange::Sse,
+            symbol: symbol.to_string(),
+            name: symbol.to_string(),
+            daily: vec![DailyData { date: date.parse().unwrap(), ..DailyData::default() }],
+            name_history: Vec::new(),
+        };
+        
+        store.save(&[stock("600000", "2025-01-02")]).unwrap();
+        assert_eq!(store.load(&PartitionFilter::all()).unwrap().len(), 1);
+        
+        // 用另一份合法的分区内容替换文件，清单中的校验和不再匹配
+        let entry = store.manifest().unwrap().partitions[0].clone();
+        let path = store.partition_path(&entry).unwrap();
+        let other = PartitionedStore::new(root.join("other"));
+        other.save(&[stock("600001", "2025-01-03")]).unwrap();
+        let replacement = other.partition_path(&other.manifest().unwrap().partitions[0]).unwrap();
+        fs::copy(replacement, &path).unwrap();
+        assert!(store.load(&PartitionFilter::all()).is_err());
+        
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() / 2);
+        fs::write(&path, bytes).unwrap();
+        assert!(store.load(&PartitionFilter::all()).is_err());
+        
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,337 @@
+use crate::config::Config;
+use crate::errors::{Result, DataHubError};
+use crate::models::security::SecurityId;
+use crate::models::stock::{DailyData, StockData};
+use crate::models::trade_date::TradeDate;
+use crate::storage::dataset::DatasetManifest;
+use crate::storage::delta::DeltaStore;
+use crate::util::{arrow_utils, fs_utils};
+use crate::util::arrow_utils::ArrowOptions;
+use log::info;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT_SUFFIX: &str = ".arrow";
+
+/// 同一交易日在两个快照中不同的日线
+#[derive(Debug, Clone)]
+pub struct BarChange {
+    pub id: SecurityId,
+    pub old: DailyData,
+    pub new: DailyData,
+}
+
+/// 两个快照之间的差异
+///
+/// 滚动窗口移出的较早日线不计入差异。
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotDiff {
+    /// 只出现在新快照中的股票
+    pub added: Vec<SecurityId>,
+    /// 只出现在旧快照中的股票
+    pub removed: Vec<SecurityId>,
+    /// 名称或名称历史变化的股票
+    pub renamed: Vec<SecurityId>,
+    /// 新快照中新增的日线数量
+    pub new_bars: usize,
+    /// 两个快照都有但内容不同的日线
+    pub changed: Vec<BarChange>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.renamed.is_empty()
+            && self.new_bars == 0 && self.changed.is_empty()
+    }
+}
+
+/// 按运行日期保存的数据集快照
+///
+/// 每个快照是 `{root}/YYYYMMDD.arrow` 完整数据文件，旁边的 `YYYYMMDD.manifest.json` 记录 sha256，
+/// 读取和恢复时校验。同一天多次标记时覆盖当天的快照。
+pub struct SnapshotStore {
+    root: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+    
+    /// 使用数据目录下的 `snapshots` 目录
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(Path::new(&config.data_dir).join("snapshots"))
+    }
+    
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+    
+    /// 指定日期的快照路径
+    pub fn snapshot_path(&self, date: TradeDate) -> PathBuf {
+        self.root.join(format!("{}{}", date.to_yyyymmdd(), SNAPSHOT_SUFFIX))
+    }
+    
+    /// 按日期升序列出现有的快照
+    pub fn list(&self) -> Result<Vec<(TradeDate, PathBuf)>> {
+        if !self.root.exists() {
+            return Ok(Vec::new());
+        }
+        
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            let date = path.file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_suffix(SNAPSHOT_SUFFIX))
+                .and_then(|d| d.parse::<TradeDate>().ok());
+            if let Some(date) = date {
+                snapshots.push((date, path));
+            }
+        }
+        snapshots.sort();
+        
+        Ok(snapshots)
+    }
+    
+    /// 标记快照：把数据写入当天的快照文件并生成清单
+    pub fn tag(&self, data: &[StockData], date: TradeDate) -> Result<PathBuf> {
+        let path = self.snapshot_path(date);
+        arrow_utils::save_stock_data_to_arrow_with_options(data, path_str(&path)?, &ArrowOptions::new())?;
+        DatasetManifest::publish(&path)?;
+        info!("Tagged snapshot {} ({} stocks)", path.display(), data.len());
+        Ok(path)
+    }
+    
+    /// 指定日期当天有效的快照，即不晚于该日期的最近一个快照
+    pub fn resolve(&self, date: TradeDate) -> Result<(TradeDate, PathBuf)> {
+        self.list()?
+            .into_iter()
+            .rev()
+            .find(|(d, _)| *d <= date)
+            .ok_or_else(|| DataHubError::DataError(format!(
+                "No snapshot on or before {} in {}", date, self.root.display()
+            )))
+    }
+    
+    // 读取快照文件内容，有清单时校验 sha256
+    fn read_verified(path: &Path) -> Result<Vec<u8>> {
+        let bytes = fs::read(path)?;
+        let manifest_path = DatasetManifest::path_for(path);
+        if manifest_path.exists() {
+            DatasetManifest::load(&manifest_path)?.verify(&bytes)?;
+        }
+        Ok(bytes)
+    }
+    
+    /// 读取指定日期当天有效的快照
+    pub fn load(&self, date: TradeDate) -> Result<Vec<StockData>> {
+        let (_, path) = self.resolve(date)?;
+        let bytes = Self::read_verified(&path)?;
+        arrow_utils::read_stock_data_from_reader(bytes.as_slice())
+    }
+    
+    /// 比较两个日期的快照，`to` 相对于 `from` 的变化
+    pub fn diff(&self, from: TradeDate, to: TradeDate) -> Result<SnapshotDiff> {
+        Ok(diff(&self.load(from)?, &self.load(to)?))
+    }
+    
+    /// 用快照替换数据文件，原文件按 `backups` 轮换备份，并重新生成数据文件的清单
+    ///
+    /// 快照是标记时的完整数据，数据文件旁的增量文件要么已包含在快照中，要么晚于快照，
+    /// 因此全部删除，重新生成的清单不再列出增量。
+    pub fn restore(&self, date: TradeDate, target: &Path, backups: usize) -> Result<TradeDate> {
+        let (snapshot_date, path) = self.resolve(date)?;
+        let bytes = Self::read_verified(&path)?;
+        fs_utils::write_atomic(
+            target,
+            backups,
+            |file| Ok(file.write_all(&bytes)?),
+            |tmp| arrow_utils::validate_arrow_file(tmp).map(|_| ()),
+        )?;
+        let deltas = DeltaStore::new(target).deltas()?;
+        for (_, delta) in &deltas {
+            fs::remove_file(delta)?;
+        }
+        DatasetManifest::publish(target)?;
+        
+        info!("Restored {} from snapshot {}, removed {} delta files", target.display(), path.display(), deltas.len());
+        Ok(snapshot_date)
+    }
+    
+    /// 只保留最近的 `keep` 个快照，返回删除的数量
+    pub fn prune(&self, keep: usize) -> Result<usize> {
+        let snapshots = self.list()?;
+        let stale = snapshots.len().saturating_sub(keep);
+        for (_, path) in &snapshots[..stale] {
+            fs::remove_file(path)?;
+            let manifest = DatasetManifest::path_for(path);
+            if manifest.exists() {
+                fs::remove_file(manifest)?;
+            }
+        }
+        
+        info!("Pruned {} snapshots from {}", stale, self.root.display());
+        Ok(stale)
+    }
+}
+
+fn path_str(path: &Path) -> Result<&str> {
+    path.to_str()
+        .ok_or_else(|| DataHubError::DataError(format!("Invalid path: {}", path.display())))
+}
+
+/// 计算两份数据之间的差异
+pub fn diff(from: &[StockData], to: &[StockData]) -> SnapshotDiff {
+    let old: HashMap<SecurityId, &StockData> = from.iter().map(|s| (s.security_id(), s)).collect();
+    let new: HashMap<SecurityId, &StockData> = to.iter().map(|s| (s.security_id(), s)).collect();
+    let mut result = SnapshotDiff::default();
+    
+    for stock in to {
+        let id = stock.security_id();
+        let Some(previous) = old.get(&id) else {
+            result.new_bars += stock.daily.len();
+            result.added.push(id);
+            continue;
+        };
+        
+        if previous.name != stock.name || previous.name_history != stock.name_history {
+            result.renamed.push(id.clone());
+        }
+        for daily in &stock.daily {
+            match previous.daily.binary_search_by(|x| daily.date.cmp(&x.date)) {
+                Ok(pos) if previous.daily[pos] != *daily => result.changed.push(BarChange {
+                    id: id.clone(),
+                    old: previous.daily[pos].clone(),
+                    new: daily.clone(),
+                }),
+                Ok(_) => {}
+                Err(_) => result.new_bars += 1,
+            }
+        }
+    }
+    
+    result.removed = from.iter()
+        .map(|s| s.security_id())
+        .filter(|id| !new.contains_key(id))
+        .collect();
+    
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::price::Price;
+    use crate::models::security::Exchange;
+    
+    fn date(s: &str) -> TradeDate {
+        s.parse().unwrap()
+    }
+    
+    fn bar(d: &str, close: f64) -> DailyData {
+        DailyData { date: date(d), close: Price::from_f64(close), ..DailyData::default() }
+    }
+    
+    fn stock(symbol: &str, name: &str, daily: Vec<DailyData>) -> StockData {
+        StockData {
+            exchange: Exchange::Szse,
+            symbol: symbol.to_string(),
+            name: name.to_string(),
+            daily,
+            name_history: Vec::new(),
+        }
+    }
+    
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("egostrategy_snapshot_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+    
+    #[test]
+    fn tag_list_diff_and_prune() {
+        let dir = temp_dir("tag");
+        let store = SnapshotStore::new(&dir);
+        let first = vec![
+            stock("000001", "A", vec![bar("2025-05-06", 10.0)]),
+            stock("000002", "B", vec![bar("2025-05-06", 5.0)]),
+        ];
+        let second = vec![
+            stock("000001", "A1", vec![bar("2025-05-07", 10.5), bar("2025-05-06", 10.1)]),
+            stock("000003", "C", vec![bar("2025-05-07", 8.0)]),
+        ];
+        store.tag(&first, date("2025-05-06")).unwrap();
+        store.tag(&second, date("2025-05-07")).unwrap();
+        store.tag(&second, date("2025-05-09")).unwrap();
+        
+        let dates: Vec<_> = store.list().unwrap().into_iter().map(|(d, _)| d).collect();
+        assert_eq!(dates, vec![date("2025-05-06"), date("2025-05-07"), date("2025-05-09")]);
+        assert_eq!(store.resolve(date("2025-05-08")).unwrap().0, date("2025-05-07"));
+        assert!(store.resolve(date("2025-05-05")).is_err());
+        
+        let diff = store.diff(date("2025-05-06"), date("2025-05-08")).unwrap();
+        assert_eq!(diff.added, vec![second[1].security_id()]);
+        assert_eq!(diff.removed, vec![first[1].security_id()]);
+        assert_eq!(diff.renamed, vec![first[0].security_id()]);
+        assert_eq!(diff.new_bars, 2);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].new.close, Price::from_f64(10.1));
+        assert!(store.diff(date("2025-05-07"), date("2025-05-09")).unwrap().is_empty());
+        
+        assert_eq!(store.prune(1).unwrap(), 2);
+        let remaining = store.list().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].0, date("2025-05-09"));
+        assert!(!DatasetManifest::path_for(&store.snapshot_path(date("2025-05-06"))).exists());
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn load_rejects_modified_snapshot() {
+        let dir = temp_dir("verify");
+        let store = SnapshotStore::new(&dir);
+        let path = store.tag(&[stock("000001", "A", vec![bar("2025-05-06", 10.0)])], date("2025-05-06")).unwrap();
+        
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+        assert!(store.load(date("2025-05-06")).is_err());
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    
+    #[test]
+    fn restore_removes_deltas_and_republishes_manifest() {
+        let dir = temp_dir("restore");
+        let store = SnapshotStore::new(dir.join("snapshots"));
+        let deltas = DeltaStore::new(dir.join("stock.arrow"));
+        let good = vec![stock("000001", "A", vec![bar("2025-05-07", 10.5), bar("2025-05-06", 10.0)])];
+        deltas.save(&good[..1]).unwrap();
+        deltas.save(&good).unwrap();
+        store.tag(&deltas.load().unwrap(), date("2025-05-07")).unwrap();
+        
+        // 错误的数据源改写了已有日线，写入当日的增量文件
+        let bad = vec![stock("000001", "A", vec![bar("2025-05-08", 99.0), bar("2025-05-07", 0.01), bar("2025-05-06", 10.0)])];
+        deltas.save(&bad).unwrap();
+        assert_eq!(deltas.load().unwrap()[0].daily[1].close, Price::from_f64(0.01));
+        
+        assert_eq!(store.restore(date("2025-05-08"), deltas.base(), 1).unwrap(), date("2025-05-07"));
+        assert!(deltas.deltas().unwrap().is_empty());
+        let restored = deltas.load().unwrap();
+        assert_eq!(restored[0].daily.len(), 2);
+        assert_eq!(restored[0].daily[0].close, Price::from_f64(10.5));
+        assert_eq!(restored[0].daily[1].close, Price::from_f64(10.0));
+        
+        let manifest = DatasetManifest::load(&DatasetManifest::path_for(deltas.base())).unwrap();
+        assert!(manifest.deltas.is_empty());
+        manifest.verify(&fs::read(deltas.base()).unwrap()).unwrap();
+        assert!(fs_utils::backup_path(deltas.base(), 1).exists());
+        
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
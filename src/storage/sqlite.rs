@@ -0,0 +1,390 @@
+use crate::errors::{Result, DataHubError};
+use crate::models::price::Price;
+use crate::models::security::{Exchange, SecurityId};
+use crate::models::stock::{DailyData, NameChange, StockData};
+use crate::models::trade_date::TradeDate;
+use crate::util::arrow_utils;
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// SQLite 表结构版本
+///
+/// - 1：价格以元为单位的 REAL 保存
+/// - 2：价格以厘（0.001元）为单位的 INTEGER 保存
+pub const SQLITE_SCHEMA_VERSION: u32 = 2;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS securities (
+        exchange TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        name TEXT NOT NULL,
+        PRIMARY KEY (exchange, symbol)
+    );
+    CREATE TABLE IF NOT EXISTS name_history (
+        exchange TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        effective_from TEXT NOT NULL,
+        name TEXT NOT NULL,
+        PRIMARY KEY (exchange, symbol, effective_from)
+    );
+    CREATE TABLE IF NOT EXISTS daily_bars (
+        exchange TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        date TEXT NOT NULL,
+        open INTEGER NOT NULL,
+        high INTEGER NOT NULL,
+        low INTEGER NOT NULL,
+        close INTEGER NOT NULL,
+        volume INTEGER NOT NULL,
+        amount INTEGER NOT NULL,
+        pre_close INTEGER NOT NULL,
+        change INTEGER NOT NULL,
+        change_pct REAL NOT NULL,
+        turnover_rate REAL NOT NULL,
+        PRIMARY KEY (exchange, symbol, date)
+    );
+    CREATE INDEX IF NOT EXISTS idx_daily_bars_symbol_date ON daily_bars (symbol, date);
+";
+
+/// SQLite 数据库
+///
+/// `securities` 保存证券列表，`daily_bars` 每只股票每个交易日一行，日期为 `YYYY-MM-DD` 文本，
+/// 价格与 `Price` 一致，以厘（0.001元）为单位的整数保存，查询时除以1000得到元。
+///
+/// 默认保存时只新增或更新记录，不删除数据库中已有的证券和历史日线，数据库可以保留比主数据更长的历史；
+/// 需要与写入的数据完全一致时使用 `with_prune`。
+pub struct SqliteStore {
+    path: PathBuf,
+    prune: bool,
+}
+
+impl SqliteStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), prune: false }
+    }
+    
+    /// 保存时删除写入数据中没有的证券、名称历史和日线
+    pub fn with_prune(mut self, prune: bool) -> Self {
+        self.prune = prune;
+        self
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    fn open(&self) -> Result<Connection> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let mut conn = Connection::open(&self.path)?;
+        conn.execute_batch(SCHEMA)?;
+        
+        let version: Option<String> = conn.query_row(
+            "SELECT value FROM meta WHERE key = 'schema_version'", [], |row| row.get(0),
+        ).optional()?;
+        let version = match version {
+            Some(version) => version.parse::<u32>().ok().filter(|&v| v <= SQLITE_SCHEMA_VERSION).ok_or_else(|| {
+                DataHubError::DataError(format!(
+                    "Unsupported SQLite schema version {} (max supported {})", version, SQLITE_SCHEMA_VERSION
+                ))
+            })?,
+            None => SQLITE_SCHEMA_VERSION,
+        };
+        if version < 2 {
+            Self::migrate_v1_prices(&mut conn)?;
+        }
+        
+        Ok(conn)
+    }
+    
+    // v1 -> v2：价格列由元为单位的 REAL 改为厘为单位的 INTEGER
+    fn migrate_v1_prices(conn: &mut Connection) -> Result<()> {
+        info!("Upgrading SQLite schema from v1 to v{}", SQLITE_SCHEMA_VERSION);
+        let tx = conn.transaction()?;
+        // REAL 列的类型亲和性会把整数存成浮点数，因此按当前的表结构重建日线表，价格四舍五入到厘
+        tx.execute_batch(
+            "ALTER TABLE daily_bars RENAME TO daily_bars_v1;
+             DROP INDEX IF EXISTS idx_daily_bars_symbol_date;",
+        )?;
+        tx.execute_batch(SCHEMA)?;
+        tx.execute_batch(
+            "INSERT INTO daily_bars
+             SELECT exchange, symbol, date,
+                    CAST(ROUND(open * 1000) AS INTEGER), CAST(ROUND(high * 1000) AS INTEGER),
+                    CAST(ROUND(low * 1000) AS INTEGER), CAST(ROUND(close * 1000) AS INTEGER),
+                    volume, amount,
+                    CAST(ROUND(pre_close * 1000) AS INTEGER), CAST(ROUND(change * 1000) AS INTEGER),
+                    change_pct, turnover_rate
+             FROM daily_bars_v1;
+             DROP TABLE daily_bars_v1;",
+        )?;
+        tx.execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?1)", params![SQLITE_SCHEMA_VERSION.to_string()])?;
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// 写入股票数据，整体在一个事务中完成
+    pub fn save(&self, data: &[StockData]) -> Result<()> {
+        let mut conn = self.open()?;
+        let tx = conn.transaction()?;
+        let mut rows = 0;
+        
+        if self.prune {
+            tx.execute_batch("DELETE FROM daily_bars; DELETE FROM name_history; DELETE FROM securities;")?;
+        }
+        
+        {
+            let mut security = tx.prepare(
+                "INSERT INTO securities (exchange, symbol, name) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (exchange, symbol) DO UPDATE SET name = excluded.name",
+            )?;
+            let mut clear_history = tx.prepare("DELETE FROM name_history WHERE exchange = ?1 AND symbol = ?2")?;
+            let mut history = tx.prepare(
+                "INSERT INTO name_history (exchange, symbol, effective_from, name) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            let mut bar = tx.prepare(
+                "INSERT OR REPLACE INTO daily_bars
+                 (exchange, symbol, date, open, high, low, close, volume, amount, pre_close, change, change_pct, turnover_rate)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            )?;
+            
+            for stock in data {
+                let exchange = stock.exchange.code();
+                security.execute(params![exchange, stock.symbol, stock.name])?;
+                
+                clear_history.execute(params![exchange, stock.symbol])?;
+                for change in &stock.name_history {
+                    history.execute(params![exchange, stock.symbol, change.effective_from.to_string(), change.name])?;
+                }
+                
+                for daily in &stock.daily {
+                    bar.execute(params![
+                        exchange,
+                        stock.symbol,
+                        daily.date.to_string(),
+                        daily.open.as_milli(),
+                        daily.high.as_milli(),
+                        daily.low.as_milli(),
+                        daily.close.as_milli(),
+                        daily.volume,
+                        daily.amount,
+                        daily.pre_close.as_milli(),
+                        daily.change.as_milli(),
+                        daily.change_pct,
+                        daily.turnover_rate,
+                    ])?;
+                }
+                rows += stock.daily.len();
+            }
+            
+            let mut meta = tx.prepare("INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)")?;
+            meta.execute(params!["schema_version", SQLITE_SCHEMA_VERSION.to_string()])?;
+            meta.execute(params!["arrow_schema_version", arrow_utils::SCHEMA_VERSION.to_string()])?;
+            meta.execute(params!["updated_at", chrono::Local::now().to_rfc3339()])?;
+        }
+        
+        tx.commit()?;
+        info!("Wrote {} stocks and {} daily bars to {}", data.len(), rows, self.path.display());
+        Ok(())
+    }
+    
+    /// 读取全部股票数据，日线按日期降序排列
+    pub fn load(&self) -> Result<Vec<StockData>> {
+        let conn = self.open()?;
+        let mut result: Vec<StockData> = Vec::new();
+        let mut index: HashMap<SecurityId, usize> = HashMap::new();
+        
+        let mut stmt = conn.prepare("SELECT exchange, symbol, name FROM securities ORDER BY exchange, symbol")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let exchange = row.get::<_, String>(0)?.parse::<Exchange>()?;
+            let symbol: String = row.get(1)?;
+            index.insert(SecurityId::new(exchange, &symbol), result.len());
+            result.push(StockData {
+                exchange,
+                symbol,
+                name: row.get(2)?,
+                daily: Vec::new(),
+                name_history: Vec::new(),
+            });
+        }
+        
+        let mut stmt = conn.prepare(
+            "SELECT exchange, symbol, effective_from, name FROM name_history ORDER BY exchange, symbol, effective_from",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            if let Some(stock) = Self::lookup(&mut result, &index, row.get(0)?, row.get(1)?)? {
+                stock.name_history.push(NameChange {
+                    effective_from: row.get::<_, String>(2)?.parse()?,
+                    name: row.get(3)?,
+                });
+            }
+        }
+        
+        let mut stmt = conn.prepare(
+            "SELECT exchange, symbol, date, open, high, low, close, volume, amount, pre_close, change, change_pct, turnover_rate
+             FROM daily_bars ORDER BY exchange, symbol, date DESC",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            if let Some(stock) = Self::lookup(&mut result, &index, row.get(0)?, row.get(1)?)? {
+                stock.daily.push(DailyData {
+                    date: row.get::<_, String>(2)?.parse::<TradeDate>()?,
+                    open: Price::from_milli(row.get(3)?),
+                    high: Price::from_milli(row.get(4)?),
+                    low: Price::from_milli(row.get(5)?),
+                    close: Price::from_milli(row.get(6)?),
+                    volume: row.get(7)?,
+                    amount: row.get(8)?,
+                    pre_close: Price::from_milli(row.get(9)?),
+                    change: Price::from_milli(row.get(10)?),
+                    change_pct: row.get(11)?,
+                    turnover_rate: row.get(12)?,
+                });
+            }
+        }
+        
+        info!("Loaded {} stocks from {}", result.len(), self.path.display());
+        Ok(result)
+    }
+    
+    // 查找行所属的股票，securities 中没有的记录忽略
+    fn lookup<'a>(
+        result: &'a mut [StockData],
+        index: &HashMap<SecurityId, usize>,
+        exchange: String,
+        symbol: String,
+    ) -> Result<Option<&'a mut StockData>> {
+        let id = SecurityId::new(exchange.parse()?, &symbol);
+        Ok(index.get(&id).map(|&i| &mut result[i]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_provider::StockDataProvider;
+    
+    fn stock(exchange: Exchange, symbol: &str, closes: &[(&str, i32)]) -> StockData {
+        StockData {
+            exchange,
+            symbol: symbol.to_string(),
+            name: format!("{}名称", symbol),
+            daily: closes.iter()
+                .map(|&(date, close)| DailyData {
+                    date: date.parse().unwrap(),
+                    open: Price::from_milli(close - 11),
+                    high: Price::from_milli(close + 7),
+                    low: Price::from_milli(close - 23),
+                    close: Price::from_milli(close),
+                    volume: 1200,
+                    amount: 12_345_678,
+                    pre_close: Price::from_milli(close - 100),
+                    change: Price::from_milli(100),
+                    change_pct: 1.5,
+                    turnover_rate: 0.25,
+                })
+                .collect(),
+            name_history: Vec::new(),
+        }
+    }
+    
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("egostrategy_sqlite_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("stock.sqlite")
+    }
+    
+    #[test]
+    fn round_trip_through_provider_keeps_exact_prices() {
+        let path = temp_path("round_trip");
+        let mut first = stock(Exchange::Sse, "600000", &[("2025-01-03", 10_123), ("2025-01-02", 10_023)]);
+        first.name_history = vec![
+            NameChange { effective_from: "2024-06-03".parse().unwrap(), name: "旧名称".to_string() },
+            NameChange { effective_from: "2025-01-02".parse().unwrap(), name: first.name.clone() },
+        ];
+        let data = vec![first, stock(Exchange::Szse, "000001", &[("2025-01-03", 999_999_999)])];
+        
+        SqliteStore::new(&path).save(&data).unwrap();
+        let provider = StockDataProvider::load_from_sqlite(path.to_str().unwrap()).unwrap();
+        assert_eq!(serde_json::to_value(provider.get_all_stocks()).unwrap(), serde_json::to_value(&data).unwrap());
+        
+        let conn = Connection::open(&path).unwrap();
+        let (kind, close): (String, i64) = conn.query_row(
+            "SELECT typeof(close), close FROM daily_bars WHERE symbol = '600000' AND date = '2025-01-03'", [], |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!((kind.as_str(), close), ("integer", 10_123));
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn prune_removes_stale_securities_and_bars() {
+        let path = temp_path("prune");
+        let store = SqliteStore::new(&path);
+        store.save(&[
+            stock(Exchange::Sse, "600000", &[("2025-01-03", 10_000), ("2025-01-02", 9_900)]),
+            stock(Exchange::Sse, "600001", &[("2025-01-03", 5_000)]),
+        ]).unwrap();
+        
+        // 默认只新增或更新
+        let latest = vec![stock(Exchange::Sse, "600000", &[("2025-01-03", 10_100)])];
+        store.save(&latest).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].daily.len(), 2);
+        assert_eq!(loaded[0].daily[0].close, Price::from_milli(10_100));
+        
+        SqliteStore::new(&path).with_prune(true).save(&latest).unwrap();
+        assert_eq!(serde_json::to_value(store.load().unwrap()).unwrap(), serde_json::to_value(&latest).unwrap());
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn migrates_v1_real_prices() {
+        let path = temp_path("v1");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let conn = Connection::open(&path).unwrap();
+        let mut v1 = SCHEMA.to_string();
+        for column in ["open", "high", "low", "close", "pre_close", "change"] {
+            v1 = v1.replace(&format!("{} INTEGER", column), &format!("{} REAL", column));
+        }
+        conn.execute_batch(&v1).unwrap();
+        conn.execute_batch(
+            "INSERT INTO meta VALUES ('schema_version', '1');
+             INSERT INTO securities VALUES ('SSE', '600000', 'A');
+             INSERT INTO daily_bars VALUES ('SSE', '600000', '2025-01-03', 10.01, 10.5, 9.87, 10.12, 100, 1012, 10.0, 0.12, 1.2, 0.1);",
+        ).unwrap();
+        drop(conn);
+        
+        let loaded = SqliteStore::new(&path).load().unwrap();
+        let daily = &loaded[0].daily[0];
+        assert_eq!((daily.open, daily.high, daily.low, daily.close), (
+            Price::from_milli(10_010), Price::from_milli(10_500), Price::from_milli(9_870), Price::from_milli(10_120),
+        ));
+        assert_eq!((daily.pre_close, daily.change), (Price::from_milli(10_000), Price::from_milli(120)));
+        
+        let conn = Connection::open(&path).unwrap();
+        let version: String = conn.query_row("SELECT value FROM meta WHERE key = 'schema_version'", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, SQLITE_SCHEMA_VERSION.to_string());
+        let kind: String = conn.query_row("SELECT typeof(close) FROM daily_bars", [], |row| row.get(0)).unwrap();
+        assert_eq!(kind, "integer");
+        let indexes: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name = 'idx_daily_bars_symbol_date'", [], |row| row.get(0),
+        ).unwrap();
+        assert_eq!(indexes, 1);
+        
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -1,74 +1,359 @@
-use chrono::NaiveDate;
+use chrono::{Datelike, NaiveDate, Weekday};
 use log::info;
-use crate::models::stock::{StockData, DailyData};
+use crate::models::stock::{StockData, DailyData, NameChange};
+use crate::models::trade_date::TradeDate;
 use crate::errors::{Result, DataHubError};
 
 // 日期转换工具
 pub fn date_string_to_int(date_str: &str) -> Result<i32> {
-    date_str.parse::<i32>().map_err(|e| DataHubError::DataError(e.to_string()))
+    date_str.parse::<TradeDate>().map(TradeDate::to_yyyymmdd)
 }
 
 pub fn int_to_naive_date(date_int: i32) -> Result<NaiveDate> {
-    let date_str = date_int.to_string();
-    if date_str.len() != 8 {
-        return Err(DataHubError::DataError(format!("Invalid date format: {}", date_str)));
+    TradeDate::from_yyyymmdd(date_int).map(TradeDate::naive_date)
+}
+
+// 解析 YYYY-MM-DD 或 YYYYMMDD 形式的交易日期
+pub fn parse_trade_date(date_str: &str) -> Result<TradeDate> {
+    date_str.parse::<TradeDate>()
+}
+
+// 生成日期区间内的交易日（跳过周末，节假日由上游返回空数据体现）
+pub fn trading_days(start: &NaiveDate, end: &NaiveDate) -> Vec<NaiveDate> {
+    start.iter_days()
+        .take_while(|d| d <= end)
+        .filter(|d| !matches!(d.weekday(), Weekday::Sat | Weekday::Sun))
+        .collect()
+}
+
+// 为缺少前收盘价的日线数据补全前收、涨跌额和涨跌幅（日线按日期降序排列）
+pub fn fill_pre_close(daily_data: &mut [DailyData]) {
+    for i in 0..daily_data.len() {
+        if daily_data[i].pre_close.is_zero() {
+            if let Some(previous) = daily_data.get(i + 1) {
+                daily_data[i].pre_close = previous.close;
+                daily_data[i].update_change();
+            }
+        }
     }
-    
-    let year = date_str[0..4].parse::<i32>()
-        .map_err(|e| DataHubError::DataError(e.to_string()))?;
-    let month = date_str[4..6].parse::<u32>()
-        .map_err(|e| DataHubError::DataError(e.to_string()))?;
-    let day = date_str[6..8].parse::<u32>()
-        .map_err(|e| DataHubError::DataError(e.to_string()))?;
-    
-    NaiveDate::from_ymd_opt(year, month, day)
-        .ok_or_else(|| DataHubError::DataError(format!("Invalid date: {}-{}-{}", year, month, day)))
 }
 
-// 限制K线记录数量
-pub fn limit_kline_records(daily_data: &mut Vec<DailyData>, max_records: usize, symbol: &str) {
+// 合并单条日线数据，已存在的日期以新数据覆盖，保持日期降序
+pub fn merge_daily(daily: &mut Vec<DailyData>, new_daily: &DailyData) {
+    match daily.binary_search_by(|d| new_daily.date.cmp(&d.date)) {
+        Ok(pos) => daily[pos] = new_daily.clone(),
+        Err(pos) => daily.insert(pos, new_daily.clone()),
+    }
+}
+
+// FNV-1a 64位哈希，用于缓存文件名和分区内容指纹
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325u64, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+// 限制K线记录数量，返回被截断的较早记录
+pub fn limit_kline_records(daily_data: &mut Vec<DailyData>, max_records: usize, symbol: &str) -> Vec<DailyData> {
     if daily_data.len() > max_records {
         info!("Limiting {} K-line records to {} for stock {}", 
                  daily_data.len(), max_records, symbol);
-        daily_data.truncate(max_records);
+        return daily_data.split_off(max_records);
     }
+    Vec::new()
 }
 
 // Arrow数据转换工具
 pub mod arrow_utils {
     use super::*;
+    use crate::models::price::Price;
+    use crate::models::security::{Exchange, SecurityId};
+    use crate::models::trade_date::TradeDate;
     use arrow::datatypes::{DataType, Field, Schema, Fields};
-    use arrow::array::{ArrayRef, StringBuilder};
-    use arrow_array::{Int32Array, Float32Array, Int64Array, StructArray, ListArray, StringArray};
+    use arrow::array::{new_null_array, ArrayRef, BooleanArray, StringBuilder};
+    use arrow::compute::{concat_batches, filter_record_batch};
+    use arrow_array::{Int32Array, Date32Array, Decimal128Array, Float32Array, Float64Array, Int64Array, StructArray, ListArray, StringArray};
     use arrow::record_batch::RecordBatch;
-    use arrow::buffer::NullBuffer;
+    use arrow::buffer::{NullBuffer, OffsetBuffer};
     use log::info;
+    use std::collections::{BTreeMap, HashMap, HashSet};
     use std::sync::Arc;
-    use std::io::Cursor;
-    use arrow::ipc::reader::FileReader;
-    use arrow::ipc::writer::FileWriter;
+    use std::io::{BufReader, BufWriter, Cursor, Read, Seek, Write};
+    use std::path::Path;
+    use arrow::ipc::reader::{read_footer_length, FileDecoder, FileReader, StreamReader};
+    use arrow::ipc::convert::fb_to_schema;
+    use arrow::ipc::{root_as_footer, Block};
+    use arrow::buffer::Buffer;
+    use std::ptr::NonNull;
+    use arrow::ipc::writer::{FileWriter, IpcWriteOptions, StreamWriter};
+    use arrow::ipc::CompressionType;
     use std::fs::File;
+    use std::str::FromStr;
     use arrow_array::Array;
+    #[cfg(feature = "parquet")]
+    use parquet::arrow::{ArrowWriter, arrow_reader::ParquetRecordBatchReaderBuilder};
+    #[cfg(feature = "parquet")]
+    use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+    #[cfg(feature = "parquet")]
+    use parquet::file::properties::WriterProperties;
 
-    // 将股票数据转换为Arrow记录批次
-    pub fn stock_data_to_record_batch(data: &[StockData]) -> Result<RecordBatch> {
-        // 创建数组构建器
-        let mut exchange_builder = StringBuilder::new();
-        let mut symbol_builder = StringBuilder::new();
-        let mut name_builder = StringBuilder::new();
+    /// 当前 Arrow 文件的 Schema 版本
+    ///
+    /// - 1：f32 价格
+    /// - 2：以0.001元为单位的 Int32 定点价格
+    /// - 3：新增前收盘价、涨跌额、涨跌幅和换手率
+    /// - 4：新增名称历史
+    /// - 5：日期列由 YYYYMMDD 形式的 Int32 改为 Arrow Date32
+    pub const SCHEMA_VERSION: u32 = 5;
+    
+    /// 文件元数据中记录 Schema 版本的键
+    pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+    
+    /// IPC 文件格式开头的魔数，流格式没有
+    const ARROW_FILE_MAGIC: &[u8] = b"ARROW1";
+    
+    /// 流式写入时每个记录批次包含的股票数量
+    pub const STREAM_BATCH_STOCKS: usize = 256;
+    
+    /// 文件元数据中记录表格布局的键
+    pub const LAYOUT_KEY: &str = "layout";
+    
+    /// 表格布局
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum TableLayout {
+        /// 每只股票一行，日线为 List<Struct> 列
+        #[default]
+        Nested,
+        /// 扁平长表，每只股票每个交易日一行
+        Flat,
+    }
+    
+    impl FromStr for TableLayout {
+        type Err = DataHubError;
+
+        fn from_str(s: &str) -> Result<Self> {
+            match s.to_ascii_lowercase().as_str() {
+                "nested" => Ok(TableLayout::Nested),
+                "flat" | "long" => Ok(TableLayout::Flat),
+                _ => Err(DataHubError::DataError(format!("Unknown table layout: {}", s))),
+            }
+        }
+    }
+    
+    /// Arrow IPC 消息体压缩方式
+    ///
+    /// JavaScript SDK 无法解压，默认不压缩；zstd 和 lz4 需要启用 `ipc_compression` 特性。
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum IpcCompression {
+        #[default]
+        None,
+        Zstd,
+        Lz4,
+    }
+    
+    impl IpcCompression {
+        /// 压缩版本文件名中的标记，如 `stock.zstd.arrow`
+        pub fn tag(&self) -> Option<&'static str> {
+            match self {
+                IpcCompression::None => None,
+                IpcCompression::Zstd => Some("zstd"),
+                IpcCompression::Lz4 => Some("lz4"),
+            }
+        }
+        
+        /// 压缩版本的路径，`stock.arrow` 对应 `stock.zstd.arrow`，不压缩时返回原路径
+        pub fn variant_path(&self, path: &str) -> String {
+            match (self.tag(), path.rsplit_once('.')) {
+                (Some(tag), Some((stem, ext))) if !ext.contains('/') => format!("{}.{}.{}", stem, tag, ext),
+                (Some(tag), _) => format!("{}.{}", path, tag),
+                (None, _) => path.to_string(),
+            }
+        }
+        
+        fn write_options(&self) -> Result<IpcWriteOptions> {
+            let codec = match self {
+                IpcCompression::None => return Ok(IpcWriteOptions::default()),
+                IpcCompression::Zstd => CompressionType::ZSTD,
+                IpcCompression::Lz4 => CompressionType::LZ4_FRAME,
+            };
+            if !cfg!(feature = "ipc_compression") {
+                return Err(DataHubError::ArrowError(format!(
+                    "{:?} IPC compression requires building with `--features ipc_compression`", self
+                )));
+            }
+            IpcWriteOptions::default()
+                .try_with_compression(Some(codec))
+                .map_err(|e| DataHubError::ArrowError(e.to_string()))
+        }
+    }
+    
+    impl FromStr for IpcCompression {
+        type Err = DataHubError;
+
+        fn from_str(s: &str) -> Result<Self> {
+            match s.to_ascii_lowercase().as_str() {
+                "none" | "uncompressed" => Ok(IpcCompression::None),
+                "zstd" => Ok(IpcCompression::Zstd),
+                "lz4" => Ok(IpcCompression::Lz4),
+                _ => Err(DataHubError::DataError(format!("Unknown IPC compression: {}", s))),
+            }
+        }
+    }
+    
+    /// Arrow 文件写入选项
+    #[derive(Debug, Clone, Default)]
+    pub struct ArrowOptions {
+        pub layout: TableLayout,
+        pub compression: IpcCompression,
+        /// 覆盖文件时保留的备份数量
+        pub backups: usize,
+    }
+    
+    impl ArrowOptions {
+        pub fn new() -> Self {
+            Self::default()
+        }
+        
+        pub fn with_layout(mut self, layout: TableLayout) -> Self {
+            self.layout = layout;
+            self
+        }
+        
+        pub fn with_compression(mut self, compression: IpcCompression) -> Self {
+            self.compression = compression;
+            self
+        }
         
-        // 创建日线数据的字段
-        let daily_fields = Fields::from(vec![
-            Field::new("date", DataType::Int32, false),
-            Field::new("open", DataType::Float32, false),
-            Field::new("high", DataType::Float32, false),
-            Field::new("low", DataType::Float32, false),
-            Field::new("close", DataType::Float32, false),
+        pub fn with_backups(mut self, backups: usize) -> Self {
+            self.backups = backups;
+            self
+        }
+    }
+
+    // 价格列：以0.001元为单位的整数，元数据中记录小数位数
+    fn price_field(name: &str) -> Field {
+        Field::new(name, DataType::Int32, false).with_metadata(HashMap::from([
+            ("scale".to_string(), Price::DECIMALS.to_string()),
+            ("unit".to_string(), "CNY".to_string()),
+        ]))
+    }
+    
+    /// 日线数据的结构字段
+    pub fn daily_fields() -> Fields {
+        Fields::from(vec![
+            Field::new("date", DataType::Date32, false),
+            price_field("open"),
+            price_field("high"),
+            price_field("low"),
+            price_field("close"),
             Field::new("volume", DataType::Int64, false),
             Field::new("amount", DataType::Int64, false),
-        ]);
+            price_field("pre_close"),
+            price_field("change"),
+            Field::new("change_pct", DataType::Float32, false),
+            Field::new("turnover_rate", DataType::Float32, false),
+        ])
+    }
+    
+    /// 名称历史的结构字段
+    pub fn name_history_fields() -> Fields {
+        Fields::from(vec![
+            Field::new("effective_from", DataType::Date32, false),
+            Field::new("name", DataType::Utf8, false),
+        ])
+    }
+    
+    /// 股票数据的Schema，元数据中记录 Schema 版本
+    pub fn stock_schema() -> Schema {
+        let metadata = HashMap::from([(SCHEMA_VERSION_KEY.to_string(), SCHEMA_VERSION.to_string())]);
+        Schema::new_with_metadata(vec![
+            Field::new("exchange", DataType::Utf8, false),
+            Field::new("symbol", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new(
+                "daily",
+                DataType::List(Arc::new(Field::new(
+                    "item",
+                    DataType::Struct(daily_fields()),
+                    false,
+                ))),
+                true,
+            ),
+            Field::new(
+                "name_history",
+                DataType::List(Arc::new(Field::new(
+                    "item",
+                    DataType::Struct(name_history_fields()),
+                    false,
+                ))),
+                true,
+            ),
+        ], metadata)
+    }
+    
+    // 构建名称历史列
+    fn name_history_array(data: &[StockData]) -> Result<ListArray> {
+        let mut effective_from_values = Vec::new();
+        let mut name_builder = StringBuilder::new();
+        let mut offsets = vec![0];
+        
+        for stock in data {
+            for change in &stock.name_history {
+                effective_from_values.push(change.effective_from.to_date32());
+                name_builder.append_value(&change.name);
+            }
+            offsets.push(offsets.last().unwrap() + stock.name_history.len() as i32);
+        }
+        
+        let fields = name_history_fields();
+        let struct_array = StructArray::try_new(
+            fields.clone(),
+            vec![
+                Arc::new(Date32Array::from(effective_from_values)),
+                Arc::new(name_builder.finish()),
+            ],
+            None,
+        ).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        ListArray::try_new(
+            Arc::new(Field::new("item", DataType::Struct(fields), false)),
+            arrow::buffer::OffsetBuffer::new(arrow::buffer::ScalarBuffer::from(offsets)),
+            Arc::new(struct_array),
+            None,
+        ).map_err(|e| DataHubError::ArrowError(e.to_string()))
+    }
+    
+    // 读取名称历史，旧版文件中不存在该列时返回空
+    pub fn read_name_history(batch: &RecordBatch, row: usize) -> Result<Vec<NameChange>> {
+        let Some(column) = batch.column_by_name("name_history") else {
+            return Ok(Vec::new());
+        };
+        let list = column.as_any().downcast_ref::<ListArray>()
+            .ok_or_else(|| DataHubError::ArrowError("Failed to downcast name_history column".to_string()))?;
+        if list.is_null(row) {
+            return Ok(Vec::new());
+        }
         
-        // 创建日线数据数组
+        let entries = list.value(row);
+        let entries = entries.as_any().downcast_ref::<StructArray>()
+            .ok_or_else(|| DataHubError::ArrowError("Failed to downcast name_history struct".to_string()))?;
+        let (Some(date_array), Some(name_array)) = (
+            entries.column_by_name("effective_from"),
+            entries.column_by_name("name").and_then(|a| a.as_any().downcast_ref::<StringArray>()),
+        ) else {
+            return Err(DataHubError::ArrowError("Missing required columns in name history".to_string()));
+        };
+        
+        (0..entries.len())
+            .map(|j| Ok(NameChange {
+                effective_from: date_value(date_array, j)?,
+                name: name_array.value(j).to_string(),
+            }))
+            .collect()
+    }
+
+    // 将日线数据按顺序编码为结构数组
+    fn daily_struct_array<'a>(daily: impl Iterator<Item = &'a DailyData>) -> Result<StructArray> {
         let mut date_values = Vec::new();
         let mut open_values = Vec::new();
         let mut high_values = Vec::new();
@@ -76,58 +361,71 @@ pub mod arrow_utils {
         let mut close_values = Vec::new();
         let mut volume_values = Vec::new();
         let mut amount_values = Vec::new();
+        let mut pre_close_values = Vec::new();
+        let mut change_values = Vec::new();
+        let mut change_pct_values = Vec::new();
+        let mut turnover_rate_values = Vec::new();
+        
+        for daily in daily {
+            date_values.push(daily.date.to_date32());
+            open_values.push(daily.open.as_milli());
+            high_values.push(daily.high.as_milli());
+            low_values.push(daily.low.as_milli());
+            close_values.push(daily.close.as_milli());
+            volume_values.push(daily.volume);
+            amount_values.push(daily.amount);
+            pre_close_values.push(daily.pre_close.as_milli());
+            change_values.push(daily.change.as_milli());
+            change_pct_values.push(daily.change_pct);
+            turnover_rate_values.push(daily.turnover_rate);
+        }
+        
+        StructArray::try_new(
+            daily_fields(),
+            vec![
+                Arc::new(Date32Array::from(date_values)),
+                Arc::new(Int32Array::from(open_values)),
+                Arc::new(Int32Array::from(high_values)),
+                Arc::new(Int32Array::from(low_values)),
+                Arc::new(Int32Array::from(close_values)),
+                Arc::new(Int64Array::from(volume_values)),
+                Arc::new(Int64Array::from(amount_values)),
+                Arc::new(Int32Array::from(pre_close_values)),
+                Arc::new(Int32Array::from(change_values)),
+                Arc::new(Float32Array::from(change_pct_values)),
+                Arc::new(Float32Array::from(turnover_rate_values)),
+            ],
+            None,
+        ).map_err(|e| DataHubError::ArrowError(e.to_string()))
+    }
+
+    // 将股票数据转换为Arrow记录批次
+    pub fn stock_data_to_record_batch(data: &[StockData]) -> Result<RecordBatch> {
+        // 创建数组构建器
+        let mut exchange_builder = StringBuilder::new();
+        let mut symbol_builder = StringBuilder::new();
+        let mut name_builder = StringBuilder::new();
         let mut offsets = vec![0];
         let mut validity = Vec::new();
         
         // 填充数据
         for stock in data {
-            exchange_builder.append_value(&stock.exchange);
+            exchange_builder.append_value(stock.exchange.code());
             symbol_builder.append_value(&stock.symbol);
             name_builder.append_value(&stock.name);
             
-            // 添加日线数据
-            for daily in &stock.daily {
-                date_values.push(daily.date);
-                open_values.push(daily.open);
-                high_values.push(daily.high);
-                low_values.push(daily.low);
-                close_values.push(daily.close);
-                volume_values.push(daily.volume);
-                amount_values.push(daily.amount);
-            }
-            
             offsets.push(offsets.last().unwrap() + stock.daily.len() as i32);
             validity.push(true);
         }
         
         // 创建日线数据的结构数组
-        let date_array = Int32Array::from(date_values);
-        let open_array = Float32Array::from(open_values);
-        let high_array = Float32Array::from(high_values);
-        let low_array = Float32Array::from(low_values);
-        let close_array = Float32Array::from(close_values);
-        let volume_array = Int64Array::from(volume_values);
-        let amount_array = Int64Array::from(amount_values);
-        
-        let struct_array = StructArray::try_new(
-            daily_fields.clone(),
-            vec![
-                Arc::new(date_array),
-                Arc::new(open_array),
-                Arc::new(high_array),
-                Arc::new(low_array),
-                Arc::new(close_array),
-                Arc::new(volume_array),
-                Arc::new(amount_array),
-            ],
-            None,
-        ).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        let struct_array = daily_struct_array(data.iter().flat_map(|stock| &stock.daily))?;
         
         // 创建列表数组
         let offset_buffer = arrow::buffer::ScalarBuffer::from(offsets);
         let list_array = ListArray::try_new(
-            Arc::new(Field::new("item", DataType::Struct(daily_fields.clone()), false)),
-            arrow::buffer::OffsetBuffer::new(offset_buffer),
+            Arc::new(Field::new("item", DataType::Struct(daily_fields()), false)),
+            OffsetBuffer::new(offset_buffer),
             Arc::new(struct_array),
             Some(NullBuffer::from(validity)),
         ).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
@@ -137,195 +435,1744 @@ pub mod arrow_utils {
         let symbol_array: ArrayRef = Arc::new(symbol_builder.finish());
         let name_array: ArrayRef = Arc::new(name_builder.finish());
         let daily_array: ArrayRef = Arc::new(list_array);
-        
-        // 创建Schema
-        let schema = Schema::new(vec![
-            Field::new("exchange", DataType::Utf8, false),
-            Field::new("symbol", DataType::Utf8, false),
-            Field::new("name", DataType::Utf8, false),
-            Field::new(
-                "daily",
-                DataType::List(Arc::new(Field::new(
-                    "item",
-                    DataType::Struct(daily_fields),
-                    false,
-                ))),
-                true,
-            ),
-        ]);
+        let name_history_array: ArrayRef = Arc::new(name_history_array(data)?);
         
         // 创建RecordBatch
         RecordBatch::try_new(
-            Arc::new(schema),
-            vec![exchange_array, symbol_array, name_array, daily_array],
+            Arc::new(stock_schema()),
+            vec![exchange_array, symbol_array, name_array, daily_array, name_history_array],
         )
         .map_err(|e| DataHubError::ArrowError(e.to_string()))
     }
 
-    // 从Arrow文件读取股票数据
-    pub fn read_stock_data_from_arrow(path: &str) -> Result<Vec<StockData>> {
-        let file = File::open(path)?;
-        let reader = FileReader::try_new(file, None)
-            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+    // 读取价格列，兼容旧版文件中的 Float32 价格
+    fn price_column(daily_struct: &StructArray, name: &str) -> Result<Vec<Price>> {
+        let column = daily_struct.column_by_name(name)
+            .ok_or_else(|| DataHubError::ArrowError(format!("Missing {} column in daily data", name)))?;
+        price_values(column, name)
+    }
+    
+    // 读取可选的价格列，旧版文件中不存在时返回 None
+    fn optional_price_column(daily_struct: &StructArray, name: &str) -> Result<Option<Vec<Price>>> {
+        daily_struct.column_by_name(name)
+            .map(|column| price_values(column, name))
+            .transpose()
+    }
+    
+    // 读取可选的百分比列，旧版文件中不存在时返回 None
+    fn optional_percent_column<'a>(daily_struct: &'a StructArray, name: &str) -> Result<Option<&'a Float32Array>> {
+        daily_struct.column_by_name(name)
+            .map(|column| column.as_any().downcast_ref::<Float32Array>()
+                .ok_or_else(|| DataHubError::ArrowError(format!("Failed to downcast {} column", name))))
+            .transpose()
+    }
+    
+    // 读取日期列
+    fn date_column(daily_struct: &StructArray) -> Result<Vec<TradeDate>> {
+        let column = daily_struct.column_by_name("date")
+            .ok_or_else(|| DataHubError::ArrowError("Missing date column in daily data".to_string()))?;
+        date_values(column)
+    }
+    
+    /// 读取日期数组，支持 Arrow Date32 和 v4 及更早版本中 YYYYMMDD 形式的 Int32
+    pub fn date_values(column: &ArrayRef) -> Result<Vec<TradeDate>> {
+        if let Some(array) = column.as_any().downcast_ref::<Date32Array>() {
+            array.values().iter().map(|&v| TradeDate::from_date32(v)).collect()
+        } else if let Some(array) = column.as_any().downcast_ref::<Int32Array>() {
+            array.values().iter().map(|&v| TradeDate::from_yyyymmdd(v)).collect()
+        } else {
+            Err(DataHubError::ArrowError(format!(
+                "Unsupported type for date column: {}", column.data_type()
+            )))
+        }
+    }
+    
+    /// 读取日期数组中的一个值，类型同 `date_values`
+    pub fn date_value(column: &ArrayRef, i: usize) -> Result<TradeDate> {
+        if let Some(array) = column.as_any().downcast_ref::<Date32Array>() {
+            TradeDate::from_date32(array.value(i))
+        } else if let Some(array) = column.as_any().downcast_ref::<Int32Array>() {
+            TradeDate::from_yyyymmdd(array.value(i))
+        } else {
+            Err(DataHubError::ArrowError(format!(
+                "Unsupported type for date column: {}", column.data_type()
+            )))
+        }
+    }
+    
+    fn price_values(column: &ArrayRef, name: &str) -> Result<Vec<Price>> {
+        if let Some(array) = column.as_any().downcast_ref::<Int32Array>() {
+            Ok(array.values().iter().map(|&v| Price::from_milli(v)).collect())
+        } else if let Some(array) = column.as_any().downcast_ref::<Float32Array>() {
+            // 旧版文件：f32 价格四舍五入到0.001元
+            array.values().iter().map(|&v| Price::try_from_f64(v as f64)).collect()
+        } else if let Some(array) = column.as_any().downcast_ref::<Float64Array>() {
+            array.values().iter().map(|&v| Price::try_from_f64(v)).collect()
+        } else {
+            Err(DataHubError::ArrowError(format!(
+                "Unsupported type for {} column: {}", name, column.data_type()
+            )))
+        }
+    }
+    
+    // 按列名读取字符串列，不存在时返回 None
+    pub fn optional_string_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<Option<&'a StringArray>> {
+        batch.column_by_name(name)
+            .map(|column| column.as_any().downcast_ref::<StringArray>()
+                .ok_or_else(|| DataHubError::ArrowError(format!("Failed to downcast {} column", name))))
+            .transpose()
+    }
+    
+    // 读取一行的交易所，缺少 exchange 列时按代码号段推断
+    pub fn row_exchange(exchange_array: Option<&StringArray>, row: usize, symbol: &str) -> Result<Exchange> {
+        match exchange_array {
+            Some(array) => array.value(row).parse::<Exchange>(),
+            None => Exchange::infer_from_symbol(symbol).ok_or_else(|| DataHubError::ExchangeError(
+                format!("Cannot infer exchange for symbol {}", symbol)
+            )),
+        }
+    }
+    
+    // 按列名读取日线字段，价格、日期兼容旧版类型，新增字段缺失时取默认值
+    pub fn daily_from_struct(daily_struct: &StructArray) -> Result<Vec<DailyData>> {
+        let open = price_column(daily_struct, "open")?;
+        let high = price_column(daily_struct, "high")?;
+        let low = price_column(daily_struct, "low")?;
+        let close = price_column(daily_struct, "close")?;
+        let pre_close = optional_price_column(daily_struct, "pre_close")?;
+        let change = optional_price_column(daily_struct, "change")?;
+        let change_pct = optional_percent_column(daily_struct, "change_pct")?;
+        let turnover_rate = optional_percent_column(daily_struct, "turnover_rate")?;
+        let dates = date_column(daily_struct)?;
         
-        let mut result = Vec::new();
+        let (Some(volume_array), Some(amount_array)) = (
+            daily_struct.column_by_name("volume").and_then(|a| a.as_any().downcast_ref::<Int64Array>()),
+            daily_struct.column_by_name("amount").and_then(|a| a.as_any().downcast_ref::<Int64Array>())
+        ) else {
+            return Err(DataHubError::ArrowError("Missing required columns in daily data".to_string()));
+        };
         
-        for batch in reader {
-            let batch = batch.map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        Ok((0..daily_struct.len()).map(|j| DailyData {
+            date: dates[j],
+            open: open[j],
+            high: high[j],
+            low: low[j],
+            close: close[j],
+            volume: volume_array.value(j),
+            amount: amount_array.value(j),
+            pre_close: pre_close.as_ref().map(|v| v[j]).unwrap_or_default(),
+            change: change.as_ref().map(|v| v[j]).unwrap_or_default(),
+            change_pct: change_pct.map(|a| a.value(j)).unwrap_or_default(),
+            turnover_rate: turnover_rate.map(|a| a.value(j)).unwrap_or_default(),
+        }).collect())
+    }
+    
+    // 将当前版本的记录批次转换为股票数据
+    //
+    // 列按名称查找：symbol 为必需列；缺少 exchange 时按代码号段推断，缺少 name 时为空，
+    // 缺少 daily / name_history 时为空列表。
+    fn record_batch_to_stock_data(batch: &RecordBatch, result: &mut Vec<StockData>) -> Result<()> {
+        let symbol_array = optional_string_column(batch, "symbol")?
+            .ok_or_else(|| DataHubError::ArrowError("Missing symbol column".to_string()))?;
+        let exchange_array = optional_string_column(batch, "exchange")?;
+        let name_array = optional_string_column(batch, "name")?;
+        let daily_array = batch.column_by_name("daily")
+            .map(|column| column.as_any().downcast_ref::<ListArray>()
+                .ok_or_else(|| DataHubError::ArrowError("Failed to downcast daily column".to_string())))
+            .transpose()?;
+        
+        for i in 0..batch.num_rows() {
+            let symbol = symbol_array.value(i).to_string();
+            let exchange = row_exchange(exchange_array, i, &symbol)?;
+            let name = name_array.map(|a| a.value(i).to_string()).unwrap_or_default();
             
-            let exchange_array = batch.column(0).as_any().downcast_ref::<StringArray>()
-                .ok_or_else(|| DataHubError::ArrowError("Failed to downcast exchange column".to_string()))?;
-            let symbol_array = batch.column(1).as_any().downcast_ref::<StringArray>()
-                .ok_or_else(|| DataHubError::ArrowError("Failed to downcast symbol column".to_string()))?;
-            let name_array = batch.column(2).as_any().downcast_ref::<StringArray>()
-                .ok_or_else(|| DataHubError::ArrowError("Failed to downcast name column".to_string()))?;
-            let daily_array = batch.column(3).as_any().downcast_ref::<ListArray>()
-                .ok_or_else(|| DataHubError::ArrowError("Failed to downcast daily column".to_string()))?;
-            
-            for i in 0..batch.num_rows() {
-                let exchange = exchange_array.value(i).to_string();
-                let symbol = symbol_array.value(i).to_string();
-                let name = name_array.value(i).to_string();
+            let mut daily_data = Vec::new();
+            
+            if let Some(daily_array) = daily_array.filter(|a| !a.is_null(i)) {
+                let daily_list = daily_array.value(i);
+                let daily_struct = daily_list.as_any().downcast_ref::<StructArray>()
+                    .ok_or_else(|| DataHubError::ArrowError("Failed to downcast daily struct".to_string()))?;
                 
-                let mut daily_data = Vec::new();
+                daily_data = daily_from_struct(daily_struct)?;
                 
-                if !daily_array.is_null(i) {
-                    let daily_list = daily_array.value(i);
-                    if let Some(daily_struct) = daily_list.as_any().downcast_ref::<StructArray>() {
-                        if let (Some(date_array), Some(open_array), Some(high_array), 
-                                Some(low_array), Some(close_array), Some(volume_array), Some(amount_array)) = (
-                            daily_struct.column_by_name("date").and_then(|a| a.as_any().downcast_ref::<Int32Array>()),
-                            daily_struct.column_by_name("open").and_then(|a| a.as_any().downcast_ref::<Float32Array>()),
-                            daily_struct.column_by_name("high").and_then(|a| a.as_any().downcast_ref::<Float32Array>()),
-                            daily_struct.column_by_name("low").and_then(|a| a.as_any().downcast_ref::<Float32Array>()),
-                            daily_struct.column_by_name("close").and_then(|a| a.as_any().downcast_ref::<Float32Array>()),
-                            daily_struct.column_by_name("volume").and_then(|a| a.as_any().downcast_ref::<Int64Array>()),
-                            daily_struct.column_by_name("amount").and_then(|a| a.as_any().downcast_ref::<Int64Array>())
-                        ) {
-                            for j in 0..daily_struct.len() {
-                                daily_data.push(DailyData {
-                                    date: date_array.value(j),
-                                    open: open_array.value(j),
-                                    high: high_array.value(j),
-                                    low: low_array.value(j),
-                                    close: close_array.value(j),
-                                    volume: volume_array.value(j),
-                                    amount: amount_array.value(j),
-                                });
-                            }
-                        } else {
-                            return Err(DataHubError::ArrowError("Missing required columns in daily data".to_string()));
-                        }
-                    } else {
-                        return Err(DataHubError::ArrowError("Failed to downcast daily struct".to_string()));
-                    }
+                // 旧版文件没有前收盘价，根据前一交易日收盘价补全
+                if daily_struct.column_by_name("pre_close").is_none() {
+                    fill_pre_close(&mut daily_data);
                 }
-                
-                result.push(StockData {
-                    exchange,
-                    symbol,
-                    name,
-                    daily: daily_data,
-                });
             }
+            
+            result.push(StockData {
+                exchange,
+                symbol,
+                name,
+                daily: daily_data,
+                name_history: read_name_history(batch, i)?,
+            });
         }
         
-        Ok(result)
+        Ok(())
     }
 
-    // 将股票数据保存到Arrow文件
-    pub fn save_stock_data_to_arrow(data: &[StockData], path: &str) -> Result<()> {
-        // 打印保存的数据信息
-        info!("Saving {} stocks to {}", data.len(), path);
-        for stock in data {
-            info!("  - {} ({}) - {}: {} daily records", 
-                     stock.name, stock.symbol, stock.exchange, stock.daily.len());
+    /// 扁平长表的Schema：每只股票每个交易日一行，name 为当日名称
+    ///
+    /// 日线字段可以为空：收盘价为空的行是占位行，只记录股票和名称。没有日线的股票写入一行日期为空的占位行，
+    /// 每条名称历史写入一行日期为生效日的占位行。
+    pub fn flat_schema() -> Schema {
+        let mut fields = vec![
+            Field::new("exchange", DataType::Utf8, false),
+            Field::new("symbol", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+        ];
+        fields.extend(daily_fields().iter().map(|f| f.as_ref().clone().with_nullable(true)));
+        
+        let metadata = HashMap::from([
+            (SCHEMA_VERSION_KEY.to_string(), SCHEMA_VERSION.to_string()),
+            (LAYOUT_KEY.to_string(), "flat".to_string()),
+        ]);
+        Schema::new_with_metadata(fields, metadata)
+    }
+    
+    /// 判断是否为扁平长表
+    pub fn is_flat_layout(schema: &Schema) -> bool {
+        schema.column_with_name("daily").is_none() && schema.column_with_name("date").is_some()
+    }
+    
+    /// 将股票数据转换为扁平长表，按代码、日期升序排列，占位行排在日线之后
+    pub fn stock_data_to_flat_record_batch(data: &[StockData]) -> Result<RecordBatch> {
+        let mut rows: Vec<(&StockData, &DailyData)> = data.iter()
+            .flat_map(|stock| stock.daily.iter().map(move |daily| (stock, daily)))
+            .collect();
+        rows.sort_by(|(a, x), (b, y)| {
+            (&a.symbol, a.exchange, x.date).cmp(&(&b.symbol, b.exchange, y.date))
+        });
+        
+        let mut exchange_builder = StringBuilder::new();
+        let mut symbol_builder = StringBuilder::new();
+        let mut name_builder = StringBuilder::new();
+        
+        for (stock, daily) in &rows {
+            exchange_builder.append_value(stock.exchange.code());
+            symbol_builder.append_value(&stock.symbol);
+            name_builder.append_value(stock.name_as_of(daily.date));
         }
         
-        let batch = stock_data_to_record_batch(data)?;
-        let file = File::create(path)?;
+        let daily_array = daily_struct_array(rows.iter().map(|(_, daily)| *daily))?;
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(exchange_builder.finish()),
+            Arc::new(symbol_builder.finish()),
+            Arc::new(name_builder.finish()),
+        ];
+        columns.extend(daily_array.columns().iter().cloned());
         
-        // 使用默认选项，不启用压缩，确保与JavaScript SDK兼容
-        let mut writer = FileWriter::try_new(file, &batch.schema())
+        let schema = Arc::new(flat_schema());
+        let bars = RecordBatch::try_new(schema.clone(), columns)
             .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        let placeholders = flat_placeholder_batch(data, schema.clone())?;
+        concat_batches(&schema, [&bars, &placeholders])
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))
+    }
+    
+    // 扁平长表的占位行：没有日线的股票，以及每条名称历史
+    //
+    // 生效日有日线的名称变化也写入，否则只有一条记录的名称历史无法与没有名称历史区分。
+    fn flat_placeholder_batch(data: &[StockData], schema: Arc<Schema>) -> Result<RecordBatch> {
+        let mut rows: Vec<(&StockData, Option<TradeDate>, &str)> = Vec::new();
+        for stock in data {
+            rows.extend(stock.name_history.iter()
+                .map(|c| (stock, Some(c.effective_from), c.name.as_str())));
+            if stock.daily.is_empty() && stock.name_history.is_empty() {
+                rows.push((stock, None, stock.name.as_str()));
+            }
+        }
+        rows.sort_by(|(a, x, _), (b, y, _)| {
+            (&a.symbol, a.exchange, x).cmp(&(&b.symbol, b.exchange, y))
+        });
         
-        writer.write(&batch)
-            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
-        writer.finish()
-            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        let columns = schema.fields().iter()
+            .map(|field| -> ArrayRef {
+                match field.name().as_str() {
+                    "exchange" => Arc::new(StringArray::from_iter_values(rows.iter().map(|(s, _, _)| s.exchange.code()))),
+                    "symbol" => Arc::new(StringArray::from_iter_values(rows.iter().map(|(s, _, _)| s.symbol.as_str()))),
+                    "name" => Arc::new(StringArray::from_iter_values(rows.iter().map(|(_, _, name)| *name))),
+                    "date" => Arc::new(Date32Array::from_iter(rows.iter().map(|(_, date, _)| date.map(TradeDate::to_date32)))),
+                    _ => new_null_array(field.data_type(), rows.len()),
+                }
+            })
+            .collect();
+        RecordBatch::try_new(schema, columns)
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))
+    }
+    
+    // 扁平长表每行的股票和名称
+    fn flat_row_keys(batch: &RecordBatch) -> Result<Vec<(SecurityId, Option<String>)>> {
+        let symbol_array = optional_string_column(batch, "symbol")?
+            .ok_or_else(|| DataHubError::ArrowError("Missing symbol column".to_string()))?;
+        let exchange_array = optional_string_column(batch, "exchange")?;
+        let name_array = optional_string_column(batch, "name")?;
         
-        Ok(())
+        (0..batch.num_rows())
+            .map(|i| {
+                let symbol = symbol_array.value(i);
+                let exchange = row_exchange(exchange_array, i, symbol)?;
+                Ok((SecurityId::new(exchange, symbol), name_array.map(|a| a.value(i).to_string())))
+            })
+            .collect()
     }
-
-    // 从内存中读取Arrow数据
-    pub fn read_stock_data_from_memory(data: &[u8]) -> Result<Vec<StockData>> {
-        let reader = FileReader::try_new(
-            Cursor::new(data), 
-            None
-        ).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+    
+    /// 将扁平长表按股票汇总，日线按日期降序排列，并根据每日名称和占位行重建名称历史
+    pub fn flat_batches_to_stock_data(batches: impl IntoIterator<Item = RecordBatch>) -> Result<Vec<StockData>> {
+        let mut stocks: Vec<StockData> = Vec::new();
+        let mut names: Vec<Vec<(TradeDate, String)>> = Vec::new();
+        // 有名称历史占位行的股票，即使只有一个名称也保留名称历史
+        let mut has_history: HashSet<usize> = HashSet::new();
+        let mut index: HashMap<SecurityId, usize> = HashMap::new();
+        let mut has_pre_close = true;
+        let mut stock_index = |id: SecurityId, stocks: &mut Vec<StockData>, names: &mut Vec<_>| {
+            *index.entry(id.clone()).or_insert_with(|| {
+                stocks.push(StockData {
+                    exchange: id.exchange,
+                    symbol: id.symbol,
+                    name: String::new(),
+                    daily: Vec::new(),
+                    name_history: Vec::new(),
+                });
+                names.push(Vec::new());
+                stocks.len() - 1
+            })
+        };
         
-        let mut result = Vec::new();
+        for batch in batches {
+            has_pre_close &= batch.column_by_name("pre_close").is_some();
+            
+            // 收盘价为空的是占位行，与日线分开处理
+            let (bars, placeholders) = match batch.column_by_name("close") {
+                Some(close) if close.null_count() > 0 => {
+                    let is_bar: BooleanArray = (0..batch.num_rows()).map(|i| Some(close.is_valid(i))).collect();
+                    let is_placeholder: BooleanArray = (0..batch.num_rows()).map(|i| Some(close.is_null(i))).collect();
+                    let filter = |mask: &BooleanArray| filter_record_batch(&batch, mask)
+                        .map_err(|e| DataHubError::ArrowError(e.to_string()));
+                    (filter(&is_bar)?, Some(filter(&is_placeholder)?))
+                }
+                _ => (batch, None),
+            };
+            
+            let daily_data = daily_from_struct(&StructArray::from(bars.clone()))?;
+            for ((id, name), daily) in flat_row_keys(&bars)?.into_iter().zip(daily_data) {
+                let idx = stock_index(id, &mut stocks, &mut names);
+                if let Some(name) = name {
+                    names[idx].push((daily.date, name));
+                }
+                stocks[idx].daily.push(daily);
+            }
+            
+            let Some(placeholders) = placeholders else { continue };
+            let dates = placeholders.column_by_name("date")
+                .ok_or_else(|| DataHubError::ArrowError("Missing date column".to_string()))?;
+            for (i, (id, name)) in flat_row_keys(&placeholders)?.into_iter().enumerate() {
+                let idx = stock_index(id, &mut stocks, &mut names);
+                let Some(name) = name else { continue };
+                if dates.is_valid(i) {
+                    names[idx].push((date_value(dates, i)?, name));
+                    has_history.insert(idx);
+                } else {
+                    // 日期为空的占位行：没有日线也没有名称历史的股票
+                    stocks[idx].name = name;
+                }
+            }
+        }
         
-        for batch in reader {
-            let batch = batch.map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        for (idx, (stock, mut names)) in stocks.iter_mut().zip(names).enumerate() {
+            stock.daily.sort_by_key(|d| std::cmp::Reverse(d.date));
+            if !has_pre_close {
+                fill_pre_close(&mut stock.daily);
+            }
             
-            let exchange_array = batch.column(0).as_any().downcast_ref::<StringArray>()
-                .ok_or_else(|| DataHubError::ArrowError("Failed to downcast exchange column".to_string()))?;
-            let symbol_array = batch.column(1).as_any().downcast_ref::<StringArray>()
-                .ok_or_else(|| DataHubError::ArrowError("Failed to downcast symbol column".to_string()))?;
-            let name_array = batch.column(2).as_any().downcast_ref::<StringArray>()
-                .ok_or_else(|| DataHubError::ArrowError("Failed to downcast name column".to_string()))?;
-            let daily_array = batch.column(3).as_any().downcast_ref::<ListArray>()
-                .ok_or_else(|| DataHubError::ArrowError("Failed to downcast daily column".to_string()))?;
-            
-            for i in 0..batch.num_rows() {
-                let exchange = exchange_array.value(i).to_string();
-                let symbol = symbol_array.value(i).to_string();
-                let name = name_array.value(i).to_string();
-                
-                let mut daily_data = Vec::new();
-                
-                if !daily_array.is_null(i) {
-                    let daily_list = daily_array.value(i);
-                    if let Some(daily_struct) = daily_list.as_any().downcast_ref::<StructArray>() {
-                        if let (Some(date_array), Some(open_array), Some(high_array), 
-                                Some(low_array), Some(close_array), Some(volume_array), Some(amount_array)) = (
-                            daily_struct.column_by_name("date").and_then(|a| a.as_any().downcast_ref::<Int32Array>()),
-                            daily_struct.column_by_name("open").and_then(|a| a.as_any().downcast_ref::<Float32Array>()),
-                            daily_struct.column_by_name("high").and_then(|a| a.as_any().downcast_ref::<Float32Array>()),
-                            daily_struct.column_by_name("low").and_then(|a| a.as_any().downcast_ref::<Float32Array>()),
-                            daily_struct.column_by_name("close").and_then(|a| a.as_any().downcast_ref::<Float32Array>()),
-                            daily_struct.column_by_name("volume").and_then(|a| a.as_any().downcast_ref::<Int64Array>()),
-                            daily_struct.column_by_name("amount").and_then(|a| a.as_any().downcast_ref::<Int64Array>())
-                        ) {
-                            for j in 0..daily_struct.len() {
-                                daily_data.push(DailyData {
-                                    date: date_array.value(j),
-                                    open: open_array.value(j),
-                                    high: high_array.value(j),
-                                    low: low_array.value(j),
-                                    close: close_array.value(j),
-                                    volume: volume_array.value(j),
-                                    amount: amount_array.value(j),
-                                });
-                            }
-                        } else {
-                            return Err(DataHubError::ArrowError("Missing required columns in daily data".to_string()));
-                        }
-                    } else {
-                        return Err(DataHubError::ArrowError("Failed to downcast daily struct".to_string()));
-                    }
+            // 每日名称按日期合并为名称变化点，没有名称历史占位行且只有一个名称时不记录历史
+            names.sort_by_key(|(date, _)| *date);
+            let mut history: Vec<NameChange> = Vec::new();
+            for (date, name) in names {
+                if history.last().is_none_or(|c| c.name != name) {
+                    history.push(NameChange { effective_from: date, name });
                 }
-                
-                result.push(StockData {
-                    exchange,
-                    symbol,
-                    name,
-                    daily: daily_data,
-                });
+            }
+            if let Some(latest) = history.last() {
+                stock.name = latest.name.clone();
+            }
+            if history.len() > 1 || has_history.contains(&idx) {
+                stock.name_history = history;
             }
         }
         
-        Ok(result)
+        Ok(stocks)
+    }
+
+    // 从Arrow文件读取股票数据，自动识别嵌套和扁平布局，旧版本文件在读取时自动升级
+    pub fn read_stock_data_from_arrow(path: impl AsRef<Path>) -> Result<Vec<StockData>> {
+        let file = File::open(path)?;
+        let reader = FileReader::try_new(file, None)
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        read_stock_data_from_file_reader(reader)
+    }
+    
+    fn read_stock_data_from_file_reader<R: Read + Seek>(reader: FileReader<R>) -> Result<Vec<StockData>> {
+        // 扁平布局按列名读取，不需要迁移，但同样拒绝更新版本写入的文件
+        let version = schema_version(&reader.schema())?;
+        if is_flat_layout(&reader.schema()) {
+            let batches = reader.collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+            return flat_batches_to_stock_data(batches);
+        }
+        
+        if version < SCHEMA_VERSION {
+            info!("Upgrading stock data schema from v{} to v{}", version, SCHEMA_VERSION);
+        }
+        
+        let mut result = Vec::new();
+        
+        for batch in reader {
+            let batch = batch.map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+            let batch = migrate_batch(batch, version)?;
+            record_batch_to_stock_data(&batch, &mut result)?;
+        }
+        
+        Ok(result)
+    }
+
+    // 取出页脚中记录的数据块，偏移或长度为负、越界时返回错误，避免 slice_with_length 越界 panic
+    fn block_data(buffer: &Buffer, block: &Block) -> Result<Buffer> {
+        let invalid = || DataHubError::ArrowError(format!(
+            "Invalid Arrow IPC block at offset {} ({} + {} bytes)",
+            block.offset(), block.metaDataLength(), block.bodyLength()
+        ));
+        let offset = usize::try_from(block.offset()).map_err(|_| invalid())?;
+        let meta_len = usize::try_from(block.metaDataLength()).map_err(|_| invalid())?;
+        let body_len = usize::try_from(block.bodyLength()).map_err(|_| invalid())?;
+        let len = meta_len.checked_add(body_len).ok_or_else(invalid)?;
+        let end = offset.checked_add(len).ok_or_else(invalid)?;
+        if end > buffer.len() {
+            return Err(invalid());
+        }
+        Ok(buffer.slice_with_length(offset, len))
+    }
+    
+    /// 内存映射Arrow文件，返回当前版本嵌套布局的记录批次
+    ///
+    /// 当前版本的文件直接引用映射的内存，不复制数据；旧版本文件和扁平布局在内存中转换。
+    pub fn map_arrow_file(path: impl AsRef<Path>) -> Result<Vec<RecordBatch>> {
+        let file = File::open(path)?;
+        // 安全性：数据文件总是写入临时文件后重命名替换，映射期间原文件内容不会被修改
+        let mmap = Arc::new(unsafe { memmap2::Mmap::map(&file)? });
+        let invalid = || DataHubError::ArrowError("Invalid Arrow IPC file".to_string());
+        let ptr = NonNull::new(mmap.as_ptr() as *mut u8).ok_or_else(invalid)?;
+        let buffer = unsafe { Buffer::from_custom_allocation(ptr, mmap.len(), mmap.clone()) };
+        
+        let trailer_start = buffer.len().checked_sub(10).ok_or_else(invalid)?;
+        let trailer: [u8; 10] = buffer[trailer_start..].try_into().map_err(|_| invalid())?;
+        let footer_len = read_footer_length(trailer)
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        let footer_start = trailer_start.checked_sub(footer_len).ok_or_else(invalid)?;
+        let footer = root_as_footer(&buffer[footer_start..trailer_start])
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        let schema = Arc::new(fb_to_schema(footer.schema().ok_or_else(invalid)?));
+        
+        let mut decoder = FileDecoder::new(schema.clone(), footer.version());
+        for block in footer.dictionaries().iter().flatten() {
+            let data = block_data(&buffer, block)?;
+            decoder.read_dictionary(block, &data)
+                .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        }
+        
+        let mut batches = Vec::new();
+        for block in footer.recordBatches().iter().flatten() {
+            let data = block_data(&buffer, block)?;
+            if let Some(batch) = decoder.read_record_batch(block, &data)
+                .map_err(|e| DataHubError::ArrowError(e.to_string()))? {
+                batches.push(batch);
+            }
+        }
+        
+        let version = schema_version(&schema)?;
+        if is_flat_layout(&schema) {
+            info!("Converting flat layout to nested layout in memory");
+            let data = flat_batches_to_stock_data(batches)?;
+            return Ok(vec![stock_data_to_record_batch(&data)?]);
+        }
+        
+        if version < SCHEMA_VERSION {
+            info!("Upgrading stock data schema from v{} to v{} in memory", version, SCHEMA_VERSION);
+        }
+        batches.into_iter().map(|batch| migrate_batch(batch, version)).collect()
+    }
+
+    // 将股票数据保存到Arrow文件
+    pub fn save_stock_data_to_arrow(data: &[StockData], path: &str) -> Result<()> {
+        save_stock_data_to_arrow_with_layout(data, path, TableLayout::Nested)
+    }
+    
+    /// 按指定布局将股票数据保存到Arrow文件
+    pub fn save_stock_data_to_arrow_with_layout(data: &[StockData], path: &str, layout: TableLayout) -> Result<()> {
+        save_stock_data_to_arrow_with_options(data, path, &ArrowOptions::new().with_layout(layout))
+    }
+    
+    /// 将股票数据保存到Arrow文件，并保留最近 `backups` 个旧版本（`stock.arrow.bak1` 为最新）
+    pub fn save_stock_data_to_arrow_with_backups(data: &[StockData], path: &str, backups: usize) -> Result<()> {
+        save_stock_data_to_arrow_with_options(data, path, &ArrowOptions::new().with_backups(backups))
+    }
+    
+    /// 按写入选项保存Arrow文件
+    ///
+    /// 先写入临时文件并重新读取校验，通过后再替换原文件。
+    pub fn save_stock_data_to_arrow_with_options(data: &[StockData], path: &str, options: &ArrowOptions) -> Result<()> {
+        let layout = options.layout;
+        let write_options = options.compression.write_options()?;
+        
+        // 打印保存的数据信息
+        info!("Saving {} stocks to {}", data.len(), path);
+        for stock in data {
+            info!("  - {} ({}) - {}: {} daily records", 
+                     stock.name, stock.symbol, stock.exchange, stock.daily.len());
+        }
+        
+        let batch = match layout {
+            TableLayout::Nested => stock_data_to_record_batch(data)?,
+            TableLayout::Flat => stock_data_to_flat_record_batch(data)?,
+        };
+        
+        fs_utils::write_atomic(
+            Path::new(path),
+            options.backups,
+            |file| write_batch_with_options(&batch, file, write_options),
+            |tmp| {
+                let stocks = validate_arrow_file(tmp)?;
+                if stocks != data.len() {
+                    return Err(DataHubError::ArrowError(format!(
+                        "Validation failed for {}: expected {} stocks, read back {}", path, data.len(), stocks
+                    )));
+                }
+                Ok(())
+            },
+        )
+    }
+    
+    /// 完整读取Arrow文件以校验其可用，返回股票数量
+    pub fn validate_arrow_file(path: impl AsRef<Path>) -> Result<usize> {
+        read_stock_data_from_arrow(path).map(|data| data.len())
+    }
+    
+    /// 将股票数据编码为内存中的Arrow文件，与 `read_stock_data_from_memory` 对应
+    pub fn stock_data_to_arrow_bytes(data: &[StockData]) -> Result<Vec<u8>> {
+        let batch = stock_data_to_record_batch(data)?;
+        let mut buffer = Vec::new();
+        write_batch(&batch, &mut buffer)?;
+        Ok(buffer)
+    }
+    
+    fn write_batch<W: Write>(batch: &RecordBatch, writer: W) -> Result<()> {
+        // 使用默认选项，不启用压缩，确保与JavaScript SDK兼容
+        write_batch_with_options(batch, writer, IpcWriteOptions::default())
+    }
+    
+    fn write_batch_with_options<W: Write>(batch: &RecordBatch, writer: W, options: IpcWriteOptions) -> Result<()> {
+        let mut writer = FileWriter::try_new_with_options(writer, &batch.schema(), options)
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        writer.write(batch)
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        writer.finish()
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        Ok(())
+    }
+
+    /// CSV 导出选项
+    #[derive(Debug, Clone)]
+    pub struct CsvOptions {
+        pub delimiter: u8,
+        /// chrono 格式字符串，如 `%Y-%m-%d`、`%Y%m%d`
+        pub date_format: String,
+        /// 写入 UTF-8 BOM，Excel 需要它才能正确显示中文名称
+        pub bom: bool,
+    }
+    
+    impl Default for CsvOptions {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+    
+    impl CsvOptions {
+        pub fn new() -> Self {
+            Self {
+                delimiter: b',',
+                date_format: "%Y-%m-%d".to_string(),
+                bom: false,
+            }
+        }
+        
+        pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+            self.delimiter = delimiter;
+            self
+        }
+        
+        pub fn with_date_format(mut self, date_format: &str) -> Self {
+            self.date_format = date_format.to_string();
+            self
+        }
+        
+        pub fn with_bom(mut self, bom: bool) -> Self {
+            self.bom = bom;
+            self
+        }
+    }
+    
+    // CSV 使用的记录批次：日期为 Date32 以便按格式输出，价格为三位小数的 Decimal128
+    fn csv_record_batch<'a>(rows: impl Iterator<Item = (&'a StockData, &'a DailyData)>) -> Result<RecordBatch> {
+        let mut exchange_builder = StringBuilder::new();
+        let mut symbol_builder = StringBuilder::new();
+        let mut name_builder = StringBuilder::new();
+        let mut dates = Vec::new();
+        let mut prices: [Vec<i128>; 6] = Default::default();
+        let mut volumes = Vec::new();
+        let mut amounts = Vec::new();
+        let mut change_pcts = Vec::new();
+        let mut turnover_rates = Vec::new();
+        
+        for (stock, daily) in rows {
+            exchange_builder.append_value(stock.exchange.code());
+            symbol_builder.append_value(&stock.symbol);
+            name_builder.append_value(stock.name_as_of(daily.date));
+            dates.push(daily.date.to_date32());
+            for (values, price) in prices.iter_mut().zip([daily.open, daily.high, daily.low, daily.close, daily.pre_close, daily.change]) {
+                values.push(price.as_milli() as i128);
+            }
+            volumes.push(daily.volume);
+            amounts.push(daily.amount);
+            change_pcts.push(daily.change_pct);
+            turnover_rates.push(daily.turnover_rate);
+        }
+        
+        let price_type = DataType::Decimal128(12, Price::DECIMALS as i8);
+        let mut fields = vec![
+            Field::new("exchange", DataType::Utf8, false),
+            Field::new("symbol", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("date", DataType::Date32, false),
+        ];
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(exchange_builder.finish()),
+            Arc::new(symbol_builder.finish()),
+            Arc::new(name_builder.finish()),
+            Arc::new(Date32Array::from(dates)),
+        ];
+        for (name, values) in ["open", "high", "low", "close", "pre_close", "change"].into_iter().zip(prices) {
+            let array = Decimal128Array::from(values)
+                .with_data_type(price_type.clone());
+            fields.push(Field::new(name, price_type.clone(), false));
+            columns.push(Arc::new(array));
+        }
+        fields.push(Field::new("volume", DataType::Int64, false));
+        columns.push(Arc::new(Int64Array::from(volumes)));
+        fields.push(Field::new("amount", DataType::Int64, false));
+        columns.push(Arc::new(Int64Array::from(amounts)));
+        fields.push(Field::new("change_pct", DataType::Float32, false));
+        columns.push(Arc::new(Float32Array::from(change_pcts)));
+        fields.push(Field::new("turnover_rate", DataType::Float32, false));
+        columns.push(Arc::new(Float32Array::from(turnover_rates)));
+        
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))
+    }
+    
+    fn write_csv(batch: &RecordBatch, path: &Path, options: &CsvOptions) -> Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        if options.bom {
+            file.write_all(b"\xEF\xBB\xBF")?;
+        }
+        
+        let mut writer = arrow::csv::WriterBuilder::new()
+            .with_header(true)
+            .with_delimiter(options.delimiter)
+            .with_date_format(options.date_format.clone())
+            .build(file);
+        writer.write(batch)
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        writer.into_inner().flush()?;
+        
+        Ok(())
+    }
+    
+    /// 每只股票导出一个 CSV 文件（如 `600519.SH.csv`），按日线原有顺序输出，返回写入的文件数
+    pub fn export_csv_by_symbol(data: &[StockData], dir: &str, options: &CsvOptions) -> Result<usize> {
+        std::fs::create_dir_all(dir)?;
+        
+        for stock in data {
+            let batch = csv_record_batch(stock.daily.iter().map(|daily| (stock, daily)))?;
+            write_csv(&batch, &Path::new(dir).join(format!("{}.csv", stock.security_id())), options)?;
+        }
+        
+        info!("Exported {} CSV files to {}", data.len(), dir);
+        Ok(data.len())
+    }
+    
+    /// 每个交易日导出一个全市场截面 CSV 文件（如 `2025-05-16.csv`），返回写入的文件数
+    pub fn export_csv_by_date(data: &[StockData], dir: &str, options: &CsvOptions) -> Result<usize> {
+        std::fs::create_dir_all(dir)?;
+        
+        let mut by_date: BTreeMap<TradeDate, Vec<(&StockData, &DailyData)>> = BTreeMap::new();
+        for stock in data {
+            for daily in &stock.daily {
+                by_date.entry(daily.date).or_default().push((stock, daily));
+            }
+        }
+        
+        for (date, rows) in &by_date {
+            let batch = csv_record_batch(rows.iter().copied())?;
+            write_csv(&batch, &Path::new(dir).join(format!("{}.csv", date)), options)?;
+        }
+        
+        info!("Exported {} CSV files to {}", by_date.len(), dir);
+        Ok(by_date.len())
+    }
+
+    /// 解析 Parquet 压缩方式：none、snappy、gzip、zstd、lz4
+    #[cfg(feature = "parquet")]
+    pub fn parse_parquet_compression(name: &str) -> Result<Compression> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" | "uncompressed" => Ok(Compression::UNCOMPRESSED),
+            "snappy" => Ok(Compression::SNAPPY),
+            "gzip" => Ok(Compression::GZIP(GzipLevel::default())),
+            "zstd" => Ok(Compression::ZSTD(ZstdLevel::default())),
+            "lz4" => Ok(Compression::LZ4_RAW),
+            _ => Err(DataHubError::DataError(format!("Unknown Parquet compression: {}", name))),
+        }
+    }
+    
+    /// 将股票数据保存为 Parquet 文件
+    #[cfg(feature = "parquet")]
+    pub fn save_stock_data_to_parquet(data: &[StockData], path: &str, layout: TableLayout, compression: Compression) -> Result<()> {
+        info!("Saving {} stocks to {} ({:?} layout, {:?})", data.len(), path, layout, compression);
+        
+        let batch = match layout {
+            TableLayout::Nested => stock_data_to_record_batch(data)?,
+            TableLayout::Flat => stock_data_to_flat_record_batch(data)?,
+        };
+        let properties = WriterProperties::builder()
+            .set_compression(compression)
+            .build();
+        
+        let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), Some(properties))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        
+        Ok(())
+    }
+    
+    /// 从 Parquet 文件读取股票数据，自动识别嵌套和扁平布局
+    #[cfg(feature = "parquet")]
+    pub fn read_stock_data_from_parquet(path: &str) -> Result<Vec<StockData>> {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+        let schema = builder.schema().clone();
+        let reader = builder.build()?;
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        let version = schema_version(&schema)?;
+        if is_flat_layout(&schema) {
+            return flat_batches_to_stock_data(batches);
+        }
+        
+        let mut result = Vec::new();
+        for batch in batches {
+            let batch = migrate_batch(batch, version)?;
+            record_batch_to_stock_data(&batch, &mut result)?;
+        }
+        
+        Ok(result)
+    }
+
+    // 从内存中读取Arrow数据
+    pub fn read_stock_data_from_memory(data: &[u8]) -> Result<Vec<StockData>> {
+        let reader = FileReader::try_new(
+            Cursor::new(data), 
+            None
+        ).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        read_stock_data_from_file_reader(reader)
+    }
+    
+    /// 从任意输入读取股票数据，根据开头的魔数自动识别 IPC 文件格式和流格式
+    ///
+    /// 文件格式需要随机访问，会先完整读入内存；流格式逐批解码。
+    pub fn read_stock_data_from_reader<R: Read>(mut reader: R) -> Result<Vec<StockData>> {
+        let mut header = Vec::with_capacity(ARROW_FILE_MAGIC.len());
+        reader.by_ref().take(ARROW_FILE_MAGIC.len() as u64).read_to_end(&mut header)?;
+        let is_file = header == ARROW_FILE_MAGIC;
+        let mut reader = Cursor::new(header).chain(reader);
+        
+        if is_file {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            read_stock_data_from_memory(&bytes)
+        } else {
+            read_stock_data_from_stream(reader)
+        }
+    }
+    
+    /// 读取 IPC 流格式的股票数据
+    pub fn read_stock_data_from_stream<R: Read>(reader: R) -> Result<Vec<StockData>> {
+        let mut result = Vec::new();
+        for stocks in StockDataStreamReader::try_new(reader)? {
+            result.extend(stocks?);
+        }
+        Ok(result)
+    }
+    
+    /// 以 IPC 流格式写入股票数据
+    ///
+    /// 每 `STREAM_BATCH_STOCKS` 只股票写入一个记录批次，读取端无需等待全部数据即可逐批处理。
+    pub fn write_stock_data_to_stream<W: Write>(data: &[StockData], writer: W, options: &ArrowOptions) -> Result<()> {
+        let schema = match options.layout {
+            TableLayout::Nested => stock_schema(),
+            TableLayout::Flat => flat_schema(),
+        };
+        let mut writer = StreamWriter::try_new_with_options(writer, &schema, options.compression.write_options()?)
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        for chunk in data.chunks(STREAM_BATCH_STOCKS) {
+            let batch = match options.layout {
+                TableLayout::Nested => stock_data_to_record_batch(chunk)?,
+                TableLayout::Flat => stock_data_to_flat_record_batch(chunk)?,
+            };
+            writer.write(&batch)
+                .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        }
+        writer.finish()
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        Ok(())
+    }
+    
+    /// 逐批读取 IPC 流中的股票数据，每次返回一个记录批次中的股票
+    ///
+    /// 扁平布局中同一只股票可能跨越多个批次，因此会读完整个流后一次返回。
+    pub struct StockDataStreamReader<R: Read> {
+        reader: StreamReader<BufReader<R>>,
+        version: u32,
+        flat: bool,
+        finished: bool,
+    }
+    
+    impl<R: Read> StockDataStreamReader<R> {
+        pub fn try_new(reader: R) -> Result<Self> {
+            let reader = StreamReader::try_new_buffered(reader, None)
+                .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+            let schema = reader.schema();
+            let flat = is_flat_layout(&schema);
+            let version = schema_version(&schema)?;
+            if !flat && version < SCHEMA_VERSION {
+                info!("Upgrading stock data schema from v{} to v{}", version, SCHEMA_VERSION);
+            }
+            
+            Ok(Self { reader, version, flat, finished: false })
+        }
+    }
+    
+    impl<R: Read> Iterator for StockDataStreamReader<R> {
+        type Item = Result<Vec<StockData>>;
+        
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.finished {
+                return None;
+            }
+            
+            if self.flat {
+                self.finished = true;
+                let batches = (&mut self.reader)
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .map_err(|e| DataHubError::ArrowError(e.to_string()));
+                return Some(batches.and_then(flat_batches_to_stock_data));
+            }
+            
+            let batch = match self.reader.next()? {
+                Ok(batch) => batch,
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(DataHubError::ArrowError(e.to_string())));
+                }
+            };
+            let mut stocks = Vec::new();
+            Some(migrate_batch(batch, self.version)
+                .and_then(|batch| record_batch_to_stock_data(&batch, &mut stocks))
+                .map(|_| stocks))
+        }
+    }
+    
+    // 将旧版本的Arrow文件升级为当前 Schema 版本，返回迁移的股票数量
+    pub fn migrate_arrow_file(input: &str, output: &str, backups: usize) -> Result<usize> {
+        let data = read_stock_data_from_arrow(input)?;
+        save_stock_data_to_arrow_with_backups(&data, output, backups)?;
+        Ok(data.len())
+    }
+    
+    /// 获取 Schema 版本
+    ///
+    /// 未写入版本号的旧文件根据列结构推断版本。
+    pub fn schema_version(schema: &Schema) -> Result<u32> {
+        if let Some(version) = schema.metadata().get(SCHEMA_VERSION_KEY) {
+            let version = version.parse::<u32>()
+                .map_err(|_| DataHubError::ArrowError(format!("Invalid schema version: {}", version)))?;
+            if version > SCHEMA_VERSION {
+                return Err(DataHubError::ArrowError(format!(
+                    "Schema version {} is newer than supported version {}, please upgrade egostrategy_datahub",
+                    version, SCHEMA_VERSION
+                )));
+            }
+            return Ok(version);
+        }
+        
+        let daily_fields = match schema.field_with_name("daily").map(|f| f.data_type()) {
+            Ok(DataType::List(item)) => match item.data_type() {
+                DataType::Struct(fields) => fields.clone(),
+                _ => return Ok(SCHEMA_VERSION),
+            },
+            _ => return Ok(SCHEMA_VERSION),
+        };
+        if daily_fields.find("date").is_some_and(|(_, f)| f.data_type() == &DataType::Date32) {
+            Ok(5)
+        } else if schema.column_with_name("name_history").is_some() {
+            Ok(4)
+        } else if daily_fields.find("pre_close").is_some() {
+            Ok(3)
+        } else if daily_fields.find("close").is_some_and(|(_, f)| f.data_type() == &DataType::Int32) {
+            Ok(2)
+        } else {
+            Ok(1)
+        }
+    }
+    
+    // Schema 迁移：每一步将记录批次从 from 版本升级到 from + 1 版本
+    struct Migration {
+        from: u32,
+        apply: fn(RecordBatch) -> Result<RecordBatch>,
+    }
+    
+    const MIGRATIONS: &[Migration] = &[
+        Migration { from: 1, apply: migrate_v1_fixed_point_prices },
+        Migration { from: 2, apply: migrate_v2_change_columns },
+        Migration { from: 3, apply: migrate_v3_name_history },
+        Migration { from: 4, apply: migrate_v4_date32 },
+    ];
+    
+    /// 将记录批次从指定版本逐步升级到当前版本
+    pub fn migrate_batch(mut batch: RecordBatch, version: u32) -> Result<RecordBatch> {
+        for migration in MIGRATIONS.iter().filter(|m| m.from >= version) {
+            batch = (migration.apply)(batch)?;
+        }
+        Ok(batch)
+    }
+    
+    // 替换记录批次中的一列，不存在时追加
+    fn replace_column(batch: &RecordBatch, field: Field, column: ArrayRef) -> Result<RecordBatch> {
+        let schema = batch.schema();
+        let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+        let mut columns = batch.columns().to_vec();
+        
+        match schema.index_of(field.name()) {
+            Ok(index) => {
+                fields[index] = field;
+                columns[index] = column;
+            }
+            Err(_) => {
+                fields.push(field);
+                columns.push(column);
+            }
+        }
+        
+        RecordBatch::try_new(Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone())), columns)
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))
+    }
+    
+    // 用新的字段和列重建日线列表，保留原有的偏移量和空值
+    fn map_daily_struct(
+        batch: RecordBatch,
+        map: impl Fn(&StructArray, &OffsetBuffer<i32>) -> Result<Vec<(Field, ArrayRef)>>,
+    ) -> Result<RecordBatch> {
+        let Some(daily_array) = batch.column_by_name("daily") else {
+            return Ok(batch);
+        };
+        let daily_array = daily_array.as_any().downcast_ref::<ListArray>()
+            .ok_or_else(|| DataHubError::ArrowError("Failed to downcast daily column".to_string()))?;
+        let daily_struct = daily_array.values().as_any().downcast_ref::<StructArray>()
+            .ok_or_else(|| DataHubError::ArrowError("Failed to downcast daily struct".to_string()))?;
+        
+        let (fields, columns): (Vec<Field>, Vec<ArrayRef>) = map(daily_struct, daily_array.offsets())?.into_iter().unzip();
+        let fields = Fields::from(fields);
+        let struct_array = StructArray::try_new(fields.clone(), columns, daily_struct.nulls().cloned())
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        let item = Field::new("item", DataType::Struct(fields), daily_struct.is_nullable());
+        let list_array = ListArray::try_new(
+            Arc::new(item),
+            daily_array.offsets().clone(),
+            Arc::new(struct_array),
+            daily_array.nulls().cloned(),
+        ).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        let field = Field::new("daily", list_array.data_type().clone(), true);
+        replace_column(&batch, field, Arc::new(list_array))
+    }
+    
+    // v1 -> v2：f32 价格转换为定点价格
+    fn migrate_v1_fixed_point_prices(batch: RecordBatch) -> Result<RecordBatch> {
+        map_daily_struct(batch, |daily_struct, _| {
+            daily_struct.fields().iter().zip(daily_struct.columns()).map(|(field, column)| {
+                if matches!(field.name().as_str(), "open" | "high" | "low" | "close") {
+                    let prices = price_values(column, field.name())?;
+                    let array: ArrayRef = Arc::new(Int32Array::from_iter_values(prices.iter().map(|p| p.as_milli())));
+                    Ok((price_field(field.name()), array))
+                } else {
+                    Ok((field.as_ref().clone(), column.clone()))
+                }
+            }).collect()
+        })
+    }
+    
+    // v2 -> v3：根据前一交易日收盘价补全前收盘价、涨跌额和涨跌幅，换手率置0
+    fn migrate_v2_change_columns(batch: RecordBatch) -> Result<RecordBatch> {
+        map_daily_struct(batch, |daily_struct, offsets| {
+            let close = price_column(daily_struct, "close")?;
+            let mut daily: Vec<DailyData> = close.iter()
+                .map(|&close| DailyData { close, ..Default::default() })
+                .collect();
+            for window in offsets.windows(2) {
+                fill_pre_close(&mut daily[window[0] as usize..window[1] as usize]);
+            }
+            
+            let mut columns: Vec<(Field, ArrayRef)> = daily_struct.fields().iter()
+                .zip(daily_struct.columns())
+                .map(|(field, column)| (field.as_ref().clone(), column.clone()))
+                .collect();
+            columns.push((price_field("pre_close"), Arc::new(Int32Array::from_iter_values(daily.iter().map(|d| d.pre_close.as_milli())))));
+            columns.push((price_field("change"), Arc::new(Int32Array::from_iter_values(daily.iter().map(|d| d.change.as_milli())))));
+            columns.push((Field::new("change_pct", DataType::Float32, false), Arc::new(Float32Array::from_iter_values(daily.iter().map(|d| d.change_pct)))));
+            columns.push((Field::new("turnover_rate", DataType::Float32, false), Arc::new(Float32Array::from(vec![0.0; daily.len()]))));
+            Ok(columns)
+        })
+    }
+    
+    // v3 -> v4：追加空的名称历史列，生效日期为 v4 的 Int32 格式
+    fn migrate_v3_name_history(batch: RecordBatch) -> Result<RecordBatch> {
+        let fields = Fields::from(vec![
+            Field::new("effective_from", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]);
+        let values = StructArray::try_new(
+            fields.clone(),
+            vec![Arc::new(Int32Array::from(Vec::<i32>::new())), Arc::new(StringArray::from(Vec::<&str>::new()))],
+            None,
+        ).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        let list_array = ListArray::try_new(
+            Arc::new(Field::new("item", DataType::Struct(fields), false)),
+            OffsetBuffer::new_zeroed(batch.num_rows()),
+            Arc::new(values),
+            None,
+        ).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        let field = Field::new("name_history", list_array.data_type().clone(), true);
+        replace_column(&batch, field, Arc::new(list_array))
+    }
+    
+    // 日期数组转换为 Date32 或 YYYYMMDD 形式的 Int32
+    fn convert_dates(column: &ArrayRef, data_type: &DataType) -> Result<ArrayRef> {
+        let dates = date_values(column)?;
+        match data_type {
+            DataType::Date32 => Ok(Arc::new(Date32Array::from_iter_values(dates.iter().map(|d| d.to_date32())))),
+            DataType::Int32 => Ok(Arc::new(Int32Array::from_iter_values(dates.iter().map(|d| d.to_yyyymmdd())))),
+            _ => Err(DataHubError::ArrowError(format!("Unsupported type for date column: {}", data_type))),
+        }
+    }
+    
+    // 将日线日期和名称历史的生效日期转换为指定类型
+    fn convert_date_columns(batch: RecordBatch, data_type: &DataType) -> Result<RecordBatch> {
+        let batch = map_daily_struct(batch, |daily_struct, _| {
+            daily_struct.fields().iter().zip(daily_struct.columns()).map(|(field, column)| {
+                if field.name() == "date" {
+                    Ok((Field::new("date", data_type.clone(), false), convert_dates(column, data_type)?))
+                } else {
+                    Ok((field.as_ref().clone(), column.clone()))
+                }
+            }).collect()
+        })?;
+        
+        let Some(history) = batch.column_by_name("name_history") else {
+            return Ok(batch);
+        };
+        let history = history.as_any().downcast_ref::<ListArray>()
+            .ok_or_else(|| DataHubError::ArrowError("Failed to downcast name_history column".to_string()))?;
+        let entries = history.values().as_any().downcast_ref::<StructArray>()
+            .ok_or_else(|| DataHubError::ArrowError("Failed to downcast name_history struct".to_string()))?;
+        let (fields, columns): (Vec<Field>, Vec<ArrayRef>) = entries.fields().iter().zip(entries.columns())
+            .map(|(field, column)| match field.name().as_str() {
+                "effective_from" => Ok((Field::new("effective_from", data_type.clone(), false), convert_dates(column, data_type)?)),
+                _ => Ok((field.as_ref().clone(), column.clone())),
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        
+        let fields = Fields::from(fields);
+        let values = StructArray::try_new(fields.clone(), columns, entries.nulls().cloned())
+            .map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        let list_array = ListArray::try_new(
+            Arc::new(Field::new("item", DataType::Struct(fields), false)),
+            history.offsets().clone(),
+            Arc::new(values),
+            history.nulls().cloned(),
+        ).map_err(|e| DataHubError::ArrowError(e.to_string()))?;
+        
+        let field = Field::new("name_history", list_array.data_type().clone(), true);
+        replace_column(&batch, field, Arc::new(list_array))
+    }
+    
+    // v4 -> v5：日线日期和名称历史的生效日期由 YYYYMMDD 形式的 Int32 改为 Date32
+    fn migrate_v4_date32(batch: RecordBatch) -> Result<RecordBatch> {
+        convert_date_columns(batch, &DataType::Date32)
+    }
+    
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        
+        #[test]
+        fn block_data_rejects_out_of_range_blocks() {
+            let buffer = Buffer::from_vec(vec![0u8; 64]);
+            assert_eq!(block_data(&buffer, &Block::new(8, 16, 40)).unwrap().len(), 56);
+            
+            for block in [
+                Block::new(-8, 16, 8),
+                Block::new(8, -16, 8),
+                Block::new(8, 16, -8),
+                Block::new(8, 16, 48),
+                Block::new(i64::MAX, 16, 8),
+                Block::new(8, 16, i64::MAX),
+            ] {
+                assert!(block_data(&buffer, &block).is_err(), "{:?} was accepted", block);
+            }
+        }
+        
+        fn stock(symbol: &str, name: &str, dates: &[&str], history: &[(&str, &str)]) -> StockData {
+            StockData {
+                exchange: Exchange::Sse,
+                symbol: symbol.to_string(),
+                name: name.to_string(),
+                daily: dates.iter()
+                    .map(|d| DailyData { date: d.parse().unwrap(), close: Price::from_f64(10.0), ..DailyData::default() })
+                    .collect(),
+                name_history: history.iter()
+                    .map(|(d, n)| NameChange { effective_from: d.parse().unwrap(), name: n.to_string() })
+                    .collect(),
+            }
+        }
+        
+        #[test]
+        fn migrates_v4_int32_dates_to_date32() {
+            let data = vec![stock("600000", "New", &["2025-05-06", "2025-05-02"], &[("2025-05-01", "Old"), ("2025-05-04", "New")])];
+            let batch = stock_data_to_record_batch(&data).unwrap();
+            let daily_type = |batch: &RecordBatch| match batch.schema().field_with_name("daily").unwrap().data_type() {
+                DataType::List(item) => item.data_type().clone(),
+                _ => unreachable!(),
+            };
+            assert!(matches!(daily_type(&batch), DataType::Struct(f) if f.find("date").unwrap().1.data_type() == &DataType::Date32));
+            
+            // 按 v4 格式写入 YYYYMMDD 形式的 Int32 日期
+            let v4 = convert_date_columns(batch, &DataType::Int32).unwrap();
+            let metadata = HashMap::from([(SCHEMA_VERSION_KEY.to_string(), "4".to_string())]);
+            let schema = Arc::new(v4.schema().as_ref().clone().with_metadata(metadata));
+            let v4 = RecordBatch::try_new(schema, v4.columns().to_vec()).unwrap();
+            let daily = v4.column_by_name("daily").unwrap().as_any().downcast_ref::<ListArray>().unwrap();
+            let dates = daily.values().as_any().downcast_ref::<StructArray>().unwrap().column_by_name("date").unwrap();
+            assert_eq!(dates.data_type(), &DataType::Int32);
+            assert_eq!(dates.as_any().downcast_ref::<Int32Array>().unwrap().value(0), 20250506);
+            
+            let mut bytes = Vec::new();
+            write_batch(&v4, &mut bytes).unwrap();
+            let loaded = read_stock_data_from_memory(&bytes).unwrap();
+            assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&data).unwrap());
+            
+            let migrated = migrate_batch(v4, 4).unwrap();
+            assert!(matches!(daily_type(&migrated), DataType::Struct(f) if f.find("date").unwrap().1.data_type() == &DataType::Date32));
+            assert_eq!(migrated.column_by_name("name_history").unwrap().data_type(), stock_schema().field_with_name("name_history").unwrap().data_type());
+        }
+        
+        // 按 v1 格式构造的记录批次：没有元数据和交易所列，f32 价格，Int32 日期
+        fn v1_batch() -> RecordBatch {
+            let fields = Fields::from(vec![
+                Field::new("date", DataType::Int32, false),
+                Field::new("open", DataType::Float32, false),
+                Field::new("high", DataType::Float32, false),
+                Field::new("low", DataType::Float32, false),
+                Field::new("close", DataType::Float32, false),
+                Field::new("volume", DataType::Int64, false),
+                Field::new("amount", DataType::Int64, false),
+            ]);
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(Int32Array::from(vec![20250103, 20250102])),
+                Arc::new(Float32Array::from(vec![10.2, 9.9])),
+                Arc::new(Float32Array::from(vec![11.3, 10.1])),
+                Arc::new(Float32Array::from(vec![10.1, 9.8])),
+                Arc::new(Float32Array::from(vec![11.0, 10.0])),
+                Arc::new(Int64Array::from(vec![2000, 1000])),
+                Arc::new(Int64Array::from(vec![22000, 10000])),
+            ];
+            let daily = StructArray::try_new(fields.clone(), columns, None).unwrap();
+            let daily = ListArray::try_new(
+                Arc::new(Field::new("item", DataType::Struct(fields), false)),
+                OffsetBuffer::from_lengths([2]),
+                Arc::new(daily),
+                None,
+            ).unwrap();
+            let schema = Schema::new(vec![
+                Field::new("symbol", DataType::Utf8, false),
+                Field::new("name", DataType::Utf8, false),
+                Field::new("daily", daily.data_type().clone(), true),
+            ]);
+            RecordBatch::try_new(Arc::new(schema), vec![
+                Arc::new(StringArray::from(vec!["600000"])),
+                Arc::new(StringArray::from(vec!["A"])),
+                Arc::new(daily),
+            ]).unwrap()
+        }
+        
+        #[test]
+        fn migrates_v1_file_to_current_schema() {
+            let v1 = v1_batch();
+            assert_eq!(schema_version(&v1.schema()).unwrap(), 1);
+            
+            // 逐步迁移，每一步的结果都能被推断为下一个版本
+            let mut batch = v1.clone();
+            for migration in MIGRATIONS {
+                batch = (migration.apply)(batch).unwrap();
+                if migration.from < 4 {
+                    assert_eq!(schema_version(&batch.schema()).unwrap(), migration.from + 1);
+                }
+            }
+            let current = stock_schema();
+            for name in ["daily", "name_history"] {
+                assert_eq!(batch.schema().field_with_name(name).unwrap().data_type(), current.field_with_name(name).unwrap().data_type());
+            }
+            
+            let mut bytes = Vec::new();
+            write_batch(&v1, &mut bytes).unwrap();
+            let loaded = read_stock_data_from_memory(&bytes).unwrap();
+            assert_eq!(loaded.len(), 1);
+            let stock = &loaded[0];
+            assert_eq!((stock.exchange, stock.symbol.as_str(), stock.name.as_str()), (Exchange::Sse, "600000", "A"));
+            assert!(stock.name_history.is_empty());
+            
+            let latest = &stock.daily[0];
+            assert_eq!(latest.date, "2025-01-03".parse().unwrap());
+            assert_eq!((latest.open, latest.high, latest.close), (Price::from_milli(10_200), Price::from_milli(11_300), Price::from_milli(11_000)));
+            assert_eq!((latest.volume, latest.amount), (2000, 22000));
+            assert_eq!((latest.pre_close, latest.change), (Price::from_milli(10_000), Price::from_milli(1_000)));
+            assert!((latest.change_pct - 10.0).abs() < 1e-4);
+            assert!(stock.daily[1].pre_close.is_zero());
+        }
+        
+        #[test]
+        fn rejects_files_from_newer_schema_versions() {
+            let data = vec![stock("600000", "A", &["2025-01-02"], &[])];
+            let future = |batch: RecordBatch| {
+                let mut metadata = batch.schema().metadata().clone();
+                metadata.insert(SCHEMA_VERSION_KEY.to_string(), (SCHEMA_VERSION + 1).to_string());
+                let schema = Arc::new(batch.schema().as_ref().clone().with_metadata(metadata));
+                RecordBatch::try_new(schema, batch.columns().to_vec()).unwrap()
+            };
+            
+            for batch in [stock_data_to_record_batch(&data).unwrap(), stock_data_to_flat_record_batch(&data).unwrap()] {
+                let flat = is_flat_layout(&batch.schema());
+                let mut bytes = Vec::new();
+                write_batch(&batch, &mut bytes).unwrap();
+                assert_eq!(read_stock_data_from_memory(&bytes).unwrap().len(), 1, "flat: {}", flat);
+                
+                let batch = future(batch);
+                let mut bytes = Vec::new();
+                write_batch(&batch, &mut bytes).unwrap();
+                assert!(read_stock_data_from_memory(&bytes).is_err(), "flat: {}", flat);
+                
+                let mut stream = Vec::new();
+                let mut writer = StreamWriter::try_new(&mut stream, &batch.schema()).unwrap();
+                writer.write(&batch).unwrap();
+                writer.finish().unwrap();
+                drop(writer);
+                assert!(read_stock_data_from_stream(stream.as_slice()).is_err(), "flat: {}", flat);
+            }
+        }
+        
+        #[test]
+        fn csv_export_by_symbol_and_by_date() {
+            let dir = std::env::temp_dir().join(format!("egostrategy_csv_{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            let data = vec![
+                stock("600000", "New", &["2025-05-06", "2025-05-02"], &[("2025-05-01", "Old"), ("2025-05-04", "New")]),
+                stock("600001", "B", &["2025-05-06"], &[]),
+            ];
+            
+            let by_symbol = dir.join("by_symbol");
+            let options = CsvOptions::new().with_delimiter(b';').with_date_format("%Y%m%d").with_bom(true);
+            assert_eq!(export_csv_by_symbol(&data, by_symbol.to_str().unwrap(), &options).unwrap(), 2);
+            let bytes = std::fs::read(by_symbol.join("600000.SH.csv")).unwrap();
+            let text = std::str::from_utf8(bytes.strip_prefix(b"\xEF\xBB\xBF").expect("missing BOM")).unwrap();
+            let lines: Vec<&str> = text.lines().collect();
+            assert_eq!(lines[0], "exchange;symbol;name;date;open;high;low;close;pre_close;change;volume;amount;change_pct;turnover_rate");
+            // 按日线原有顺序输出，名称为当日有效的名称
+            assert_eq!(lines[1], "SSE;600000;New;20250506;0.000;0.000;0.000;10.000;0.000;0.000;0;0;0.0;0.0");
+            assert!(lines[2].starts_with("SSE;600000;Old;20250502;"), "{}", lines[2]);
+            assert_eq!(lines.len(), 3);
+            
+            let by_date = dir.join("by_date");
+            assert_eq!(export_csv_by_date(&data, by_date.to_str().unwrap(), &CsvOptions::default()).unwrap(), 2);
+            let text = std::fs::read_to_string(by_date.join("2025-05-06.csv")).unwrap();
+            assert!(text.starts_with("exchange,symbol,"));
+            let lines: Vec<&str> = text.lines().collect();
+            assert_eq!(lines.len(), 3);
+            assert!(lines[1].starts_with("SSE,600000,New,2025-05-06,"), "{}", lines[1]);
+            assert!(lines[2].starts_with("SSE,600001,B,2025-05-06,"), "{}", lines[2]);
+            assert_eq!(std::fs::read_to_string(by_date.join("2025-05-02.csv")).unwrap().lines().count(), 2);
+            
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        
+        #[cfg(feature = "parquet")]
+        #[test]
+        fn parquet_round_trip_in_both_layouts() {
+            let dir = std::env::temp_dir().join(format!("egostrategy_parquet_{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            let data = vec![
+                stock("600000", "New", &["2025-05-06", "2025-05-02"], &[("2025-05-01", "Old"), ("2025-05-04", "New")]),
+                stock("600001", "Listed", &[], &[]),
+            ];
+            
+            for layout in [TableLayout::Nested, TableLayout::Flat] {
+                for compression in ["none", "snappy", "zstd"] {
+                    let path = dir.join(format!("stock_{:?}_{}.parquet", layout, compression));
+                    let path = path.to_str().unwrap();
+                    save_stock_data_to_parquet(&data, path, layout, parse_parquet_compression(compression).unwrap()).unwrap();
+                    
+                    let mut loaded = read_stock_data_from_parquet(path).unwrap();
+                    loaded.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+                    assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&data).unwrap(), "{:?} {}", layout, compression);
+                }
+            }
+            assert!(parse_parquet_compression("brotli9").is_err());
+            
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+        
+        #[test]
+        fn flat_layout_keeps_stocks_without_bars_and_name_history() {
+            let data = vec![
+                // 改名生效日不是交易日
+                stock("600000", "New", &["2025-05-06", "2025-05-02"], &[("2025-05-01", "Old"), ("2025-05-04", "New")]),
+                // 没有日线，也没有名称历史
+                stock("600001", "Listed", &[], &[]),
+                // 没有日线，但有名称历史
+                stock("600002", "B", &[], &[("2025-01-02", "A"), ("2025-03-03", "B")]),
+                // 只有一条名称历史，生效日有日线
+                stock("600003", "D", &["2025-05-06", "2025-05-02"], &[("2025-05-02", "D")]),
+                // 只有一条名称历史，没有日线
+                stock("600004", "E", &[], &[("2025-01-02", "E")]),
+                // 有日线，没有名称历史
+                stock("600005", "F", &["2025-05-06"], &[]),
+            ];
+            
+            let mut bytes = Vec::new();
+            write_stock_data_to_stream(&data, &mut bytes, &ArrowOptions::new().with_layout(TableLayout::Flat)).unwrap();
+            let mut loaded = read_stock_data_from_stream(bytes.as_slice()).unwrap();
+            loaded.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+            assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&data).unwrap());
+            
+            let mut bytes = Vec::new();
+            write_batch(&stock_data_to_flat_record_batch(&data).unwrap(), &mut bytes).unwrap();
+            let mut loaded = read_stock_data_from_memory(&bytes).unwrap();
+            loaded.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+            assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&data).unwrap());
+        }
+        
+        #[test]
+        fn reader_detects_file_and_stream_formats() {
+            let data = vec![
+                stock("600000", "New", &["2025-05-06", "2025-05-02"], &[("2025-05-01", "Old"), ("2025-05-04", "New")]),
+                stock("600001", "Listed", &[], &[]),
+            ];
+            let expected = serde_json::to_value(&data).unwrap();
+            
+            let file = stock_data_to_arrow_bytes(&data).unwrap();
+            assert!(file.starts_with(ARROW_FILE_MAGIC));
+            let loaded = read_stock_data_from_reader(file.as_slice()).unwrap();
+            assert_eq!(serde_json::to_value(&loaded).unwrap(), expected);
+            
+            let mut stream = Vec::new();
+            write_stock_data_to_stream(&data, &mut stream, &ArrowOptions::new()).unwrap();
+            assert!(!stream.starts_with(ARROW_FILE_MAGIC));
+            let loaded = read_stock_data_from_reader(stream.as_slice()).unwrap();
+            assert_eq!(serde_json::to_value(&loaded).unwrap(), expected);
+            
+            // 不足魔数长度或内容无效时返回错误
+            assert!(read_stock_data_from_reader(&b""[..]).is_err());
+            assert!(read_stock_data_from_reader(&ARROW_FILE_MAGIC[..3]).is_err());
+            assert!(read_stock_data_from_reader(&file[..file.len() / 2]).is_err());
+        }
+        
+        #[test]
+        fn stream_reader_yields_one_batch_per_chunk() {
+            let data: Vec<StockData> = (0..STREAM_BATCH_STOCKS * 2 + 1)
+                .map(|i| stock(&format!("{:06}", 600000 + i), "A", &["2025-05-06"], &[]))
+                .collect();
+            let mut bytes = Vec::new();
+            write_stock_data_to_stream(&data, &mut bytes, &ArrowOptions::new()).unwrap();
+            
+            let batches = StockDataStreamReader::try_new(bytes.as_slice()).unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![STREAM_BATCH_STOCKS, STREAM_BATCH_STOCKS, 1]);
+            let loaded: Vec<StockData> = batches.into_iter().flatten().collect();
+            assert_eq!(serde_json::to_value(&loaded).unwrap(), serde_json::to_value(&data).unwrap());
+            
+            // 扁平布局读完整个流后一次返回
+            let mut bytes = Vec::new();
+            write_stock_data_to_stream(&data, &mut bytes, &ArrowOptions::new().with_layout(TableLayout::Flat)).unwrap();
+            let batches = StockDataStreamReader::try_new(bytes.as_slice()).unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(batches.len(), 1);
+            assert_eq!(batches[0].len(), data.len());
+        }
+    }
+}
+
+// 文件写入工具
+pub mod fs_utils {
+    use super::*;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    /// 数据文件默认保留的备份数量
+    pub const DEFAULT_BACKUPS: usize = 3;
+
+    /// 第 n 个备份的路径，1 为最新，如 `stock.arrow.bak1`
+    pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".bak{}", n));
+        PathBuf::from(name)
+    }
+    
+    /// 按从新到旧的顺序列出现有备份
+    pub fn list_backups(path: &Path) -> Result<Vec<(usize, PathBuf)>> {
+        let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let prefix = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => format!("{}.bak", name),
+            None => return Ok(Vec::new()),
+        };
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let n = name.to_str()
+                .and_then(|n| n.strip_prefix(&prefix))
+                .and_then(|n| n.parse::<usize>().ok());
+            if let Some(n) = n {
+                backups.push((n, backup_path(path, n)));
+            }
+        }
+        backups.sort();
+        
+        Ok(backups)
+    }
+    
+    fn temp_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".tmp{}", std::process::id()));
+        PathBuf::from(name)
+    }
+    
+    /// 原子写入文件
+    ///
+    /// 先写入同目录下的临时文件并同步到磁盘，`validate` 通过后轮换备份，再重命名覆盖原文件。
+    /// 任何一步失败都不会影响原文件。
+    pub fn write_atomic(
+        path: &Path,
+        backups: usize,
+        write: impl FnOnce(&mut File) -> Result<()>,
+        validate: impl FnOnce(&Path) -> Result<()>,
+    ) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        
+        let tmp = temp_path(path);
+        let result = File::create(&tmp)
+            .map_err(DataHubError::from)
+            .and_then(|mut file| {
+                write(&mut file)?;
+                file.sync_all()?;
+                Ok(())
+            })
+            .and_then(|_| validate(&tmp));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        
+        if backups > 0 && path.exists() {
+            rotate_backups(path, backups)?;
+        }
+        fs::rename(&tmp, path)?;
+        
+        Ok(())
+    }
+    
+    // 备份依次后移，最旧的被删除，当前文件成为第一个备份
+    fn rotate_backups(path: &Path, backups: usize) -> Result<()> {
+        let oldest = backup_path(path, backups);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for n in (1..backups).rev() {
+            let from = backup_path(path, n);
+            if from.exists() {
+                fs::rename(&from, backup_path(path, n + 1))?;
+            }
+        }
+        
+        // 硬链接保留原文件内容，随后的重命名不会出现原文件缺失的窗口
+        let first = backup_path(path, 1);
+        if fs::hard_link(path, &first).is_err() {
+            fs::copy(path, &first)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// 用第 n 个备份原子替换文件，被替换的文件按 `backups` 轮换为第一个备份
+    ///
+    /// `validate` 校验写入的副本，失败时不影响原文件和备份。
+    pub fn restore_backup(path: &Path, n: usize, backups: usize, validate: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+        let backup = backup_path(path, n);
+        if !backup.exists() {
+            return Err(DataHubError::DataError(format!("Backup not found: {}", backup.display())));
+        }
+        
+        // 轮换会移动备份，先读出内容
+        let bytes = fs::read(&backup)?;
+        write_atomic(path, backups, |file| Ok(file.write_all(&bytes)?), validate)
+    }
+}
+
+// JSON / NDJSON 数据转换工具
+pub mod json_utils {
+    use super::*;
+    use serde::Serialize;
+    use std::fs::File;
+    use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+    use std::path::Path;
+    use std::str::FromStr;
+
+    /// 当前 JSON 导出格式版本，结构不兼容变化时递增
+    pub const JSON_FORMAT_VERSION: u32 = 1;
+    
+    const FORMAT_NAME: &str = "egostrategy_datahub";
+
+    /// JSON 导出格式
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum JsonFormat {
+        /// 单个 JSON 文档：`{"format", "version", "stocks": [...]}`
+        Json,
+        /// 首行为版本头，之后每行一只股票
+        Ndjson,
+    }
+
+    impl JsonFormat {
+        /// 根据扩展名推断格式，.ndjson/.jsonl 为 NDJSON，其余为 JSON
+        pub fn from_path(path: &str) -> Self {
+            match Path::new(path).extension().and_then(|e| e.to_str()) {
+                Some(ext) if ext.eq_ignore_ascii_case("ndjson") || ext.eq_ignore_ascii_case("jsonl") => JsonFormat::Ndjson,
+                _ => JsonFormat::Json,
+            }
+        }
+    }
+
+    impl FromStr for JsonFormat {
+        type Err = DataHubError;
+
+        fn from_str(s: &str) -> Result<Self> {
+            match s.to_ascii_lowercase().as_str() {
+                "json" => Ok(JsonFormat::Json),
+                "ndjson" | "jsonl" => Ok(JsonFormat::Ndjson),
+                _ => Err(DataHubError::DataError(format!("Unknown JSON format: {}", s))),
+            }
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Header<'a> {
+        format: &'a str,
+        version: u32,
+    }
+
+    #[derive(Serialize)]
+    struct Document<'a> {
+        format: &'a str,
+        version: u32,
+        stocks: &'a [StockData],
+    }
+
+    fn check_version(version: u64) -> Result<()> {
+        let version = u32::try_from(version).map_err(|_| DataHubError::DataError(format!(
+            "Invalid JSON format version {}", version
+        )))?;
+        if version > JSON_FORMAT_VERSION {
+            return Err(DataHubError::DataError(format!(
+                "Unsupported JSON format version {} (max supported {})", version, JSON_FORMAT_VERSION
+            )));
+        }
+        Ok(())
+    }
+
+    // 读入后补全旧数据缺少的前收盘价
+    fn finish_import(mut data: Vec<StockData>) -> Vec<StockData> {
+        for stock in &mut data {
+            fill_pre_close(&mut stock.daily);
+        }
+        data
+    }
+
+    /// 写出带版本号的 JSON 文档
+    pub fn write_json<W: Write>(data: &[StockData], mut writer: W) -> Result<()> {
+        let document = Document { format: FORMAT_NAME, version: JSON_FORMAT_VERSION, stocks: data };
+        serde_json::to_writer(&mut writer, &document)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// 读取 JSON 文档，也接受无版本的股票数组
+    pub fn read_json<R: Read>(reader: R) -> Result<Vec<StockData>> {
+        let data = match serde_json::from_reader(reader)? {
+            // 兼容直接序列化 Vec<StockData> 得到的无版本数组
+            serde_json::Value::Array(stocks) => serde_json::from_value(serde_json::Value::Array(stocks))?,
+            serde_json::Value::Object(mut document) => {
+                let version = document.get("version")
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| DataHubError::DataError("Missing or invalid JSON format version".to_string()))?;
+                check_version(version)?;
+                let stocks = document.remove("stocks")
+                    .ok_or_else(|| DataHubError::DataError("Missing stocks in JSON document".to_string()))?;
+                serde_json::from_value(stocks)?
+            }
+            _ => return Err(DataHubError::DataError("Expected a JSON document or an array of stocks".to_string())),
+        };
+        Ok(finish_import(data))
+    }
+
+    /// 写出 NDJSON：首行为版本头，之后每行一只股票
+    pub fn write_ndjson<W: Write>(data: &[StockData], mut writer: W) -> Result<()> {
+        serde_json::to_writer(&mut writer, &Header { format: FORMAT_NAME, version: JSON_FORMAT_VERSION })?;
+        writer.write_all(b"\n")?;
+        for stock in data {
+            serde_json::to_writer(&mut writer, stock)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// 读取 NDJSON，版本头可省略，空行会被跳过
+    pub fn read_ndjson<R: BufRead>(reader: R) -> Result<Vec<StockData>> {
+        let mut data = Vec::new();
+        
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = |e: serde_json::Error| DataHubError::DataError(format!("Invalid NDJSON at line {}: {}", index + 1, e));
+            
+            let value: serde_json::Value = serde_json::from_str(&line).map_err(invalid)?;
+            if data.is_empty() && value.get("symbol").is_none() {
+                if let Some(version) = value.get("version").and_then(|v| v.as_u64()) {
+                    check_version(version)?;
+                    continue;
+                }
+            }
+            data.push(serde_json::from_value(value).map_err(invalid)?);
+        }
+        
+        Ok(finish_import(data))
+    }
+
+    /// 按指定格式将股票数据保存到文件
+    pub fn save_stock_data_to_json(data: &[StockData], path: &str, format: JsonFormat) -> Result<()> {
+        info!("Exporting {} stocks to {}", data.len(), path);
+        let writer = BufWriter::new(File::create(path)?);
+        match format {
+            JsonFormat::Json => write_json(data, writer),
+            JsonFormat::Ndjson => write_ndjson(data, writer),
+        }
+    }
+
+    /// 按指定格式从文件读取股票数据
+    pub fn read_stock_data_from_json(path: &str, format: JsonFormat) -> Result<Vec<StockData>> {
+        let reader = BufReader::new(File::open(path)?);
+        match format {
+            JsonFormat::Json => read_json(reader),
+            JsonFormat::Ndjson => read_ndjson(reader),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::models::price::Price;
+        
+        const BAR: &str = r#"{"date":"2025-05-16","open":10.0,"high":10.5,"low":9.8,"close":10.2,"volume":1000,"amount":10200}"#;
+        
+        fn document(version: &str, daily: &str) -> String {
+            format!(r#"{{"format":"egostrategy_datahub","version":{},"stocks":[{{"exchange":"SSE","symbol":"600000","name":"A","daily":[{}]}}]}}"#, version, daily)
+        }
+        
+        #[test]
+        fn optional_daily_fields_default() {
+            let data = read_json(document("1", BAR).as_bytes()).unwrap();
+            let daily = &data[0].daily[0];
+            assert_eq!(daily.close, Price::from_f64(10.2));
+            assert_eq!(daily.turnover_rate, 0.0);
+        }
+        
+        #[test]
+        fn missing_core_daily_fields_are_rejected() {
+            for field in ["date", "open", "close", "volume"] {
+                let mut bar: serde_json::Map<String, serde_json::Value> = serde_json::from_str(BAR).unwrap();
+                bar.remove(field);
+                let bar = serde_json::to_string(&bar).unwrap();
+                assert!(read_json(document("1", &bar).as_bytes()).is_err(), "missing {} was accepted", field);
+                
+                let ndjson = format!(r#"{{"exchange":"SSE","symbol":"600000","name":"A","daily":[{}]}}"#, bar);
+                assert!(read_ndjson(ndjson.as_bytes()).is_err(), "missing {} was accepted in NDJSON", field);
+            }
+        }
+        
+        #[test]
+        fn oversized_version_is_rejected() {
+            let version = (u32::MAX as u64 + 2).to_string();
+            assert!(read_json(document(&version, BAR).as_bytes()).is_err());
+            
+            let ndjson = format!("{{\"format\":\"egostrategy_datahub\",\"version\":{}}}\n", version);
+            assert!(read_ndjson(ndjson.as_bytes()).is_err());
+        }
     }
 }